@@ -1,99 +1,112 @@
 #import bevy_pbr::forward_io::VertexOutput
 
 @group(2) @binding(0) var<uniform> atmosphere: AtmosphereUniform;
+@group(2) @binding(1) var transmittance_lut: texture_2d<f32>;
+@group(2) @binding(2) var transmittance_sampler: sampler;
+@group(2) @binding(3) var multi_scattering_lut: texture_2d<f32>;
+@group(2) @binding(4) var multi_scattering_sampler: sampler;
 
-// atmosphere paramaters
+// atmosphere paramaters, km and per km
+// same as AtmosphereParams in atmosphere.rs, which builds the lookup tables
 struct AtmosphereUniform {
     sun_direction: vec3<f32>,
-    camera_position: vec3<f32>,
-    rayleigh_coeff: vec3<f32>,
-    mie_coeff: f32,
     sun_intensity: f32,
-    atmosphere_radius: f32,
-    _padding: f32,
+    camera_position: vec3<f32>,
+    bottom_radius: f32,
+    rayleigh_scattering: vec3<f32>,
+    top_radius: f32,
+    ozone_absorption: vec3<f32>,
+    rayleigh_scale_height: f32,
+    mie_scattering: f32,
+    mie_extinction: f32,
+    mie_scale_height: f32,
+    mie_g: f32,
+    ozone_center: f32,
+    ozone_width: f32,
+    _padding: vec2<f32>,
 }
 
-// ray-marching config
-const SAMPLE_COUNT: i32 = 64;
-const EARTH_RADIUS: f32 = 6378.0;
+// ray-marching config, only the view ray is marched
+const SAMPLE_COUNT: i32 = 32;
+const PI: f32 = 3.14159265;
 
 // phase functions for scattering
 fn rayleigh_phase(cos_theta: f32) -> f32 {
-    return 0.75 * (1.0 + cos_theta * cos_theta);
+    return 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
 }
 
 fn mie_phase(cos_theta: f32, g: f32) -> f32 {
-    // Henvey-Greenstein phase function
+    // Henyey-Greenstein phase function
     // https://www.oceanopticsbook.info/view/scattering/level-2/the-henyey-greenstein-phase-function
     let g2 = g * g;
-    let denom = 1.0 + g2 - 2.0 * g2 * cos_theta;
-    return (1.0 - g2) / (4.0 * 3.14159 * pow(denom, 1.5));
+    let denom = 1.0 + g2 - 2.0 * g * cos_theta;
+    return (1.0 - g2) / (4.0 * PI * pow(denom, 1.5));
 }
 
-// camera ray sphere intesersection
-// return distance or -1 if no hit
-fn compute_ray_sphere_hit(ray_origin: vec3<f32>, ray_dir: vec3<f32>, center: vec3<f32>, radius: f32) -> f32 {
-    let oc = ray_origin - center;
-    let a = dot(ray_dir, ray_dir);
-    let b = 2.0 * dot(oc, ray_dir);
-    let c = dot(oc, oc) - radius * radius;
-    let discriminant = b * b - 4.0 * a * c;
+// ray sphere intersection around the planet centre
+// returns near and far distances, or -1 for both if missed
+fn ray_sphere(ray_origin: vec3<f32>, ray_dir: vec3<f32>, radius: f32) -> vec2<f32> {
+    let b = dot(ray_origin, ray_dir);
+    let c = dot(ray_origin, ray_origin) - radius * radius;
+    let discriminant = b * b - c;
 
     if discriminant < 0.0 {
-        return -1.0; // no intersect
+        return vec2<f32>(-1.0); // no intersect
     }
 
-    let t1 = (-b - sqrt(discriminant)) / (2.0 * a);
-    let t2 = (-b + sqrt(discriminant)) / (2.0 * a);
+    let root = sqrt(discriminant);
+    return vec2<f32>(-b - root, -b + root);
+}
 
-    // return nearest positive intersection
-    if t1 > 0.0 {
-        return t1;
-    } else if t2 > 0.0 {
-        return t2;
-    }
+// extinction and scattering at an altitude
+struct Medium {
+    rayleigh: vec3<f32>,
+    mie: f32,
+    extinction: vec3<f32>,
+}
 
-    return -1.0;
+fn sample_medium(altitude: f32) -> Medium {
+    let h = max(altitude, 0.0);
+    let rayleigh_density = exp(-h / atmosphere.rayleigh_scale_height);
+    let mie_density = exp(-h / atmosphere.mie_scale_height);
+    let ozone_density = max(0.0, 1.0 - abs(h - atmosphere.ozone_center) / (atmosphere.ozone_width * 0.5));
+
+    var medium: Medium;
+    medium.rayleigh = atmosphere.rayleigh_scattering * rayleigh_density;
+    medium.mie = atmosphere.mie_scattering * mie_density;
+    medium.extinction = medium.rayleigh
+        + vec3<f32>(atmosphere.mie_extinction * mie_density)
+        + atmosphere.ozone_absorption * ozone_density;
+    return medium;
 }
 
-// simple density function
-fn atmosphere_density(position: vec3<f32>) -> f32 {
-    // let altitude = length(position) - EARTH_RADIUS;
-    let altitude = max(0.0, length(position) - EARTH_RADIUS);
-    let max_altitude = atmosphere.atmosphere_radius - EARTH_RADIUS + 100.0;
+// transmittance to the top of the atmosphere, see transmittance_r_mu_to_uv in atmosphere.rs
+fn sample_transmittance(r: f32, mu: f32) -> vec3<f32> {
+    let bottom = atmosphere.bottom_radius;
+    let top = atmosphere.top_radius;
 
-    if altitude < -1.0 || altitude > max_altitude {
-        return 0.0;
+    // the ground is in the way
+    let ground_discriminant = r * r * (mu * mu - 1.0) + bottom * bottom;
+    if mu < 0.0 && ground_discriminant >= 0.0 {
+        return vec3<f32>(0.0);
     }
 
-    let scale_height = 8000.0; // km, controls exponential dropoff
-    let exp_falloff = exp(-altitude / scale_height);
-    let fade_to_zero = clamp(1.0 - (altitude / max_altitude), 0.0, 1.0);
+    let h = sqrt(top * top - bottom * bottom);
+    let rho = sqrt(max(r * r - bottom * bottom, 0.0));
+    let discriminant = r * r * (mu * mu - 1.0) + top * top;
+    let d = max(0.0, -r * mu + sqrt(max(discriminant, 0.0)));
+    let d_min = top - r;
+    let d_max = rho + h;
+    let uv = vec2<f32>((d - d_min) / (d_max - d_min), rho / h);
 
-    return exp_falloff * fade_to_zero;
+    return textureSampleLevel(transmittance_lut, transmittance_sampler, uv, 0.0).rgb;
 }
 
-// calculate how much light get;s abosrbed along a path
-// optical depth
-fn compute_optical_depth(start_pos: vec3<f32>, end_pos: vec3<f32>, steps: i32) -> vec2<f32> {
-    let ray_dir = end_pos - start_pos;
-    let step_size = length(ray_dir) / f32(steps);
-    let step_dir = normalize(ray_dir);
-
-    var rayleigh_depth = 0.0;
-    var mie_depth = 0.0;
-
-    // TODO: depth and sun should determine color
-    for (var i = 0; i < steps; i++) {
-        let t = (f32(i) + 0.5) * step_size;
-        let sample_pos = start_pos * step_dir * t;
-        let density = atmosphere_density(sample_pos);
-
-        rayleigh_depth += density * step_size;
-        mie_depth += density * step_size;
-    }
-
-    return vec2<f32>(rayleigh_depth, mie_depth);
+// isotropic multiple scattering, see multi_scattering_uv_to_r_mu in atmosphere.rs
+fn sample_multi_scattering(r: f32, mu_sun: f32) -> vec3<f32> {
+    let altitude = (r - atmosphere.bottom_radius) / (atmosphere.top_radius - atmosphere.bottom_radius);
+    let uv = clamp(vec2<f32>(mu_sun * 0.5 + 0.5, altitude), vec2<f32>(0.0), vec2<f32>(1.0));
+    return textureSampleLevel(multi_scattering_lut, multi_scattering_sampler, uv, 0.0).rgb;
 }
 
 @fragment
@@ -105,54 +118,52 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
     let ray_dir = normalize(world_pos - camera_pos);
     let ray_origin = camera_pos;
 
-    // check if ray hits earth
-    // for termination since I don't want to deal with layers
-    let earth_distance = compute_ray_sphere_hit(ray_origin, ray_dir, vec3<f32>(0.0), EARTH_RADIUS);
-
-    // determine how far to march
-    // either hit the earth or until atmosphere becomes negligible
-    let max_march_distance = select(atmosphere.atmosphere_radius * 10.0, earth_distance, earth_distance > 0.0);
+    // the part of the ray inside the atmosphere, cut short by the planet
+    let atmosphere_hit = ray_sphere(ray_origin, ray_dir, atmosphere.top_radius);
+    if atmosphere_hit.y < 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let start = max(atmosphere_hit.x, 0.0);
+    var end = atmosphere_hit.y;
+    let ground_hit = ray_sphere(ray_origin, ray_dir, atmosphere.bottom_radius);
+    if ground_hit.x > 0.0 {
+        end = min(end, ground_hit.x);
+    }
 
     // ray marching
-    let step_size = max_march_distance / f32(SAMPLE_COUNT);
+    let step_size = (end - start) / f32(SAMPLE_COUNT);
     let sun_dir = normalize(atmosphere.sun_direction);
+    let cos_theta = dot(ray_dir, sun_dir); // angle between view and sun
+    let rayleigh_phase_value = rayleigh_phase(cos_theta);
+    let mie_phase_value = mie_phase(cos_theta, atmosphere.mie_g);
 
-    var rayleigh_sum = vec3<f32>(0.0);
-    var mie_sum = vec3<f32>(0.0);
-    var total_density_encountered = 0.0;
+    var luminance = vec3<f32>(0.0);
+    var throughput = vec3<f32>(1.0);
 
     // march along ray
     for (var i = 0; i < SAMPLE_COUNT; i++) {
-        let t = f32(i) * step_size + step_size * 0.5; // sample middle of each step
+        let t = start + (f32(i) + 0.5) * step_size; // sample middle of each step
         let sample_pos = ray_origin + ray_dir * t;
-
-        // get atmospheric density at this point
-        let density = atmosphere_density(sample_pos);
-        if density < 0.00001 { continue; } // skip thin atmosphere
-
-        total_density_encountered += density;
-
-        // calculate scattering contributions
-        let cos_theta = dot(ray_dir, sun_dir); // angle between view and sun
-        let rayleigh_scatter = atmosphere.rayleigh_coeff * density * rayleigh_phase(cos_theta);
-        
-        // mie
-        let g = 0.76;
-        let mie_scatter = vec3<f32>(atmosphere.mie_coeff) * density * mie_phase(cos_theta, g);
-
-        // calculate how much sunlight reaches this point
-        let sun_attenuation = clamp(dot(normalize(sample_pos), sun_dir), 0.005, 1.0);
-
-        rayleigh_sum += rayleigh_scatter * sun_attenuation * step_size;
-        mie_sum += mie_scatter * sun_attenuation * step_size;
+        let r = length(sample_pos);
+        let mu_sun = dot(sample_pos / r, sun_dir);
+
+        let medium = sample_medium(r - atmosphere.bottom_radius);
+        let extinction = max(medium.extinction, vec3<f32>(1e-9));
+        let step_transmittance = exp(-extinction * step_size);
+
+        // single scattering of direct sunlight, plus everything scattered more than once
+        let sun_transmittance = sample_transmittance(r, mu_sun);
+        let scattering = medium.rayleigh + vec3<f32>(medium.mie);
+        let single = sun_transmittance * (medium.rayleigh * rayleigh_phase_value + vec3<f32>(medium.mie * mie_phase_value));
+        let multiple = sample_multi_scattering(r, mu_sun) * scattering;
+        let in_scattered = (single + multiple) * atmosphere.sun_intensity;
+
+        // analytic integral over the step
+        luminance += throughput * (in_scattered - in_scattered * step_transmittance) / extinction;
+        throughput *= step_transmittance;
     }
 
-    // combine scattering effects
-    let final_color = (rayleigh_sum + mie_sum) * atmosphere.sun_intensity;
-    
-    // calculate alpha baed on total scattering
-    // let alpha = min(1.0, (rayleigh_sum.r + rayleigh_sum.g + rayleigh_sum.b + mie_sum.r) * 0.5);
-    let alpha = min(1.0, total_density_encountered * 0.01);
-
-    return vec4<f32>(final_color, alpha);
-}
\ No newline at end of file
+    // premultiplied: add the scattered light, dim whatever is behind by the transmittance
+    let alpha = 1.0 - dot(throughput, vec3<f32>(1.0 / 3.0));
+    return vec4<f32>(luminance, alpha);
+}
@@ -3,16 +3,26 @@
 @group(2) @binding(0) var cloud_texture: texture_2d<f32>;
 @group(2) @binding(1) var cloud_sampler: sampler;
 @group(2) @binding(2) var<uniform> sun_uniform: SunUniform;
-@group(2) @binding(3) var<uniform> cloud_opacity: f32;
+@group(2) @binding(3) var<uniform> cloud_uniform: CloudUniform;
+@group(2) @binding(4) var next_cloud_texture: texture_2d<f32>;
+@group(2) @binding(5) var next_cloud_sampler: sampler;
 
 struct SunUniform {
    sun_direction: vec3<f32>,
    _padding: f32,
 }
 
+struct CloudUniform {
+   opacity: f32,
+   blend: f32, // cross-fade between dated frames
+   _padding: vec2<f32>,
+}
+
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-   let color = textureSample(cloud_texture, cloud_sampler, in.uv);
+   let current = textureSample(cloud_texture, cloud_sampler, in.uv);
+   let next = textureSample(next_cloud_texture, next_cloud_sampler, in.uv);
+   let color = mix(current, next, cloud_uniform.blend);
 
    // use brightness as alpha
    let alpha = (color.r + color.g + color.b) / 3.0;
@@ -31,5 +41,5 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
    // darken clouds at night
    let surface_light = max(0.0, sun_uniform.sun_direction.y * 0.5 + 0.5);
    
-   return vec4<f32>(final_color * surface_light, alpha * cloud_opacity);
+   return vec4<f32>(final_color * surface_light, alpha * cloud_uniform.opacity);
 }
\ No newline at end of file
@@ -11,13 +11,26 @@
 @group(2) @binding(8) var normal_map: texture_2d<f32>;
 @group(2) @binding(9) var normal_map_sampler: sampler;
 @group(2) @binding(10) var<uniform> sun_uniform: SunUniform;
+@group(2) @binding(11) var detail_day: texture_2d<f32>;
+@group(2) @binding(12) var detail_day_sampler: sampler;
+@group(2) @binding(13) var detail_night: texture_2d<f32>;
+@group(2) @binding(14) var detail_night_sampler: sampler;
+@group(2) @binding(15) var<uniform> imagery: ImageryUniform;
+@group(2) @binding(16) var<uniform> layers: LayerUniform;
 
 struct SunUniform {
     sun_direction: vec3<f32>,
     _padding: f32, // 16-byte alignment
 };
 
-const PI: f32 = 3.14159265;
+struct ImageryUniform {
+    uv_rect: vec4<f32>, // u, v, width, height covered by the detail textures
+};
+
+struct LayerUniform {
+    night_lights: f32, // 0 or 1, set from the settings panel
+    _padding: vec3<f32>,
+};
 
 // desaturate a color
 fn desaturate(color: vec3<f32>, factor: f32) -> vec3<f32> {
@@ -25,57 +38,38 @@ fn desaturate(color: vec3<f32>, factor: f32) -> vec3<f32> {
     return mix(color, vec3<f32>(gray), factor);
 }
 
-// calculate tangent space vectors for spherical surfaces
-fn calculate_sphere_tangent_space(world_pos: vec3<f32>, uv: vec2<f32>) -> mat3x3<f32> {
-    // normalize position to get point on unit sphere
-    let point_on_sphere = normalize(world_pos);
-
-    let longitude = (uv.x * 2.0 - 1.0) * PI;
-    let latitude = (0.5 - uv.y) * PI;
-
-    // calculate tangent
-    // direction of increasing longitude
-    let tangent = vec3<f32>(
-        -sin(longitude),
-        0.0,
-        cos(longitude)
-    );
-
-    // calculate bitangent
-    // direction of increasing latitude
-    let bitangent = vec3<f32>(
-        -cos(longitude) * sin(latitude),
-        cos(latitude),
-        -sin(longitude) * sin(latitude)
-    );
-
-    let normal = point_on_sphere;
-
-    return mat3x3<f32>(
-        normalize(tangent),
-        normalize(bitangent),
-        normal
-    );
+// tangent frame on the surface: east, north, up
+// the normal map is generated in this space, see normal.rs
+fn surface_tangent_frame(in: VertexOutput) -> mat3x3<f32> {
+    let up = normalize(in.world_normal);
+
+    // mesh tangents follow increasing u, i.e. east
+#ifdef VERTEX_TANGENTS
+    var east = in.world_tangent.xyz - up * dot(in.world_tangent.xyz, up);
+#else
+    var east = cross(vec3<f32>(0.0, 1.0, 0.0), up);
+#endif
+    // degenerate at the poles, any horizontal direction will do
+    if (length(east) < 1e-4) {
+        east = cross(up, vec3<f32>(1.0, 0.0, 0.0));
+    }
+    east = normalize(east);
+    let north = cross(up, east);
+
+    return mat3x3<f32>(east, north, up);
 }
 
 // sample and decode normal
-fn sample_normal_map_sphere(uv: vec2<f32>, world_pos: vec3<f32>) -> vec3<f32> {
-    // get the mesh normal
-    let mesh_normal = normalize(world_pos);
-
-    // sample normal map
-    let normal_sample = textureSample(normal_map, normal_map_sampler, uv).rgb;
-    
-    let detail_normal = normal_sample * 2.0 - 1.0;
-    let tbn_matrix = calculate_sphere_tangent_space(world_pos, uv);
-    
-    // transform detail normal to world space
-    let world_detail_normal = tbn_matrix * detail_normal;
+fn sample_normal_map_sphere(in: VertexOutput, uv: vec2<f32>) -> vec3<f32> {
+    // sample normal map, stored linear in 0..1
+    let detail_normal = textureSample(normal_map, normal_map_sampler, uv).rgb * 2.0 - 1.0;
 
     // u can control normal strength here
-    let blended_normal = normalize(mesh_normal + world_detail_normal * 0.32);
-    
-    return blended_normal;
+    let strength = 0.32;
+    let tilted = vec3<f32>(detail_normal.xy * strength, detail_normal.z);
+
+    // transform detail normal to world space
+    return normalize(surface_tangent_frame(in) * tilted);
 }
 
 // calculate specular value at a given position
@@ -96,13 +90,23 @@ fn calculate_specular(
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    // sample textures
-    let day_color = textureSample(day_texture, day_sampler, in.uv);
-    let night_color = textureSample(night_texture, night_sampler, in.uv).rgb * vec3<f32>(0.94, 0.78, 0.67);
-    let mask_value = textureSample(ocean_mask, ocean_mask_sampler, in.uv).r;
-    let specular_value = textureSample(specular_map, specular_map_sampler, in.uv).r;
-
-    let world_normal = sample_normal_map_sphere(in.uv, in.world_position.xyz);
+    // mesh u is continuous across the antimeridian and can leave 0..1, wrap it here
+    let uv = vec2<f32>(fract(in.uv.x), in.uv.y);
+
+    // streamed tiles for this chunk, mesh uv is unwrapped the same way as uv_rect
+    let detail_uv = (in.uv - imagery.uv_rect.xy) / imagery.uv_rect.zw;
+    let day_detail = textureSample(detail_day, detail_day_sampler, detail_uv);
+    let night_detail = textureSample(detail_night, detail_night_sampler, detail_uv);
+
+    // sample textures, tiles replace the global imagery wherever they exist
+    let day_global = textureSample(day_texture, day_sampler, uv);
+    let day_color = vec4<f32>(mix(day_global.rgb, day_detail.rgb, day_detail.a), day_global.a);
+    let night_global = textureSample(night_texture, night_sampler, uv).rgb;
+    let night_color = mix(night_global, night_detail.rgb, night_detail.a) * vec3<f32>(0.94, 0.78, 0.67) * layers.night_lights;
+    let mask_value = textureSample(ocean_mask, ocean_mask_sampler, uv).r;
+    let specular_value = textureSample(specular_map, specular_map_sampler, uv).r;
+
+    let world_normal = sample_normal_map_sphere(in, uv);
 
     // calculate lighing vectors
     let light_dir = normalize(sun_uniform.sun_direction);
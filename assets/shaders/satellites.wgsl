@@ -0,0 +1,51 @@
+#import bevy_pbr::mesh_view_bindings::{view, lights}
+
+// instanced satellite renderer
+// one shared sphere mesh, per-instance position/size/colour from a vertex buffer
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) i_pos_scale: vec4<f32>, // xyz world position, w radius (km)
+    @location(4) i_color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+// satellites never shrink below this many pixels across
+const MIN_PIXEL_SIZE: f32 = 3.0;
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let center = vertex.i_pos_scale.xyz;
+
+    // world units per pixel at this distance
+    // clip_from_view[1][1] is the vertical focal length for perspective projections
+    let distance = length(center - view.world_position);
+    let world_per_pixel = 2.0 * distance / (view.clip_from_view[1][1] * view.viewport.w);
+    let radius = max(vertex.i_pos_scale.w, MIN_PIXEL_SIZE * 0.5 * world_per_pixel);
+
+    let position = vertex.position * radius + center;
+
+    var out: VertexOutput;
+    // instance positions are already in world space
+    out.clip_position = view.clip_from_world * vec4<f32>(position, 1.0);
+    out.color = vertex.i_color;
+    out.normal = vertex.normal;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // simple lambert against the sun, with a floor so the night side stays visible
+    let sun_dir = normalize(lights.directional_lights[0].direction_to_light);
+    let diffuse = max(dot(normalize(in.normal), sun_dir), 0.0);
+    let lighting = 0.35 + 0.65 * diffuse;
+
+    return vec4<f32>(in.color.rgb * lighting, in.color.a);
+}
@@ -5,22 +5,51 @@
 
 // Earth measurements (in km)
 pub const EARTH_RADIUS: f32 = 6378.0;
-pub const ATMOSPHERE_RADIUS: f32 = 7000.0;
+pub const EARTH_POLAR_RADIUS: f32 = 6356.752; // WGS84, label occlusion uses the ellipsoid, the globe is drawn as a sphere
+pub const ATMOSPHERE_RADIUS: f32 = 6478.0; // top of the atmosphere, ~100km up
 pub const CLOUD_RADIUS: f32 = 6478.0;
 pub const DISPLACEMENT_SCALE: f32 = 80.0; // maximum terrain height
+pub const HEIGHTMAP_BICUBIC: bool = true; // bicubic displacement sampling, bilinear if false
+pub const EARTH_MU: f64 = 398600.4418; // gravitational parameter, km^3/s^2
 
-// Atmospheric scattering parameters
-// based on values from https://www.scratchapixel.com/lessons/procedural-generation-virtual-worlds/simulating-sky/simulating-colors-of-the-sky.html
-pub const RAYLEIGH_COEFF: [f32; 3] = [5.8e-6, 13.5e-6, 33.1e-6]; // RGB wavelengths
-pub const MIE_COEFF: f32 = 210.0e-5;
-pub const SUN_INTENSITY: f32 = 10.0;
+// Terrain level-of-detail
+pub const TERRAIN_CHUNK_RESOLUTION: u32 = 48; // vertices per chunk edge
+pub const TERRAIN_MAX_DEPTH: u32 = 6; // quadtree levels below the 24 root chunks
+pub const TERRAIN_SPLIT_FACTOR: f32 = 2.0; // split when camera is closer than chunk size * this
+pub const TERRAIN_SKIRT_DEPTH: f32 = 20.0; // km, hides cracks between LOD levels
+
+// Tiled imagery (optional), slippy-map directories of Web Mercator tiles: {z}/{x}/{y}.png
+// streamed into LOD chunks for close-ups, the global textures are used wherever tiles are missing
+pub const IMAGERY_DAY_TILES: &str = "assets/tiles/day";
+pub const IMAGERY_NIGHT_TILES: &str = "assets/tiles/night";
+pub const IMAGERY_TILES_TMS: bool = false; // rows numbered from the south (TMS) instead of the north (XYZ)
+pub const IMAGERY_MIN_DEPTH: u32 = 2; // LOD depth at which chunks start loading tiles
+pub const IMAGERY_CHUNK_TEXTURE_SIZE: u32 = 512; // detail texture resolution per chunk
 
-// Rotation speeds
-pub const EARTH_ROTATION_SPEED: f32 = 0.00005; // radians, dont touch
+// Atmospheric scattering parameters, per km at sea level
+// earth values from Hillaire 2020 (https://sebh.github.io/publications/egsr2020.pdf), see atmosphere.rs
+pub const RAYLEIGH_COEFF: [f32; 3] = [5.802e-3, 13.558e-3, 33.1e-3]; // RGB wavelengths
+pub const RAYLEIGH_SCALE_HEIGHT: f32 = 8.0; // km, density falls off by e over this
+pub const MIE_COEFF: f32 = 3.996e-3; // scattering
+pub const MIE_EXTINCTION: f32 = 4.44e-3; // scattering + absorption
+pub const MIE_SCALE_HEIGHT: f32 = 1.2; // km
+pub const MIE_G: f32 = 0.8; // haze forward scattering
+pub const OZONE_ABSORPTION: [f32; 3] = [0.650e-3, 1.881e-3, 0.085e-3]; // at the layer's peak
+pub const OZONE_CENTER_ALTITUDE: f32 = 25.0; // km
+pub const OZONE_WIDTH: f32 = 30.0; // km
+pub const GROUND_ALBEDO: f32 = 0.3; // light bounced back into the atmosphere
+pub const SUN_INTENSITY: f32 = 10.0;
 
 // Normal map generation config
-pub const USE_SAVED_NORMAL_MAP: bool = true; // change this if you want the program to generate a new normal map every time it compiles
+pub const USE_SAVED_NORMAL_MAP: bool = true; // change this if you want the program to generate a new normal map every run, it's regenerated automatically when the heightmap changes
 pub const SAVED_NORMAL_MAP_PATH: &str = "textures/normal.png";
+pub const NORMAL_MAP_16BIT: bool = false; // 16 bits per channel (saved as a 16-bit PNG), smoother shading on gentle slopes
+pub const SAVED_NORMAL_MAP_KEY_PATH: &str = "textures/normal.key"; // hash of the inputs the saved map was built from
+
+// Cloud layer
+pub const CLOUD_SEQUENCE_DIR: &str = "textures/clouds"; // dated cloud images, cross-faded by sim time (under assets/)
+pub const CLOUD_OPACITY: f32 = 0.7;
+pub const CLOUD_DRIFT_DEG_PER_HOUR: f32 = 0.0; // cloud rotation relative to the surface
 
 // Asset paths
 pub const EARTH_DIFFUSE_TEXTURE: &str = "textures/diffuse.tif";
@@ -29,4 +58,61 @@ pub const EARTH_CLOUDS_TEXTURE: &str = "textures/clouds.tif";
 pub const EARTH_OCEAN_MASK_TEXTURE: &str = "textures/ocean_mask.png";
 pub const EARTH_SPECULAR_TEXTURE: &str = "textures/specular.tif";
 
+// Vector overlays, GeoJSON (.geojson/.json) or Shapefile (.shp) with WGS84 lon/lat
+// missing files are skipped, see overlays.rs
+pub const OVERLAY_COASTLINES: &str = "assets/data/coastlines.geojson";
+pub const OVERLAY_BORDERS: &str = "assets/data/borders.geojson";
+pub const OVERLAY_CITIES: &str = "assets/data/cities.geojson"; // points, drawn as small crosses
+pub const GRATICULE_SPACING_DEG: f32 = 15.0;
+pub const OVERLAY_LIFT: f32 = 2.0; // km above the terrain, keeps lines from sinking into it
+
+// Orbit line opacity
+pub const ORBIT_ALPHA: f32 = 0.05;
+
+// Controls, see controls.rs
+pub const BINDINGS_PATH: &str = "assets/config/bindings.txt"; // overrides the default key/mouse/gamepad bindings
+pub const CAMERA_ZOOM_STEP: f32 = 0.15; // fraction of the altitude above the surface per wheel line
+pub const CAMERA_KEY_ORBIT_SPEED: f32 = 400.0; // holding an orbit key is like dragging this many px a second
+pub const CAMERA_KEY_ZOOM_SPEED: f32 = 6.0; // wheel lines a second while a zoom key is held
+pub const CAMERA_STICK_SPEED: f32 = 600.0; // px a second at full stick
+pub const CAMERA_PINCH_ZOOM: f32 = 40.0; // wheel lines per unit of touchpad pinch
+pub const GAMEPAD_DEADZONE: f32 = 0.15;
+
+// Camera modes, see camera_modes.rs
+pub const CAMERA_TRANSITION_SECONDS: f32 = 1.2; // blend between modes
+pub const OBSERVER_LOCATION: (f32, f32) = (51.48, 0.0); // lat/lon in degrees, used when the cursor isn't on the globe
+pub const OBSERVER_EYE_HEIGHT: f32 = 0.2; // km above the terrain
+
+// Camera bookmarks, see bookmarks.rs
+pub const BOOKMARKS_PATH: &str = "assets/config/bookmarks.user.json"; // yours, rewritten whenever a bookmark is saved (not tracked)
+pub const BOOKMARK_SAMPLES_PATH: &str = "assets/config/bookmarks.json"; // the starting set until you save one
+pub const FLY_TO_SECONDS: f32 = 2.5;
+pub const FLY_TO_HOP: f32 = 0.5; // how far a flight to the far side lifts out, as a fraction of the distance
+
+// Screenshots and recordings, see capture.rs
+pub const CAPTURE_DIR: &str = "captures"; // relative to the working directory
+pub const RECORD_FPS: f64 = 30.0; // a recorded frame is always this much time, whatever the render speed
+// frames are piped to this as raw rgb24 instead of saved as PNGs, {width} {height} {fps} {dir} are filled in
+// and it's split on whitespace (no quoting), e.g.
+// Some("ffmpeg -y -f rawvideo -pix_fmt rgb24 -s {width}x{height} -r {fps} -i - -pix_fmt yuv420p {dir}/recording.mp4")
+pub const RECORD_ENCODER: Option<&str> = None;
+
+// Scenario files, see scenario.rs
+pub const SCENARIO_PATH: &str = "scenarios/scenario.json"; // F5 / F8 without --scenario or --save-scenario
+pub const SCENARIO_EMBED_TLES: bool = false; // save the catalogues' TLEs into the scenario instead of their paths
+
+// Satellite labels, see labels.rs
+pub const LABEL_REFRESH_SECONDS: f32 = 0.25; // how often label text is rewritten
+pub const LABEL_GRID_CELL: f32 = 12.0; // px, overlapping labels are found on a grid this fine
+pub const LABEL_FULL_DISTANCE: f32 = 10000.0; // km from the camera, closer labels show everything
+pub const LABEL_BRIEF_DISTANCE: f32 = 25000.0; // name and altitude, past this only the name
+
+// 2D map view, see map.rs
+pub const MAP_TRACK_LIMIT: usize = 50; // every satellite gets a ground track and footprint up to this many, otherwise only the selected one
+pub const MAP_TRACK_SAMPLES: usize = 180; // points per ground track (half an orbit back, one ahead)
+pub const MAP_NIGHT_ALPHA: f32 = 0.45; // darkening of the night side
+
+// TLE catalogues, loaded from disk relative to the working directory
+pub const TLE_SOURCES: &[&str] = &["assets/data/weather.txt", "assets/data/gnss.txt"];
+
 pub const EARTH_DISPLACEMENT_TEXTURE: &str = "textures/topography.png";
\ No newline at end of file
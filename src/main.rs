@@ -8,6 +8,7 @@
 use bevy::prelude::*;
 use bevy::window::{PresentMode, WindowMode};
 use bevy::pbr::wireframe::{WireframePlugin, WireframeConfig};
+use bevy::render::view::NoIndirectDrawing;
 
 pub mod config;
 
@@ -15,6 +16,11 @@ mod systems;
 use systems::time::TimePlugin;
 use systems::camera::CameraPlugin;
 use systems::ui::UIPlugin;
+use systems::settings::SettingsPlugin;
+use systems::map::MapPlugin;
+use systems::controls::ControlsPlugin;
+use systems::capture::CapturePlugin;
+use systems::scenario::ScenarioPlugin;
 
 use systems::satellites::SatellitePlugin;
 use systems::earth::EarthPlugin;
@@ -23,12 +29,22 @@ use systems::earth::EarthPlugin;
 pub struct Sun;
 
 fn main() -> bevy::app::AppExit {
-    App::new()
+    let mut app = App::new();
+
+    // `--bench [counts]` runs the constellation frame-time benchmark and exits
+    // vsync is off while benchmarking so frame times aren't capped
+    let benchmark = systems::satellites::bench::Benchmark::from_args();
+    let present_mode = if benchmark.is_some() { PresentMode::AutoNoVsync } else { PresentMode::AutoVsync };
+    if let Some(benchmark) = benchmark {
+        app.insert_resource(benchmark);
+    }
+
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "ApogeeTrak".into(),
                 resolution: (1920.0, 1080.0).into(),
-                present_mode: PresentMode::AutoVsync,
+                present_mode,
                 mode: WindowMode::BorderlessFullscreen(bevy::window::MonitorSelection::Primary),
                 ..default()
             }),
@@ -39,14 +55,19 @@ fn main() -> bevy::app::AppExit {
         }))
         .add_plugins(WireframePlugin::default())
         .insert_resource(WireframeConfig {
-            global: false, // toggle wireframes here
+            global: false, // toggled from the settings panel
             default_color: Color::BLACK,
         })
+        .add_plugins(ControlsPlugin)
+        .add_plugins(SettingsPlugin)
         .add_plugins(TimePlugin) // IMPORTANT
         .add_plugins(CameraPlugin)
         .add_plugins(UIPlugin)
         .add_plugins(SatellitePlugin)
         .add_plugins(EarthPlugin)
+        .add_plugins(MapPlugin)
+        .add_plugins(CapturePlugin)
+        .add_plugins(ScenarioPlugin) // after TimePlugin, a --scenario replaces its start time
         .insert_resource(ClearColor(Color::BLACK)) // background color
         .add_systems(Startup, setup)
         .run()
@@ -58,6 +79,7 @@ fn setup(
     // spawn camera
     commands.spawn((
         Camera3d::default(),
+        NoIndirectDrawing, // instanced satellites issue direct draws
         Transform::from_xyz(-8000.0, 8000.0, 12000.0).looking_at(Vec3::ZERO, Vec3::Y),
         systems::camera::OrbitCamera::new(15000.0, 0.3)
             .with_target(Vec3::ZERO)
@@ -0,0 +1,349 @@
+//! bookmarks.rs
+//!
+//! Named camera views: B saves the current orbit view, H saves it together with the sim time,
+//! 1-9 fly to the first nine. they're kept in BOOKMARKS_PATH as JSON (name, position, target,
+//! up, optional RFC 3339 time) so they can be renamed or written by hand. until that file exists
+//! the samples in BOOKMARK_SAMPLES_PATH are loaded, the first save copies them over
+//!
+//! views are stored in the earth's frame, a bookmark over a ground station stays over it
+//! however far the globe has turned. the globe's turn follows the sim time, so one saved with
+//! a time comes back with the earth, sun and satellites where they were. flying there follows
+//! a great circle around the planet, lifting out a little on long hops
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+
+use crate::config::{BOOKMARKS_PATH, FLY_TO_HOP, FLY_TO_SECONDS};
+use crate::systems::camera::OrbitCamera;
+use crate::systems::camera_modes::{CameraMode, CameraModeState};
+use crate::systems::controls::{Action, Actions};
+use crate::systems::earth::Earth;
+use crate::systems::time::TimeState;
+
+const RECALL: [Action; 9] = [
+    Action::Bookmark1,
+    Action::Bookmark2,
+    Action::Bookmark3,
+    Action::Bookmark4,
+    Action::Bookmark5,
+    Action::Bookmark6,
+    Action::Bookmark7,
+    Action::Bookmark8,
+    Action::Bookmark9,
+];
+
+/// an orbit camera view, in the earth's frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub target: Vec3,
+    pub rotation: Quat,
+    pub radius: f32,
+    pub sim_time: Option<DateTime<Utc>>,
+}
+
+impl Bookmark {
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let vector = |key: &str| {
+            let values = json.get(key)?.as_array()?;
+            let values: Vec<f32> = values.iter().filter_map(|v| v.as_f64().map(|v| v as f32)).collect();
+            (values.len() == 3).then(|| Vec3::from_slice(&values))
+        };
+        let name = json.get("name")?.as_str()?.to_string();
+        let position = vector("position")?;
+        let target = vector("target").unwrap_or(Vec3::ZERO);
+        let up = vector("up").unwrap_or(Vec3::Y);
+        let sim_time = match json.get("time").and_then(Value::as_str) {
+            Some(text) => Some(DateTime::parse_from_rfc3339(text).ok()?.with_timezone(&Utc)),
+            None => None,
+        };
+
+        let radius = position.distance(target);
+        if radius <= 0.0 {
+            return None;
+        }
+        let rotation = Transform::from_translation(position).looking_at(target, up).rotation;
+        Some(Self { name, target, rotation, radius, sim_time })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let position = self.target + self.rotation * Vec3::Z * self.radius;
+        let up = self.rotation * Vec3::Y;
+        let mut json = json!({
+            "name": self.name,
+            "position": position.to_array().map(decimal),
+            "target": self.target.to_array().map(decimal),
+            "up": up.to_array().map(decimal),
+        });
+        if let Some(time) = self.sim_time {
+            json["time"] = json!(time.to_rfc3339());
+        }
+        json
+    }
+}
+
+/// an f32 as the number it prints as, so files say 0.15 rather than 0.15000000596046448
+pub fn decimal(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct Bookmarks(pub Vec<Bookmark>);
+
+impl Bookmarks {
+    /// a missing file is no bookmarks yet, bad entries are skipped with a warning
+    pub fn load(path: &str) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Could not read {}: {}", path, e);
+                return Self::default();
+            }
+        };
+        let json = match serde_json::from_str::<Value>(&text) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Could not parse {}: {}", path, e);
+                return Self::default();
+            }
+        };
+
+        let mut bookmarks = Vec::new();
+        for (index, entry) in json.as_array().into_iter().flatten().enumerate() {
+            match Bookmark::from_json(entry) {
+                Some(bookmark) => bookmarks.push(bookmark),
+                None => warn!("Bookmark {} in {} is invalid, skipped", index + 1, path),
+            }
+        }
+        info!("Loaded {} camera bookmarks", bookmarks.len());
+        Self(bookmarks)
+    }
+
+    /// the user's file, or the samples until there is one
+    pub fn load_or(path: &str, samples: &str) -> Self {
+        if std::path::Path::new(path).exists() {
+            Self::load(path)
+        } else {
+            Self::load(samples)
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let json = Value::Array(self.0.iter().map(Bookmark::to_json).collect());
+        let result = serde_json::to_string_pretty(&json)
+            .map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(path, text + "\n").map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            error!("Could not save bookmarks to {}: {}", path, e);
+        }
+    }
+}
+
+/// a fly-to in progress
+#[derive(Clone, Debug)]
+struct Flight {
+    bookmark: Bookmark,
+    from: (Vec3, Quat, f32), // world target, rotation, radius
+    elapsed: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct FlyTo(Option<Flight>);
+
+impl FlyTo {
+    pub fn cancel(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// B / H save the current view, 1-9 start flying to one
+#[allow(clippy::too_many_arguments)]
+pub fn handle_bookmarks(
+    actions: Res<Actions>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut fly_to: ResMut<FlyTo>,
+    mut mode: ResMut<CameraModeState>,
+    mut time_state: ResMut<TimeState>,
+    camera: Query<(&Transform, &OrbitCamera)>,
+    earth: Query<&Transform, (With<Earth>, Without<OrbitCamera>)>,
+) {
+    let (Ok((transform, camera)), Ok(earth)) = (camera.single(), earth.single()) else { return; };
+
+    let save = actions.just_pressed(Action::SaveBookmark);
+    let save_with_time = actions.just_pressed(Action::SaveBookmarkWithTime);
+    if save || save_with_time {
+        if mode.mode != CameraMode::Orbit {
+            info!("Bookmarks save the orbit camera, switch back to it first");
+        } else {
+            let to_earth = earth.rotation.inverse();
+            let bookmark = Bookmark {
+                name: format!("Bookmark {}", bookmarks.0.len() + 1),
+                target: to_earth * (camera.target - earth.translation),
+                rotation: to_earth * camera.rotation,
+                radius: camera.radius,
+                sim_time: save_with_time.then_some(time_state.sim_time),
+            };
+            info!("Saved camera bookmark '{}'", bookmark.name);
+            bookmarks.0.push(bookmark);
+            bookmarks.save(BOOKMARKS_PATH);
+        }
+    }
+
+    let Some(index) = RECALL.iter().position(|&action| actions.just_pressed(action)) else { return; };
+    let Some(bookmark) = bookmarks.0.get(index) else {
+        info!("No camera bookmark {}", index + 1);
+        return;
+    };
+
+    info!("Flying to '{}'", bookmark.name);
+    if let Some(time) = bookmark.sim_time {
+        time_state.sim_time = time;
+    }
+    // other modes blend back into the orbit camera as it sets off
+    if mode.mode != CameraMode::Orbit {
+        mode.switch(CameraMode::Orbit, transform);
+    }
+    fly_to.0 = Some(Flight {
+        bookmark: bookmark.clone(),
+        from: (camera.target, camera.rotation, camera.radius),
+        elapsed: 0.0,
+    });
+}
+
+/// move the orbit camera along the flight, any camera input takes over from it
+pub fn fly(
+    actions: Res<Actions>,
+    time: Res<Time>,
+    mut fly_to: ResMut<FlyTo>,
+    mut camera: Query<&mut OrbitCamera>,
+    earth: Query<&Transform, (With<Earth>, Without<OrbitCamera>)>,
+) {
+    let Some(flight) = fly_to.0.as_mut() else { return; };
+    let (Ok(mut camera), Ok(earth)) = (camera.single_mut(), earth.single()) else { return; };
+
+    if actions.orbit != Vec2::ZERO || actions.pan != Vec2::ZERO || actions.zoom != 0.0 || actions.just_pressed(Action::ResetCamera) {
+        fly_to.0 = None;
+        return;
+    }
+
+    // the destination follows the earth as it turns
+    let bookmark = &flight.bookmark;
+    let to_target = earth.translation + earth.rotation * bookmark.target;
+    let to_rotation = earth.rotation * bookmark.rotation;
+    let (from_target, from_rotation, from_radius) = flight.from;
+
+    flight.elapsed += time.delta_secs();
+    let t = (flight.elapsed / FLY_TO_SECONDS).clamp(0.0, 1.0);
+    if t >= 1.0 {
+        camera.set_view(to_target, to_rotation, bookmark.radius);
+        fly_to.0 = None;
+        return;
+    }
+    let eased = t * t * (3.0 - 2.0 * t);
+
+    // camera positions relative to the planet, swung along the great circle between them
+    let from = from_target + from_rotation * Vec3::Z * from_radius;
+    let to = to_target + to_rotation * Vec3::Z * bookmark.radius;
+    let (from_direction, to_direction) = (from.normalize_or(Vec3::Z), to.normalize_or(Vec3::Z));
+    let angle = from_direction.angle_between(to_direction);
+    let direction = Quat::IDENTITY.slerp(Quat::from_rotation_arc(from_direction, to_direction), eased) * from_direction;
+
+    // distance eased in log space, so zooming from GEO to the ground doesn't rush the end
+    let distance = (from.length().ln() + (to.length().ln() - from.length().ln()) * eased).exp();
+    let hop = 1.0 + FLY_TO_HOP * (angle / PI) * (PI * eased).sin();
+    let position = direction * distance * hop;
+
+    // keep looking at the target, rolling from the old up to the new one on the way
+    let target = from_target.lerp(to_target, eased);
+    let offset = position - target;
+    let rotation = from_rotation.slerp(to_rotation, eased);
+    let rotation = Quat::from_rotation_arc(rotation * Vec3::Z, offset.normalize_or(Vec3::Z)) * rotation;
+
+    camera.set_view(target, rotation.normalize(), offset.length());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(json: &Value, key: &str) -> Vec3 {
+        let values: Vec<f32> = json[key].as_array().unwrap().iter().map(|v| v.as_f64().unwrap() as f32).collect();
+        Vec3::from_slice(&values)
+    }
+
+    #[test]
+    fn conversion() {
+        let json = json!({
+            "name": "above",
+            "position": [0.0, 0.0, 3.0],
+            "target": [0.0, 0.0, 1.0],
+            "up": [0.0, 1.0, 0.0],
+        });
+        let bookmark = Bookmark::from_json(&json).unwrap();
+        assert_eq!(bookmark.name, "above");
+        assert_eq!(bookmark.target, Vec3::new(0.0, 0.0, 1.0));
+        assert!((bookmark.radius - 2.0).abs() < 1e-6);
+        // looking down -z from +z is no rotation at all
+        assert!(bookmark.rotation.angle_between(Quat::IDENTITY) < 1e-4);
+        assert_eq!(bookmark.sim_time, None);
+        assert!(bookmark.to_json().get("time").is_none());
+
+        // target and up default to the origin and +y
+        let json = json!({ "name": "side", "position": [2.0, 0.0, 0.0] });
+        let bookmark = Bookmark::from_json(&json).unwrap();
+        assert_eq!(bookmark.target, Vec3::ZERO);
+        assert!((bookmark.radius - 2.0).abs() < 1e-6);
+        assert!((bookmark.rotation * Vec3::Y).distance(Vec3::Y) < 1e-4);
+        assert!((bookmark.rotation * Vec3::Z).distance(Vec3::X) < 1e-4);
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = json!({
+            "name": "tilted",
+            "position": [1.5, -2.0, 4.0],
+            "target": [0.1, 0.2, -0.3],
+            "up": [0.3, 1.0, 0.1],
+            "time": "2024-03-20T03:06:00+00:00",
+        });
+        let bookmark = Bookmark::from_json(&json).unwrap();
+        let saved = bookmark.to_json();
+        assert_eq!(saved["time"], json["time"]);
+        assert!(vector(&saved, "position").distance(vector(&json, "position")) < 1e-4);
+        assert!(vector(&saved, "target").distance(vector(&json, "target")) < 1e-6);
+        // up comes back orthogonalised, but in the same plane as the view direction
+        let up = vector(&saved, "up");
+        assert!((up.length() - 1.0).abs() < 1e-4);
+        assert!(up.dot(vector(&json, "up")) > 0.9);
+
+        let loaded = Bookmark::from_json(&saved).unwrap();
+        assert_eq!(loaded.name, bookmark.name);
+        assert_eq!(loaded.sim_time, bookmark.sim_time);
+        assert!(loaded.target.distance(bookmark.target) < 1e-6);
+        assert!((loaded.radius - bookmark.radius).abs() < 1e-4);
+        assert!(loaded.rotation.angle_between(bookmark.rotation) < 1e-3);
+        assert_eq!(
+            loaded.sim_time.unwrap(),
+            DateTime::parse_from_rfc3339("2024-03-20T03:06:00Z").unwrap().with_timezone(&Utc)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_bookmarks() {
+        // position on the target
+        let json = json!({ "name": "inside", "position": [1.0, 2.0, 3.0], "target": [1.0, 2.0, 3.0] });
+        assert_eq!(Bookmark::from_json(&json), None);
+        // position missing or not three numbers
+        assert_eq!(Bookmark::from_json(&json!({ "name": "nowhere" })), None);
+        assert_eq!(Bookmark::from_json(&json!({ "name": "flat", "position": [1.0, 2.0] })), None);
+        // no name
+        assert_eq!(Bookmark::from_json(&json!({ "position": [0.0, 0.0, 2.0] })), None);
+        // a time that isn't RFC 3339
+        let json = json!({ "name": "when", "position": [0.0, 0.0, 2.0], "time": "yesterday" });
+        assert_eq!(Bookmark::from_json(&json), None);
+    }
+}
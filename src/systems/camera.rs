@@ -4,23 +4,43 @@
 //! to figure it out
 
 use bevy::prelude::*;
-use bevy::input::mouse::MouseWheel;
+
+use crate::config::{BOOKMARK_SAMPLES_PATH, BOOKMARKS_PATH, CAMERA_ZOOM_STEP, EARTH_RADIUS};
+use crate::systems::bookmarks::{self, Bookmarks, FlyTo};
+use crate::systems::camera_modes::{self, CameraModeState};
+use crate::systems::controls::{Action, Actions};
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_orbit_camera);
+        app.init_resource::<CameraModeState>()
+            .insert_resource(Bookmarks::load_or(BOOKMARKS_PATH, BOOKMARK_SAMPLES_PATH))
+            .init_resource::<FlyTo>()
+            .add_systems(Update, (
+                camera_modes::cycle_mode,
+                bookmarks::handle_bookmarks.after(camera_modes::cycle_mode),
+                bookmarks::fly.after(bookmarks::handle_bookmarks),
+                update_orbit_camera
+                    .run_if(camera_modes::orbiting)
+                    .after(bookmarks::fly),
+                camera_modes::update.after(camera_modes::cycle_mode),
+                camera_modes::apply_transition
+                    .after(update_orbit_camera)
+                    .after(camera_modes::update),
+                camera_modes::draw_observer_rings,
+            ));
     }
 }
 
 // camera component
+// arcball style: the camera's orientation around the target is a quaternion, drags rotate it about
+// the camera's own up and right axes, so there's no pitch limit and nothing flips over the poles
 #[derive(Component, Debug)]
 pub struct OrbitCamera {
     pub radius: f32,
     pub speed: f32, 
-    pub angle: f32,
-    pub v_angle: f32,
+    pub rotation: Quat, // camera looks down its -z at the target
     pub is_dragging: bool,
     pub is_panning: bool,
     pub target: Vec3,
@@ -30,8 +50,7 @@ pub struct OrbitCamera {
 
     // smoothing values
     target_radius: f32,
-    target_angle: f32,
-    target_v_angle: f32,
+    target_rotation: Quat,
     target_position: Vec3, // for panning
 
     // panning sensitivity
@@ -43,8 +62,7 @@ impl Default for OrbitCamera {
         Self {
             radius: 15.0,
             speed: 0.1,
-            angle: 0.0,
-            v_angle: 0.3,
+            rotation: Self::home_rotation(),
             is_dragging: false,
             is_panning: false,
             target: Vec3::ZERO,
@@ -53,8 +71,7 @@ impl Default for OrbitCamera {
             max_radius: 1000.0,
 
             target_radius: 15.0,
-            target_angle: 0.0,
-            target_v_angle: 0.3,
+            target_rotation: Self::home_rotation(),
             target_position: Vec3::ZERO,
 
             pan_speed: 0.1,
@@ -98,107 +115,145 @@ impl OrbitCamera {
     // reset camera
     pub fn reset(&mut self) {
         self.target_radius = 15000.0;
-        self.target_angle = 0.0;
-        self.target_v_angle = 0.3;
+        self.target_rotation = Self::home_rotation();
         self.target_position = Vec3::ZERO;
     }
 
-    // calculate world position from spherical coordinates
-    // https://en.wikipedia.org/wiki/Spherical_coordinate_system#Cartesian_coordinates
+    // jump straight to a view, smoothing included so nothing drifts afterwards
+    pub fn set_view(&mut self, target: Vec3, rotation: Quat, radius: f32) {
+        self.target = target;
+        self.target_position = target;
+        self.rotation = rotation;
+        self.target_rotation = rotation;
+        self.radius = radius;
+        self.target_radius = radius;
+    }
+
+    // starting view, a little above the equator with north up
+    fn home_rotation() -> Quat {
+        let direction = Vec3::new(0.3f32.cos(), 0.3f32.sin(), 0.0);
+        Transform::from_translation(direction).looking_at(Vec3::ZERO, Vec3::Y).rotation
+    }
+
+    // turn around the target by a drag (px), about the camera's own up and right axes
+    // turns about two axes pick up roll, so each step is levelled again afterwards
+    pub fn rotate(&mut self, drag: Vec2) {
+        let step = self.speed * 0.01;
+        let rotation = self.target_rotation
+            * Quat::from_rotation_y(-drag.x * step)
+            * Quat::from_rotation_x(-drag.y * step);
+        self.target_rotation = Self::level(rotation.normalize());
+    }
+
+    // roll about the view direction until up points at the pole as seen on screen
+    // (or away from it once dragged past, so going over the top doesn't flip the view)
+    // looking straight along the pole there's no level to find, it's left as it is
+    fn level(rotation: Quat) -> Quat {
+        let forward = rotation * Vec3::NEG_Z;
+        let up = rotation * Vec3::Y;
+        let pole = Vec3::Y.reject_from_normalized(forward);
+        if pole.length_squared() < 1e-6 {
+            return rotation;
+        }
+        let pole = pole.normalize() * up.dot(pole).signum();
+        (Quat::from_rotation_arc(up, pole) * rotation).normalize()
+    }
+
+    // zoom by wheel lines, each one moves a fixed fraction of the altitude above the surface
+    // so it's fine near the ground and still quick out at GEO
+    pub fn zoom(&mut self, lines: f32) {
+        let camera = self.target_position + self.target_rotation * Vec3::Z * self.target_radius;
+        let altitude = (camera.length() - EARTH_RADIUS).max(1.0);
+        let new_altitude = altitude * (-lines * CAMERA_ZOOM_STEP).exp();
+        self.target_radius = (self.target_radius + new_altitude - altitude).clamp(self.min_radius, self.max_radius);
+    }
+
+    // world position from the orientation and distance
     pub fn calculate_position(&self) -> Vec3 {
-        let x = self.radius * self.v_angle.cos() * self.angle.cos();
-        let y = self.radius * self.v_angle.sin();
-        let z = self.radius * self.v_angle.cos() * self.angle.sin();
-        
-        self.target + Vec3::new(x, y, z)
+        self.target + self.rotation * Vec3::Z * self.radius
     }
 
     // helper function to get the camera's right and up vectors for panning
     // lets us move in screen space rather than world space
     fn get_camera_basis(&self) -> (Vec3, Vec3) {
-        let forward = (self.target - self.calculate_position()).normalize();
-        let right = forward.cross(Vec3::Y).normalize();
-        let up = right.cross(forward).normalize();
-        (right, up)
+        (self.rotation * Vec3::X, self.rotation * Vec3::Y)
     }
 }
 
 fn update_orbit_camera(
     mut camera_query: Query<(&mut Transform, &mut OrbitCamera)>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut mouse_motion: EventReader<CursorMoved>,
-    mut scroll_events: EventReader<MouseWheel>,
+    actions: Res<Actions>,
     time: Res<Time>,
 ) {
-    const ZOOM_SPEED: f32 = 1400.0;
     const SMOOTH_SPEED: f32 = 10.0;
 
     for (mut transform, mut camera) in camera_query.iter_mut() {
-        // reset camera on R key
-        if keyboard.just_pressed(KeyCode::KeyR) {
+        // reset camera
+        if actions.just_pressed(Action::ResetCamera) {
             camera.reset();
         }
 
-        // handle mouse drag
-        if mouse_buttons.just_pressed(MouseButton::Right) {
-            camera.is_dragging = true;
-        }
-        if mouse_buttons.just_released(MouseButton::Right) {
-            camera.is_dragging = false;
-        }
+        // drags, keys and sticks all arrive as drag-equivalent pixels, see controls.rs
+        camera.is_dragging = actions.pressed(Action::OrbitDrag);
+        camera.is_panning = actions.pressed(Action::PanDrag);
 
-        // handle mouse drag, panning
-        if mouse_buttons.just_pressed(MouseButton::Middle) {
-            camera.is_panning = true;
-        }
-        if mouse_buttons.just_released(MouseButton::Middle) {
-            camera.is_panning = false;
+        // rotating around the target
+        if actions.orbit != Vec2::ZERO {
+            camera.rotate(actions.orbit);
         }
 
-        // update camera angles 
-        if camera.is_dragging || camera.is_panning {
-            for motion in mouse_motion.read() {
-                if let Some(delta) = motion.delta {
-                    if camera.is_dragging {
-                        // rotating around the target
-                        camera.target_angle += delta.x * camera.speed * 0.01;
-                        camera.target_v_angle += delta.y * camera.speed * 0.01;
-                        // clamp pitch on the target value so we don't flip upside down
-                        camera.target_v_angle = camera.target_v_angle.clamp(-1.5, 1.5);
-                    } else if camera.is_panning {
-                        // moving the target point around in screen space
-                        let (right, up) = camera.get_camera_basis();
-                        let pan_distance = camera.radius * 0.001; // scale panning with distance
-                        
-                        // move target in camera's local coordinate system
-                        let pan_offset = (-right * delta.x + up * delta.y) * camera.pan_speed * pan_distance;
-                        camera.target_position += pan_offset;
-                    }
-                }
-            }
+        // moving the target point around in screen space
+        if actions.pan != Vec2::ZERO {
+            let (right, up) = camera.get_camera_basis();
+            let pan_distance = camera.radius * 0.001; // scale panning with distance
+
+            // move target in camera's local coordinate system
+            let pan_offset = (-right * actions.pan.x + up * actions.pan.y) * camera.pan_speed * pan_distance;
+            camera.target_position += pan_offset;
         }
 
-        // handle mouse scroll
-        for scroll in scroll_events.read() {
-            // zoom speed
-            // TODO: expose functionality
-            camera.target_radius -= scroll.y * ZOOM_SPEED;
-            camera.target_radius = camera.target_radius.clamp(camera.min_radius, camera.max_radius);
+        if actions.zoom != 0.0 {
+            camera.zoom(actions.zoom);
         }
 
         // interpolate actual values towards targets
         let dt = time.delta_secs();
-        camera.angle += (camera.target_angle - camera.angle) * dt * SMOOTH_SPEED;
-        camera.v_angle += (camera.target_v_angle - camera.v_angle) * dt * SMOOTH_SPEED;
-        camera.radius += (camera.target_radius - camera.radius) * dt * SMOOTH_SPEED;
+        let blend = (dt * SMOOTH_SPEED).min(1.0);
+        camera.rotation = camera.rotation.slerp(camera.target_rotation, blend);
+        camera.radius += (camera.target_radius - camera.radius) * blend;
         
         // smooth the target position change
-        let new_target = camera.target + (camera.target_position - camera.target) * dt * SMOOTH_SPEED;
+        let new_target = camera.target + (camera.target_position - camera.target) * blend;
         camera.target = new_target;
 
         // update camera position/orientation
         transform.translation = camera.calculate_position();
-        transform.look_at(camera.target, Vec3::Y);
+        transform.rotation = camera.rotation;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // circling drags used to leave the horizon tilted
+    #[test]
+    fn rotate_does_not_roll() {
+        let mut camera = OrbitCamera::new(15000.0, 0.3);
+        for i in 0..2000 {
+            let angle = i as f32 * 0.05;
+            camera.rotate(Vec2::new(angle.cos(), angle.sin()) * 40.0);
+
+            let forward = camera.target_rotation * Vec3::NEG_Z;
+            let up = camera.target_rotation * Vec3::Y;
+            // up stays in the plane through the view direction and the pole
+            // (close to looking along the pole that plane is barely defined, skip it)
+            let side = forward.cross(Vec3::Y);
+            if side.length() < 0.05 {
+                continue;
+            }
+            let tilt = up.dot(side.normalize());
+            assert!(tilt.abs() < 1e-3, "step {i}: up {up} is rolled by {tilt}");
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! camera_modes.rs
+//!
+//! Camera modes besides the orbit camera (K cycles them):
+//! - free-fly: 6-DOF, look with the orbit inputs, roll with Q/E, fly with zoom, strafe with pan
+//! - ground observer: stands on the surface at a lat/lon (the cursor's, if it's on the globe)
+//!   looking at the sky, with horizon, elevation and azimuth rings
+//! - satellite POV: rides the selected satellite looking at nadir or along track (N switches)
+//!
+//! each mode writes the camera transform, switching blends from the old view over CAMERA_TRANSITION_SECONDS
+
+use bevy::prelude::*;
+
+use crate::config::{CAMERA_TRANSITION_SECONDS, DISPLACEMENT_SCALE, EARTH_RADIUS, OBSERVER_EYE_HEIGHT, OBSERVER_LOCATION};
+use crate::systems::controls::{Action, Actions};
+use crate::systems::earth::Earth;
+use crate::systems::earth::cursor::CursorLocation;
+use crate::systems::earth::heightmap::HeightFilter;
+use crate::systems::earth::lod::TerrainLod;
+use crate::systems::earth::uv::LatLon;
+use crate::systems::satellites::{Satellite, SelectedSatellite};
+use crate::systems::time::TimeState;
+
+const LOOK_SPEED: f32 = 0.003; // radians per drag pixel
+const ROLL_SPEED: f32 = 1.2; // radians per second
+const FLY_SPEED: f32 = 0.1; // of the altitude, per wheel line
+const STRAFE_SPEED: f32 = 0.001; // of the altitude, per drag pixel
+const MIN_FLY_ALTITUDE: f32 = 1.0; // km, keeps free-fly out of the ground
+const RING_DISTANCE: f32 = 1000.0; // km from the observer the sky rings are drawn at
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    Orbit,
+    FreeFly {
+        position: Vec3,
+        rotation: Quat,
+    },
+    Observer {
+        latitude: f32, // degrees
+        longitude: f32,
+        azimuth: f32,   // radians clockwise from north
+        elevation: f32, // radians above the horizon
+    },
+    SatellitePov {
+        satellite: Entity,
+        view: PovView,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PovView {
+    Nadir,
+    AlongTrack,
+}
+
+/// blend from the view before a switch
+#[derive(Clone, Copy, Debug)]
+struct Transition {
+    from: Transform,
+    elapsed: f32,
+}
+
+#[derive(Resource)]
+pub struct CameraModeState {
+    pub mode: CameraMode,
+    transition: Option<Transition>,
+}
+
+impl Default for CameraModeState {
+    fn default() -> Self {
+        Self { mode: CameraMode::Orbit, transition: None }
+    }
+}
+
+impl CameraModeState {
+    /// switch modes, starting a transition from the current view
+    pub fn switch(&mut self, mode: CameraMode, current: &Transform) {
+        self.mode = mode;
+        self.transition = Some(Transition { from: *current, elapsed: 0.0 });
+    }
+}
+
+/// run condition for the orbit camera
+pub fn orbiting(state: Res<CameraModeState>) -> bool {
+    state.mode == CameraMode::Orbit
+}
+
+/// cycle orbit -> free-fly -> observer -> satellite POV with K, N flips the POV view
+pub fn cycle_mode(
+    actions: Res<Actions>,
+    mut state: ResMut<CameraModeState>,
+    camera: Query<&Transform, With<Camera3d>>,
+    cursor: Res<CursorLocation>,
+    selected: Res<SelectedSatellite>,
+    satellites: Query<(), With<Satellite>>,
+) {
+    let Ok(transform) = camera.single() else { return; };
+
+    if actions.just_pressed(Action::TogglePovView)
+        && let CameraMode::SatellitePov { view, .. } = &mut state.mode
+    {
+        *view = match view {
+            PovView::Nadir => PovView::AlongTrack,
+            PovView::AlongTrack => PovView::Nadir,
+        };
+        let current = *transform;
+        state.transition = Some(Transition { from: current, elapsed: 0.0 });
+    }
+
+    if !actions.just_pressed(Action::CycleCameraMode) {
+        return;
+    }
+
+    let observer = || {
+        let (latitude, longitude) = cursor.0.map_or(OBSERVER_LOCATION, |point| (point.latitude, point.longitude));
+        CameraMode::Observer { latitude, longitude, azimuth: 0.0, elevation: 30f32.to_radians() }
+    };
+    // the POV needs a satellite to ride, skipped when none is selected
+    let pov = selected
+        .0
+        .filter(|&entity| satellites.contains(entity))
+        .map(|satellite| CameraMode::SatellitePov { satellite, view: PovView::Nadir });
+
+    let next = match state.mode {
+        CameraMode::Orbit => CameraMode::FreeFly { position: transform.translation, rotation: transform.rotation },
+        CameraMode::FreeFly { .. } => observer(),
+        CameraMode::Observer { .. } => pov.unwrap_or(CameraMode::Orbit),
+        CameraMode::SatellitePov { .. } => CameraMode::Orbit,
+    };
+    info!("Camera mode: {:?}", next);
+    state.switch(next, transform);
+}
+
+/// place the camera for modes other than orbit
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    actions: Res<Actions>,
+    time: Res<Time>,
+    time_state: Res<TimeState>,
+    mut state: ResMut<CameraModeState>,
+    mut camera: Query<&mut Transform, With<Camera3d>>,
+    earth: Query<&Transform, (With<Earth>, Without<Camera3d>)>,
+    satellites: Query<&Satellite>,
+    lod: Option<Res<TerrainLod>>,
+) {
+    let (Ok(mut transform), Ok(earth)) = (camera.single_mut(), earth.single()) else { return; };
+    let dt = time.delta_secs();
+
+    // satellite went away, back to the globe
+    if let CameraMode::SatellitePov { satellite, .. } = state.mode
+        && !satellites.contains(satellite)
+    {
+        let current = *transform;
+        state.switch(CameraMode::Orbit, &current);
+        return;
+    }
+
+    match &mut state.mode {
+        CameraMode::Orbit => {} // camera.rs
+        CameraMode::FreeFly { position, rotation } => {
+            let altitude = (position.length() - EARTH_RADIUS).max(MIN_FLY_ALTITUDE);
+            let roll = (actions.pressed(Action::RollLeft) as i32 - actions.pressed(Action::RollRight) as i32) as f32;
+
+            // turn about the camera's own axes, no fixed up
+            *rotation = (*rotation
+                * Quat::from_euler(
+                    EulerRot::YXZ,
+                    -actions.orbit.x * LOOK_SPEED,
+                    actions.orbit.y * LOOK_SPEED,
+                    roll * ROLL_SPEED * dt,
+                ))
+            .normalize();
+
+            // faster the further out
+            let movement = *rotation * Vec3::new(-actions.pan.x * STRAFE_SPEED, actions.pan.y * STRAFE_SPEED, -actions.zoom * FLY_SPEED);
+            *position += movement * altitude;
+            *position = position.normalize_or(Vec3::Z) * position.length().max(EARTH_RADIUS + MIN_FLY_ALTITUDE);
+
+            *transform = Transform::from_translation(*position).with_rotation(*rotation);
+        }
+        CameraMode::Observer { latitude, longitude, azimuth, elevation } => {
+            *azimuth += actions.orbit.x * LOOK_SPEED;
+            *elevation = (*elevation + actions.orbit.y * LOOK_SPEED).clamp(-0.2, std::f32::consts::FRAC_PI_2);
+
+            let (position, frame) = observer_frame(earth, *latitude, *longitude, lod.as_deref());
+            let look = Quat::from_rotation_y(-*azimuth) * Quat::from_rotation_x(*elevation);
+            *transform = Transform::from_translation(position).with_rotation(frame * look);
+        }
+        CameraMode::SatellitePov { satellite, view } => {
+            let Ok(satellite) = satellites.get(*satellite) else { return; };
+
+            let position = satellite.get_position(time_state.sim_time);
+            let radial = position.normalize();
+            let along = satellite.get_velocity(time_state.sim_time).reject_from(radial).normalize_or(Vec3::Y);
+            let (forward, up) = match view {
+                PovView::Nadir => (-radial, along),
+                PovView::AlongTrack => (along, radial),
+            };
+            *transform = Transform::from_translation(position).looking_to(forward, up);
+        }
+    }
+}
+
+/// observer position and orientation, camera x east, y up, -z north
+fn observer_frame(earth: &Transform, latitude: f32, longitude: f32, lod: Option<&TerrainLod>) -> (Vec3, Quat) {
+    let point = LatLon::from_degrees(latitude, longitude);
+    let up = point.to_direction();
+    let height = lod.map_or(0.0, |lod| {
+        let (u, v) = point.to_uv();
+        lod.displacement().sample(u, v, HeightFilter::Bilinear) * DISPLACEMENT_SCALE
+    });
+
+    // north is the pole direction projected onto the horizon
+    let north = Vec3::Y.reject_from(up).normalize_or(Vec3::Z);
+    let east = north.cross(up);
+    let frame = Quat::from_mat3(&Mat3::from_cols(east, up, -north));
+
+    let position = earth.transform_point(up * (EARTH_RADIUS + height + OBSERVER_EYE_HEIGHT));
+    (position, earth.rotation * frame)
+}
+
+/// blend from the view before the last switch, runs after every mode has placed the camera
+pub fn apply_transition(
+    time: Res<Time>,
+    mut state: ResMut<CameraModeState>,
+    mut camera: Query<&mut Transform, With<Camera3d>>,
+) {
+    let Ok(mut transform) = camera.single_mut() else { return; };
+    let Some(transition) = state.transition.as_mut() else { return; };
+
+    transition.elapsed += time.delta_secs();
+    let t = (transition.elapsed / CAMERA_TRANSITION_SECONDS).clamp(0.0, 1.0);
+    if t >= 1.0 {
+        state.transition = None;
+        return;
+    }
+
+    // around the planet rather than through it: direction and distance are blended separately
+    let eased = t * t * (3.0 - 2.0 * t);
+    let (from, to) = (transition.from.translation, transform.translation);
+    let direction = Quat::from_rotation_arc(from.normalize_or(Vec3::Z), to.normalize_or(Vec3::Z));
+    let distance = from.length() + (to.length() - from.length()) * eased;
+
+    transform.translation = (Quat::IDENTITY.slerp(direction, eased) * from.normalize_or(Vec3::Z)) * distance;
+    transform.rotation = transition.from.rotation.slerp(transform.rotation, eased);
+}
+
+/// horizon, elevation rings every 30° and azimuth lines every 30° (north in red) around the observer
+pub fn draw_observer_rings(
+    state: Res<CameraModeState>,
+    earth: Query<&Transform, With<Earth>>,
+    lod: Option<Res<TerrainLod>>,
+    mut gizmos: Gizmos,
+) {
+    let CameraMode::Observer { latitude, longitude, .. } = state.mode else { return; };
+    let Ok(earth) = earth.single() else { return; };
+    let (position, frame) = observer_frame(earth, latitude, longitude, lod.as_deref());
+
+    // sky direction in world space
+    let sky = |azimuth: f32, elevation: f32| {
+        let local = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        position + frame * local * RING_DISTANCE
+    };
+
+    for elevation_deg in [0.0f32, 30.0, 60.0] {
+        let color = if elevation_deg == 0.0 { Color::srgba(0.6, 0.9, 1.0, 0.8) } else { Color::srgba(0.6, 0.9, 1.0, 0.35) };
+        gizmos.linestrip((0..=72).map(|i| sky((i as f32 * 5.0).to_radians(), elevation_deg.to_radians())), color);
+    }
+    for azimuth_deg in (0..360).step_by(30) {
+        let color = if azimuth_deg == 0 { Color::srgba(1.0, 0.3, 0.3, 0.9) } else { Color::srgba(0.6, 0.9, 1.0, 0.35) };
+        gizmos.linestrip((0..=18).map(|i| sky((azimuth_deg as f32).to_radians(), (i as f32 * 5.0).to_radians())), color);
+    }
+}
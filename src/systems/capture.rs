@@ -0,0 +1,218 @@
+//! capture.rs
+//!
+//! Screenshots and recordings, written under CAPTURE_DIR
+//! F12 saves a timestamped PNG of the window. F9 starts/stops recording: while it runs every
+//! frame advances time by exactly 1 / RECORD_FPS, so the sim, the camera and the globe move the
+//! same amount per frame however slowly they render, and the frames play back at RECORD_FPS.
+//! frames go to numbered PNGs, or into the encoder command in RECORD_ENCODER if there is one
+//!
+//! the frame before recording stops may still be in flight and is dropped
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured, save_to_disk};
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy::time::TimeUpdateStrategy;
+use chrono::Local;
+
+use crate::config::{CAPTURE_DIR, RECORD_ENCODER, RECORD_FPS};
+use crate::systems::controls::{Action, Actions};
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .add_systems(Update, (screenshot, toggle_recording, record_frame.after(toggle_recording)));
+    }
+}
+
+/// recording in progress
+struct Session {
+    directory: PathBuf,
+    frame: u32,
+    encoder: Option<Encoder>,
+}
+
+/// external encoder, started with the first frame once the size is known
+enum Encoder {
+    Pending(String),
+    Running { child: Child, stdin: ChildStdin, size: UVec2 },
+    Failed,
+}
+
+#[derive(Resource, Default)]
+pub struct Recording(Option<Session>);
+
+fn timestamp() -> String {
+    Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+fn create_directory(path: &Path) -> bool {
+    match std::fs::create_dir_all(path) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Could not create {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+/// F12: the whole window, UI included, to a timestamped PNG
+fn screenshot(actions: Res<Actions>, mut commands: Commands) {
+    if !actions.just_pressed(Action::Screenshot) {
+        return;
+    }
+
+    let directory = PathBuf::from(CAPTURE_DIR);
+    if !create_directory(&directory) {
+        return;
+    }
+    // two in the same second get a suffix
+    let stamp = timestamp();
+    let mut path = directory.join(format!("screenshot-{}.png", stamp));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = directory.join(format!("screenshot-{}-{}.png", stamp, n));
+    }
+
+    commands.spawn(Screenshot::primary_window()).observe(save_to_disk(path));
+}
+
+/// F9: start or stop recording, time runs in fixed steps while it's on
+fn toggle_recording(
+    actions: Res<Actions>,
+    mut recording: ResMut<Recording>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    if !actions.just_pressed(Action::ToggleRecording) {
+        return;
+    }
+
+    if let Some(session) = recording.0.take() {
+        *strategy = TimeUpdateStrategy::Automatic;
+        info!("Recorded {} frames to {}", session.frame, session.directory.display());
+        if let Some(Encoder::Running { child, stdin, .. }) = session.encoder {
+            finish_encoder(child, stdin);
+        }
+        return;
+    }
+
+    let directory = PathBuf::from(CAPTURE_DIR).join(format!("recording-{}", timestamp()));
+    if !create_directory(&directory) {
+        return;
+    }
+
+    *strategy = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / RECORD_FPS));
+    info!("Recording at {} fps to {}", RECORD_FPS, directory.display());
+    let encoder = RECORD_ENCODER.map(|command| Encoder::Pending(command.to_string()));
+    recording.0 = Some(Session { directory, frame: 0, encoder });
+}
+
+/// one screenshot per frame while recording
+fn record_frame(mut recording: ResMut<Recording>, mut commands: Commands) {
+    let Some(session) = recording.0.as_mut() else { return; };
+
+    let frame = session.frame;
+    session.frame += 1;
+
+    if session.encoder.is_some() {
+        commands.spawn(Screenshot::primary_window()).observe(encode_frame);
+    } else {
+        let path = session.directory.join(format!("frame-{:06}.png", frame));
+        commands.spawn(Screenshot::primary_window()).observe(move |trigger: Trigger<ScreenshotCaptured>| {
+            let image = trigger.event().0.clone();
+            let path = path.clone();
+            // PNG compression is slow, keep it off the main thread
+            AsyncComputeTaskPool::get()
+                .spawn(async move {
+                    let result = image
+                        .try_into_dynamic()
+                        .map_err(|e| e.to_string())
+                        .and_then(|image| image.to_rgb8().save(&path).map_err(|e| e.to_string()));
+                    if let Err(e) = result {
+                        error!("Could not save frame {}: {}", path.display(), e);
+                    }
+                })
+                .detach();
+        });
+    }
+}
+
+/// hand a frame to the encoder, starting it on the first one
+fn encode_frame(trigger: Trigger<ScreenshotCaptured>, mut recording: ResMut<Recording>) {
+    let Some(session) = recording.0.as_mut() else { return; };
+    let Some(encoder) = session.encoder.as_mut() else { return; };
+
+    let image = match trigger.event().0.clone().try_into_dynamic() {
+        Ok(image) => image.to_rgb8(),
+        Err(e) => {
+            error!("Could not read frame: {}", e);
+            return;
+        }
+    };
+    let size = UVec2::new(image.width(), image.height());
+
+    if let Encoder::Pending(command) = encoder {
+        let command = command.clone();
+        *encoder = start_encoder(&command, size, &session.directory);
+    }
+    let Encoder::Running { stdin, size: encoder_size, .. } = encoder else { return; };
+
+    // raw video can't change size mid-stream
+    if size != *encoder_size {
+        warn!("Window resized while recording, frame skipped");
+        return;
+    }
+    if let Err(e) = stdin.write_all(image.as_raw()) {
+        error!("Encoder stopped taking frames: {}", e);
+        if let Encoder::Running { child, stdin, .. } = std::mem::replace(encoder, Encoder::Failed) {
+            finish_encoder(child, stdin);
+        }
+    }
+}
+
+/// {width}, {height}, {fps} and {dir} in the command are filled in, frames arrive on stdin as raw rgb24
+fn start_encoder(command: &str, size: UVec2, directory: &Path) -> Encoder {
+    let command = command
+        .replace("{width}", &size.x.to_string())
+        .replace("{height}", &size.y.to_string())
+        .replace("{fps}", &RECORD_FPS.to_string())
+        .replace("{dir}", &directory.display().to_string());
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        error!("RECORD_ENCODER is empty");
+        return Encoder::Failed;
+    };
+
+    let child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            info!("Encoding with `{}`", command);
+            let stdin = child.stdin.take().expect("stdin is piped");
+            Encoder::Running { child, stdin, size }
+        }
+        Err(e) => {
+            error!("Could not start encoder `{}`: {}", program, e);
+            Encoder::Failed
+        }
+    }
+}
+
+/// close the pipe and let the encoder finish in the background
+fn finish_encoder(mut child: Child, stdin: ChildStdin) {
+    drop(stdin);
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => info!("Encoder finished"),
+        Ok(status) => warn!("Encoder exited with {}", status),
+        Err(e) => error!("Encoder: {}", e),
+    });
+}
@@ -0,0 +1,507 @@
+//! controls.rs
+//!
+//! Input mapping: keys, mouse buttons and gamepad buttons are bound to named actions,
+//! every other system asks `Actions` instead of reading devices directly
+//! bindings come from BINDINGS_PATH (one `action = binding, binding` per line), anything
+//! not listed there keeps its default. analog input (drags, sticks, wheel, pinch) is folded
+//! into per-frame orbit / pan / zoom amounts for the camera
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::input::InputSystem;
+use bevy::input::gestures::PinchGesture;
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::prelude::*;
+
+use crate::config::{
+    BINDINGS_PATH, CAMERA_KEY_ORBIT_SPEED, CAMERA_KEY_ZOOM_SPEED, CAMERA_PINCH_ZOOM, CAMERA_STICK_SPEED, GAMEPAD_DEADZONE,
+};
+
+const PIXELS_PER_LINE: f32 = 100.0; // touchpads scroll in pixels, the camera zooms in wheel lines
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load(BINDINGS_PATH))
+            .init_resource::<Actions>()
+            .add_systems(PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+/// everything that can be bound
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    // camera
+    OrbitDrag, // held while the mouse drags
+    PanDrag,
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    ResetCamera,
+    CycleCameraMode,
+    TogglePovView, // nadir / along-track from a satellite
+    RollLeft,      // free-fly
+    RollRight,
+    SaveBookmark,
+    SaveBookmarkWithTime, // also stores the sim time
+    Bookmark1,            // fly to a saved view
+    Bookmark2,
+    Bookmark3,
+    Bookmark4,
+    Bookmark5,
+    Bookmark6,
+    Bookmark7,
+    Bookmark8,
+    Bookmark9,
+    // time
+    Pause,
+    TimeFaster,
+    TimeSlower,
+    TimeReset,
+    // layers and views
+    CycleSelection,
+    ToggleVelocity,
+    ToggleAxes,
+    CycleColors,
+    CycleTrails,
+    ToggleGraticule,
+    CloudOpacityDown,
+    CloudOpacityUp,
+    SettingsPanel,
+    CycleView,
+    ToggleProjection,
+    // capture
+    Screenshot,
+    ToggleRecording,
+    // scenarios
+    SaveScenario,
+    LoadScenario,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 44] = [
+        Action::OrbitDrag,
+        Action::PanDrag,
+        Action::OrbitLeft,
+        Action::OrbitRight,
+        Action::OrbitUp,
+        Action::OrbitDown,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ResetCamera,
+        Action::CycleCameraMode,
+        Action::TogglePovView,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::SaveBookmark,
+        Action::SaveBookmarkWithTime,
+        Action::Bookmark1,
+        Action::Bookmark2,
+        Action::Bookmark3,
+        Action::Bookmark4,
+        Action::Bookmark5,
+        Action::Bookmark6,
+        Action::Bookmark7,
+        Action::Bookmark8,
+        Action::Bookmark9,
+        Action::Pause,
+        Action::TimeFaster,
+        Action::TimeSlower,
+        Action::TimeReset,
+        Action::CycleSelection,
+        Action::ToggleVelocity,
+        Action::ToggleAxes,
+        Action::CycleColors,
+        Action::CycleTrails,
+        Action::ToggleGraticule,
+        Action::CloudOpacityDown,
+        Action::CloudOpacityUp,
+        Action::SettingsPanel,
+        Action::CycleView,
+        Action::ToggleProjection,
+        Action::Screenshot,
+        Action::ToggleRecording,
+        Action::SaveScenario,
+        Action::LoadScenario,
+        Action::Quit,
+    ];
+
+    /// name used in the bindings file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::OrbitDrag => "orbit_drag",
+            Action::PanDrag => "pan_drag",
+            Action::OrbitLeft => "orbit_left",
+            Action::OrbitRight => "orbit_right",
+            Action::OrbitUp => "orbit_up",
+            Action::OrbitDown => "orbit_down",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::ResetCamera => "reset_camera",
+            Action::CycleCameraMode => "cycle_camera_mode",
+            Action::TogglePovView => "toggle_pov_view",
+            Action::RollLeft => "roll_left",
+            Action::RollRight => "roll_right",
+            Action::SaveBookmark => "save_bookmark",
+            Action::SaveBookmarkWithTime => "save_bookmark_with_time",
+            Action::Bookmark1 => "bookmark_1",
+            Action::Bookmark2 => "bookmark_2",
+            Action::Bookmark3 => "bookmark_3",
+            Action::Bookmark4 => "bookmark_4",
+            Action::Bookmark5 => "bookmark_5",
+            Action::Bookmark6 => "bookmark_6",
+            Action::Bookmark7 => "bookmark_7",
+            Action::Bookmark8 => "bookmark_8",
+            Action::Bookmark9 => "bookmark_9",
+            Action::Pause => "pause",
+            Action::TimeFaster => "time_faster",
+            Action::TimeSlower => "time_slower",
+            Action::TimeReset => "time_reset",
+            Action::CycleSelection => "cycle_selection",
+            Action::ToggleVelocity => "toggle_velocity",
+            Action::ToggleAxes => "toggle_axes",
+            Action::CycleColors => "cycle_colors",
+            Action::CycleTrails => "cycle_trails",
+            Action::ToggleGraticule => "toggle_graticule",
+            Action::CloudOpacityDown => "cloud_opacity_down",
+            Action::CloudOpacityUp => "cloud_opacity_up",
+            Action::SettingsPanel => "settings_panel",
+            Action::CycleView => "cycle_view",
+            Action::ToggleProjection => "toggle_projection",
+            Action::Screenshot => "screenshot",
+            Action::ToggleRecording => "toggle_recording",
+            Action::SaveScenario => "save_scenario",
+            Action::LoadScenario => "load_scenario",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn defaults(self) -> Vec<Binding> {
+        use Binding::{Gamepad as Pad, Key, Mouse};
+        match self {
+            Action::OrbitDrag => vec![Mouse(MouseButton::Right)],
+            Action::PanDrag => vec![Mouse(MouseButton::Middle)],
+            Action::OrbitLeft => vec![Key(KeyCode::ArrowLeft), Key(KeyCode::KeyA), Pad(GamepadButton::DPadLeft)],
+            Action::OrbitRight => vec![Key(KeyCode::ArrowRight), Key(KeyCode::KeyD), Pad(GamepadButton::DPadRight)],
+            Action::OrbitUp => vec![Key(KeyCode::ArrowUp), Key(KeyCode::KeyW), Pad(GamepadButton::DPadUp)],
+            Action::OrbitDown => vec![Key(KeyCode::ArrowDown), Key(KeyCode::KeyS), Pad(GamepadButton::DPadDown)],
+            Action::ZoomIn => vec![Key(KeyCode::Equal), Key(KeyCode::NumpadAdd), Pad(GamepadButton::RightTrigger2)],
+            Action::ZoomOut => vec![Key(KeyCode::Minus), Key(KeyCode::NumpadSubtract), Pad(GamepadButton::LeftTrigger2)],
+            Action::ResetCamera => vec![Key(KeyCode::KeyR), Pad(GamepadButton::RightThumb)],
+            Action::CycleCameraMode => vec![Key(KeyCode::KeyK), Pad(GamepadButton::East)],
+            Action::TogglePovView => vec![Key(KeyCode::KeyN)],
+            Action::RollLeft => vec![Key(KeyCode::KeyQ)],
+            Action::RollRight => vec![Key(KeyCode::KeyE)],
+            Action::SaveBookmark => vec![Key(KeyCode::KeyB)],
+            Action::SaveBookmarkWithTime => vec![Key(KeyCode::KeyH)],
+            Action::Bookmark1 => vec![Key(KeyCode::Digit1)],
+            Action::Bookmark2 => vec![Key(KeyCode::Digit2)],
+            Action::Bookmark3 => vec![Key(KeyCode::Digit3)],
+            Action::Bookmark4 => vec![Key(KeyCode::Digit4)],
+            Action::Bookmark5 => vec![Key(KeyCode::Digit5)],
+            Action::Bookmark6 => vec![Key(KeyCode::Digit6)],
+            Action::Bookmark7 => vec![Key(KeyCode::Digit7)],
+            Action::Bookmark8 => vec![Key(KeyCode::Digit8)],
+            Action::Bookmark9 => vec![Key(KeyCode::Digit9)],
+            Action::Pause => vec![Key(KeyCode::Space), Pad(GamepadButton::Start)],
+            Action::TimeFaster => vec![Key(KeyCode::Period), Pad(GamepadButton::RightTrigger)],
+            Action::TimeSlower => vec![Key(KeyCode::Comma), Pad(GamepadButton::LeftTrigger)],
+            Action::TimeReset => vec![Key(KeyCode::Backspace)],
+            Action::CycleSelection => vec![Key(KeyCode::Tab), Pad(GamepadButton::South)],
+            Action::ToggleVelocity => vec![Key(KeyCode::KeyV)],
+            Action::ToggleAxes => vec![Key(KeyCode::KeyF)],
+            Action::CycleColors => vec![Key(KeyCode::KeyC), Pad(GamepadButton::West)],
+            Action::CycleTrails => vec![Key(KeyCode::KeyT)],
+            Action::ToggleGraticule => vec![Key(KeyCode::KeyG)],
+            Action::CloudOpacityDown => vec![Key(KeyCode::BracketLeft)],
+            Action::CloudOpacityUp => vec![Key(KeyCode::BracketRight)],
+            Action::SettingsPanel => vec![Key(KeyCode::KeyL), Pad(GamepadButton::Select)],
+            Action::CycleView => vec![Key(KeyCode::KeyM), Pad(GamepadButton::North)],
+            Action::ToggleProjection => vec![Key(KeyCode::KeyP)],
+            Action::Screenshot => vec![Key(KeyCode::F12)],
+            Action::ToggleRecording => vec![Key(KeyCode::F9)],
+            Action::SaveScenario => vec![Key(KeyCode::F5)],
+            Action::LoadScenario => vec![Key(KeyCode::F8)],
+            Action::Quit => vec![Key(KeyCode::Escape)],
+        }
+    }
+}
+
+/// one physical input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    /// "KeyW", "Mouse:Right", "Gamepad:South", names as in Bevy's KeyCode / MouseButton / GamepadButton
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            Some(("Mouse", button)) => parse_mouse_button(button).map(Binding::Mouse),
+            Some(("Gamepad", button)) => parse_gamepad_button(button).map(Binding::Gamepad),
+            Some(_) => None,
+            None => parse_key(text).map(Binding::Key),
+        }
+    }
+}
+
+/// action to inputs, any of which triggers it
+#[derive(Resource, Clone, Debug)]
+pub struct Bindings(pub HashMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self(Action::ALL.iter().map(|&action| (action, action.defaults())).collect())
+    }
+}
+
+impl Bindings {
+    /// defaults, overridden by whatever the file at `path` sets
+    /// a missing file just means defaults, bad lines are skipped with a warning
+    pub fn load(path: &str) -> Self {
+        let mut bindings = Self::default();
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                bindings.apply(&text);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not read {}: {}", path, e),
+        }
+        bindings
+    }
+
+    /// apply `action = binding, binding` lines, # starts a comment
+    /// an empty right-hand side unbinds the action
+    /// bad lines and inputs are skipped, each one is warned about and returned
+    pub fn apply(&mut self, text: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((name, inputs)) = line.split_once('=') else {
+                problems.push(format!("Bindings line {}: expected `action = binding, ...`", number + 1));
+                continue;
+            };
+            let Some(action) = Action::ALL.into_iter().find(|action| action.name() == name.trim()) else {
+                problems.push(format!("Bindings line {}: unknown action `{}`", number + 1, name.trim()));
+                continue;
+            };
+
+            let bindings = inputs
+                .split(',')
+                .map(str::trim)
+                .filter(|input| !input.is_empty())
+                .filter_map(|input| {
+                    let binding = Binding::parse(input);
+                    if binding.is_none() {
+                        problems.push(format!("Bindings line {}: unknown input `{}`", number + 1, input));
+                    }
+                    binding
+                })
+                .collect();
+            self.0.insert(action, bindings);
+        }
+
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+        problems
+    }
+}
+
+/// this frame's input, in terms of actions
+#[derive(Resource, Default)]
+pub struct Actions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    pub orbit: Vec2, // drag-equivalent pixels this frame
+    pub pan: Vec2,   // same
+    pub zoom: f32,   // wheel lines this frame, positive zooms in
+}
+
+impl Actions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_actions(
+    bindings: Res<Bindings>,
+    mut actions: ResMut<Actions>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    mut pinch: EventReader<PinchGesture>,
+    gamepads: Query<&Gamepad>,
+    time: Res<Time>,
+) {
+    let actions = actions.as_mut();
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+
+    for (&action, inputs) in &bindings.0 {
+        for input in inputs {
+            let (pressed, just_pressed) = match *input {
+                Binding::Key(key) => (keyboard.pressed(key), keyboard.just_pressed(key)),
+                Binding::Mouse(button) => (mouse_buttons.pressed(button), mouse_buttons.just_pressed(button)),
+                Binding::Gamepad(button) => (
+                    gamepads.iter().any(|gamepad| gamepad.pressed(button)),
+                    gamepads.iter().any(|gamepad| gamepad.just_pressed(button)),
+                ),
+            };
+            if pressed {
+                actions.pressed.insert(action);
+            }
+            if just_pressed {
+                actions.just_pressed.insert(action);
+            }
+        }
+    }
+
+    let dt = time.delta_secs();
+    let axis = |negative: Action, positive: Action| {
+        actions.pressed(positive) as i32 as f32 - actions.pressed(negative) as i32 as f32
+    };
+
+    // orbit: right drag, arrows/WASD, left stick
+    let mut orbit = Vec2::new(axis(Action::OrbitLeft, Action::OrbitRight), axis(Action::OrbitDown, Action::OrbitUp))
+        * CAMERA_KEY_ORBIT_SPEED
+        * dt;
+    // pan: middle drag, right stick
+    let mut pan = Vec2::ZERO;
+    if actions.pressed(Action::OrbitDrag) {
+        orbit += mouse_motion.delta;
+    }
+    if actions.pressed(Action::PanDrag) {
+        pan += mouse_motion.delta;
+    }
+
+    for gamepad in &gamepads {
+        let stick = |value: Vec2| if value.length() > GAMEPAD_DEADZONE { value } else { Vec2::ZERO };
+        // sticks point up with +y, screen drags go down with +y
+        orbit += stick(gamepad.left_stick()) * Vec2::new(1.0, -1.0) * CAMERA_STICK_SPEED * dt;
+        pan += stick(gamepad.right_stick()) * Vec2::new(1.0, -1.0) * CAMERA_STICK_SPEED * dt;
+    }
+
+    // zoom: wheel, +/-, triggers, pinch
+    let scroll = match mouse_scroll.unit {
+        MouseScrollUnit::Line => mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => mouse_scroll.delta.y / PIXELS_PER_LINE,
+    };
+    let pinch: f32 = pinch.read().map(|gesture| gesture.0).sum();
+    let zoom = scroll + axis(Action::ZoomOut, Action::ZoomIn) * CAMERA_KEY_ZOOM_SPEED * dt + pinch * CAMERA_PINCH_ZOOM;
+
+    actions.orbit = orbit;
+    actions.pan = pan;
+    actions.zoom = zoom;
+}
+
+/// match arms from variant names
+macro_rules! parse_names {
+    ($name:ident -> $ty:ident { $($variant:ident),* $(,)? }) => {
+        match $name {
+            $(stringify!($variant) => Some($ty::$variant),)*
+            _ => None,
+        }
+    };
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    parse_names!(name -> KeyCode {
+        KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+        KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+        Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        ArrowLeft, ArrowRight, ArrowUp, ArrowDown,
+        Space, Tab, Enter, Escape, Backspace, Delete, Insert, Home, End, PageUp, PageDown,
+        Minus, Equal, BracketLeft, BracketRight, Backslash, Semicolon, Quote, Backquote, Comma, Period, Slash,
+        ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+    })
+}
+
+fn parse_mouse_button(name: &str) -> Option<MouseButton> {
+    parse_names!(name -> MouseButton { Left, Right, Middle, Back, Forward })
+}
+
+fn parse_gamepad_button(name: &str) -> Option<GamepadButton> {
+    parse_names!(name -> GamepadButton {
+        South, East, North, West, C, Z,
+        LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+        Select, Start, Mode, LeftThumb, RightThumb,
+        DPadUp, DPadDown, DPadLeft, DPadRight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prefixes() {
+        assert_eq!(Binding::parse("KeyW"), Some(Binding::Key(KeyCode::KeyW)));
+        assert_eq!(Binding::parse("Mouse:Right"), Some(Binding::Mouse(MouseButton::Right)));
+        assert_eq!(Binding::parse("Gamepad:South"), Some(Binding::Gamepad(GamepadButton::South)));
+        assert_eq!(Binding::parse("Gamepad:DPadLeft"), Some(Binding::Gamepad(GamepadButton::DPadLeft)));
+
+        // unknown prefix, unknown name, a key name behind the wrong prefix
+        assert_eq!(Binding::parse("Joystick:South"), None);
+        assert_eq!(Binding::parse("KeyWW"), None);
+        assert_eq!(Binding::parse("Mouse:South"), None);
+        assert_eq!(Binding::parse("Gamepad:KeyW"), None);
+    }
+
+    #[test]
+    fn apply_lines() {
+        let mut bindings = Bindings::default();
+        let problems = bindings.apply(
+            "# a comment\n\
+             \n\
+             orbit_drag = Mouse:Left  # trailing comment\n\
+             zoom_in = KeyZ, NotAKey, Gamepad:North\n\
+             no_such_action = KeyX\n\
+             missing equals\n\
+             pause =\n",
+        );
+
+        assert_eq!(bindings.0[&Action::OrbitDrag], vec![Binding::Mouse(MouseButton::Left)]);
+        // the unknown input is dropped, the rest of the line still applies
+        assert_eq!(
+            bindings.0[&Action::ZoomIn],
+            vec![Binding::Key(KeyCode::KeyZ), Binding::Gamepad(GamepadButton::North)]
+        );
+        // an empty right-hand side unbinds
+        assert!(bindings.0[&Action::Pause].is_empty());
+        // untouched actions keep their defaults
+        assert_eq!(bindings.0[&Action::PanDrag], Action::PanDrag.defaults());
+
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].contains("line 4") && problems[0].contains("NotAKey"));
+        assert!(problems[1].contains("line 5") && problems[1].contains("no_such_action"));
+        assert!(problems[2].contains("line 6"));
+    }
+
+    #[test]
+    fn shipped_bindings_parse() {
+        let text = std::fs::read_to_string(BINDINGS_PATH).unwrap();
+        let problems = Bindings::default().apply(&text);
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+}
@@ -0,0 +1,450 @@
+//! atmosphere.rs
+//!
+//! Precomputed atmospheric scattering lookup tables, after Hillaire 2020,
+//! "A Scalable and Production Ready Sky and Atmosphere Rendering Technique"
+//! https://sebh.github.io/publications/egsr2020.pdf
+//!
+//! built on the CPU from physical parameters (radii, scale heights, ozone) and uploaded as textures:
+//! - transmittance: light lost from a point to the top of the atmosphere, by altitude and zenith angle
+//! - multi-scattering: light scattered more than once, as an isotropic term by altitude and sun angle
+//!
+//! atmosphere.wgsl ray-marches the view ray once and reads everything else from these.
+//! tables are regenerated in the background whenever the parameters change
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+
+use std::f32::consts::PI;
+
+use crate::config::{
+    ATMOSPHERE_RADIUS, EARTH_RADIUS, GROUND_ALBEDO, MIE_COEFF, MIE_EXTINCTION, MIE_G, MIE_SCALE_HEIGHT,
+    OZONE_ABSORPTION, OZONE_CENTER_ALTITUDE, OZONE_WIDTH, RAYLEIGH_COEFF, RAYLEIGH_SCALE_HEIGHT,
+};
+use crate::systems::earth::Atmosphere;
+use crate::systems::earth::materials::AtmosphereMaterial;
+use crate::systems::settings::RenderSettings;
+
+pub const TRANSMITTANCE_LUT_SIZE: (usize, usize) = (256, 64); // zenith angle x altitude
+pub const MULTI_SCATTERING_LUT_SIZE: usize = 32; // sun angle x altitude
+
+const TRANSMITTANCE_STEPS: usize = 40;
+const MULTI_SCATTERING_STEPS: usize = 20;
+const MULTI_SCATTERING_DIRECTIONS: usize = 8; // per axis, 64 directions over the sphere
+
+/// physical description of an atmosphere, lengths in km and coefficients per km
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtmosphereParams {
+    pub bottom_radius: f32, // planet surface
+    pub top_radius: f32,    // density is treated as zero above this
+    pub rayleigh_scattering: Vec3, // at sea level
+    pub rayleigh_scale_height: f32,
+    pub mie_scattering: f32,
+    pub mie_extinction: f32, // scattering plus absorption
+    pub mie_scale_height: f32,
+    pub mie_g: f32, // Henyey-Greenstein asymmetry, forward scattering haze
+    pub ozone_absorption: Vec3, // at the peak of the layer
+    pub ozone_center: f32,      // altitude of the peak
+    pub ozone_width: f32,       // tent profile, zero at center +- width / 2
+    pub ground_albedo: f32,
+}
+
+impl Default for AtmosphereParams {
+    fn default() -> Self {
+        Self {
+            bottom_radius: EARTH_RADIUS,
+            top_radius: ATMOSPHERE_RADIUS,
+            rayleigh_scattering: Vec3::from(RAYLEIGH_COEFF),
+            rayleigh_scale_height: RAYLEIGH_SCALE_HEIGHT,
+            mie_scattering: MIE_COEFF,
+            mie_extinction: MIE_EXTINCTION,
+            mie_scale_height: MIE_SCALE_HEIGHT,
+            mie_g: MIE_G,
+            ozone_absorption: Vec3::from(OZONE_ABSORPTION),
+            ozone_center: OZONE_CENTER_ALTITUDE,
+            ozone_width: OZONE_WIDTH,
+            ground_albedo: GROUND_ALBEDO,
+        }
+    }
+}
+
+impl AtmosphereParams {
+    /// relative densities at an altitude: rayleigh, mie, ozone
+    pub fn density(&self, altitude: f32) -> (f32, f32, f32) {
+        let altitude = altitude.max(0.0);
+        let rayleigh = (-altitude / self.rayleigh_scale_height).exp();
+        let mie = (-altitude / self.mie_scale_height).exp();
+        let ozone = (1.0 - (altitude - self.ozone_center).abs() / (self.ozone_width * 0.5)).max(0.0);
+        (rayleigh, mie, ozone)
+    }
+
+    /// rayleigh and mie scattering coefficients at an altitude
+    pub fn scattering(&self, altitude: f32) -> (Vec3, f32) {
+        let (rayleigh, mie, _) = self.density(altitude);
+        (self.rayleigh_scattering * rayleigh, self.mie_scattering * mie)
+    }
+
+    /// total extinction coefficient at an altitude
+    pub fn extinction(&self, altitude: f32) -> Vec3 {
+        let (rayleigh, mie, ozone) = self.density(altitude);
+        self.rayleigh_scattering * rayleigh + Vec3::splat(self.mie_extinction * mie) + self.ozone_absorption * ozone
+    }
+}
+
+/// a small RGB table, sampled with clamped bilinear filtering like the GPU does
+#[derive(Clone, Debug)]
+pub struct Lut {
+    pub width: usize,
+    pub height: usize,
+    data: Vec<Vec3>,
+}
+
+impl Lut {
+    /// evaluate `f` at every texel centre
+    fn from_fn(width: usize, height: usize, f: impl Fn(Vec2) -> Vec3) -> Self {
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                data.push(f(Vec2::new((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32)));
+            }
+        }
+        Self { width, height, data }
+    }
+
+    pub fn texel(&self, x: usize, y: usize) -> Vec3 {
+        self.data[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+
+    pub fn sample(&self, uv: Vec2) -> Vec3 {
+        let x = (uv.x * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (uv.y * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (tx, ty) = (x.fract(), y.fract());
+
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), tx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), tx);
+        top.lerp(bottom, ty)
+    }
+
+    /// half float RGBA, filterable on every backend
+    pub fn to_image(&self) -> Image {
+        let data = self
+            .data
+            .iter()
+            .flat_map(|texel| texel.extend(1.0).to_array())
+            .flat_map(|c| half::f16::from_f32(c).to_le_bytes())
+            .collect();
+
+        Image::new(
+            Extent3d { width: self.width as u32, height: self.height as u32, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba16Float,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+}
+
+/// both tables for one set of parameters
+pub struct AtmosphereLuts {
+    pub params: AtmosphereParams,
+    pub transmittance: Lut,
+    pub multi_scattering: Lut,
+}
+
+impl AtmosphereLuts {
+    pub fn generate(params: AtmosphereParams) -> Self {
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        let transmittance = transmittance_lut(&params, width, height);
+        let multi_scattering = multi_scattering_lut(&params, &transmittance, MULTI_SCATTERING_LUT_SIZE);
+        Self { params, transmittance, multi_scattering }
+    }
+}
+
+// TRANSMITTANCE
+
+/// distances along a ray to where it enters and leaves a sphere around the planet centre
+/// the ray starts at radius `r`, `mu` is the cosine of its zenith angle
+fn ray_sphere(r: f32, mu: f32, radius: f32) -> Option<(f32, f32)> {
+    let discriminant = r * r * (mu * mu - 1.0) + radius * radius;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some((-r * mu - root, -r * mu + root))
+}
+
+/// does a ray from radius `r` going up at `mu` hit the ground
+fn hits_ground(params: &AtmosphereParams, r: f32, mu: f32) -> bool {
+    mu < 0.0 && ray_sphere(r, mu, params.bottom_radius).is_some_and(|(near, _)| near >= 0.0)
+}
+
+/// lut uv to altitude and zenith angle, non-linear so the horizon and low altitudes get more texels
+/// rays that hit the ground aren't stored, see `transmittance`
+pub fn transmittance_uv_to_r_mu(params: &AtmosphereParams, uv: Vec2) -> (f32, f32) {
+    let (bottom, top) = (params.bottom_radius, params.top_radius);
+    let h = (top * top - bottom * bottom).sqrt(); // distance to the top along the horizon, from the ground
+    let rho = h * uv.y;
+    let r = (rho * rho + bottom * bottom).sqrt();
+
+    let d_min = top - r;
+    let d_max = rho + h;
+    let d = d_min + uv.x * (d_max - d_min);
+    let mu = if d == 0.0 { 1.0 } else { (h * h - rho * rho - d * d) / (2.0 * r * d) };
+    (r, mu.clamp(-1.0, 1.0))
+}
+
+/// inverse of `transmittance_uv_to_r_mu`
+pub fn transmittance_r_mu_to_uv(params: &AtmosphereParams, r: f32, mu: f32) -> Vec2 {
+    let (bottom, top) = (params.bottom_radius, params.top_radius);
+    let h = (top * top - bottom * bottom).sqrt();
+    let rho = (r * r - bottom * bottom).max(0.0).sqrt();
+
+    let discriminant = r * r * (mu * mu - 1.0) + top * top;
+    let d = (-r * mu + discriminant.max(0.0).sqrt()).max(0.0);
+    let d_min = top - r;
+    let d_max = rho + h;
+    Vec2::new((d - d_min) / (d_max - d_min), rho / h)
+}
+
+/// integrate extinction from radius `r` to the top of the atmosphere
+fn optical_depth(params: &AtmosphereParams, r: f32, mu: f32) -> Vec3 {
+    let Some((_, exit)) = ray_sphere(r, mu, params.top_radius) else { return Vec3::ZERO; };
+    let length = exit.max(0.0);
+    let step = length / TRANSMITTANCE_STEPS as f32;
+
+    (0..TRANSMITTANCE_STEPS)
+        .map(|i| {
+            let t = (i as f32 + 0.5) * step;
+            // radius at distance t along the ray, law of cosines
+            let radius = (r * r + t * t + 2.0 * r * mu * t).sqrt();
+            params.extinction(radius - params.bottom_radius) * step
+        })
+        .sum()
+}
+
+pub fn transmittance_lut(params: &AtmosphereParams, width: usize, height: usize) -> Lut {
+    Lut::from_fn(width, height, |uv| {
+        let (r, mu) = transmittance_uv_to_r_mu(params, uv);
+        (-optical_depth(params, r, mu)).exp()
+    })
+}
+
+/// transmittance from radius `r` towards `mu` out of the atmosphere, zero if the ground is in the way
+pub fn transmittance(params: &AtmosphereParams, lut: &Lut, r: f32, mu: f32) -> Vec3 {
+    if hits_ground(params, r, mu) {
+        return Vec3::ZERO;
+    }
+    lut.sample(transmittance_r_mu_to_uv(params, r, mu))
+}
+
+// MULTIPLE SCATTERING
+
+/// lut uv to altitude (v) and cosine of the sun zenith angle (u)
+pub fn multi_scattering_uv_to_r_mu(params: &AtmosphereParams, uv: Vec2) -> (f32, f32) {
+    let r = params.bottom_radius + uv.y * (params.top_radius - params.bottom_radius);
+    (r, uv.x * 2.0 - 1.0)
+}
+
+/// isotropic multiple scattering per Hillaire section 5.5:
+/// second order light gathered from every direction, then the geometric series
+/// 1 + f + f^2 + ... for the fraction f that keeps being scattered
+pub fn multi_scattering_lut(params: &AtmosphereParams, transmittance_lut: &Lut, size: usize) -> Lut {
+    Lut::from_fn(size, size, |uv| {
+        let (r, mu_sun) = multi_scattering_uv_to_r_mu(params, uv);
+        let position = Vec3::new(0.0, r, 0.0);
+        let sun = Vec3::new((1.0 - mu_sun * mu_sun).max(0.0).sqrt(), mu_sun, 0.0);
+
+        let mut luminance = Vec3::ZERO;
+        let mut transfer = Vec3::ZERO;
+        let n = MULTI_SCATTERING_DIRECTIONS;
+        for j in 0..n {
+            for i in 0..n {
+                // stratified uniform directions over the sphere
+                let cos_theta = 1.0 - 2.0 * (j as f32 + 0.5) / n as f32;
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * (i as f32 + 0.5) / n as f32;
+                let direction = Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+
+                let (l, f) = integrate_scattered(params, transmittance_lut, position, direction, sun);
+                luminance += l;
+                transfer += f;
+            }
+        }
+
+        // isotropic phase over the whole sphere averages the directions
+        let count = (n * n) as f32;
+        let (luminance, transfer) = (luminance / count, transfer / count);
+        luminance / (Vec3::ONE - transfer).max(Vec3::splat(1e-4))
+    })
+}
+
+/// second order luminance and transfer along one ray, for unit sun illuminance
+fn integrate_scattered(
+    params: &AtmosphereParams,
+    transmittance_lut: &Lut,
+    position: Vec3,
+    direction: Vec3,
+    sun: Vec3,
+) -> (Vec3, Vec3) {
+    const ISOTROPIC_PHASE: f32 = 1.0 / (4.0 * PI);
+
+    let r = position.length();
+    let mu = direction.dot(position) / r;
+    let ground = hits_ground(params, r, mu);
+    let length = if ground {
+        ray_sphere(r, mu, params.bottom_radius).map_or(0.0, |(near, _)| near)
+    } else {
+        ray_sphere(r, mu, params.top_radius).map_or(0.0, |(_, far)| far.max(0.0))
+    };
+    let step = length / MULTI_SCATTERING_STEPS as f32;
+
+    let mut throughput = Vec3::ONE;
+    let mut luminance = Vec3::ZERO;
+    let mut transfer = Vec3::ZERO;
+    for i in 0..MULTI_SCATTERING_STEPS {
+        let sample = position + direction * (i as f32 + 0.5) * step;
+        let sample_r = sample.length();
+        let altitude = sample_r - params.bottom_radius;
+
+        let (rayleigh, mie) = params.scattering(altitude);
+        let scattering = rayleigh + Vec3::splat(mie);
+        let extinction = params.extinction(altitude).max(Vec3::splat(1e-9));
+        let step_transmittance = (-extinction * step).exp();
+
+        let sun_transmittance = transmittance(params, transmittance_lut, sample_r, sun.dot(sample) / sample_r);
+        let in_scattered = scattering * ISOTROPIC_PHASE * sun_transmittance;
+
+        // analytic integral over the step, energy conserving for thick steps
+        luminance += throughput * (in_scattered - in_scattered * step_transmittance) / extinction;
+        transfer += throughput * (scattering - scattering * step_transmittance) / extinction;
+        throughput *= step_transmittance;
+    }
+
+    // sunlight bouncing off the ground, lambertian
+    if ground {
+        let ground_point = position + direction * length;
+        let normal = ground_point.normalize();
+        let cos_sun = normal.dot(sun);
+        let sun_transmittance = transmittance(params, transmittance_lut, params.bottom_radius, cos_sun);
+        luminance += throughput * sun_transmittance * cos_sun.max(0.0) * params.ground_albedo / PI;
+    }
+
+    (luminance, transfer)
+}
+
+// BEVY
+
+/// tracks which parameters the atmosphere material's tables were built from
+#[derive(Resource, Default)]
+pub struct AtmosphereLutState {
+    applied: Option<AtmosphereParams>,
+    task: Option<Task<AtmosphereLuts>>,
+}
+
+/// parameters from config, adjusted by the settings panel
+fn current_params(settings: &RenderSettings) -> AtmosphereParams {
+    let mut params = AtmosphereParams::default();
+    params.rayleigh_scattering *= settings.rayleigh_scale;
+    params
+}
+
+/// rebuild the tables in the background when the parameters change, then swap them in
+pub fn update_luts(
+    settings: Res<RenderSettings>,
+    mut state: ResMut<AtmosphereLutState>,
+    atmosphere_query: Query<&MeshMaterial3d<AtmosphereMaterial>, With<Atmosphere>>,
+    mut materials: ResMut<Assets<AtmosphereMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // one build at a time, a slider drag only rebuilds for where it ends up
+    if let Some(task) = state.task.as_mut() {
+        let Some(luts) = block_on(future::poll_once(task)) else { return; };
+        state.task = None;
+
+        if let Ok(material_handle) = atmosphere_query.single()
+            && let Some(material) = materials.get_mut(&material_handle.0)
+        {
+            material.atmosphere_uniform.set_params(&luts.params);
+            material.transmittance_lut = images.add(luts.transmittance.to_image());
+            material.multi_scattering_lut = images.add(luts.multi_scattering.to_image());
+        }
+        state.applied = Some(luts.params);
+    }
+
+    let params = current_params(&settings);
+    if state.applied != Some(params) {
+        state.task = Some(AsyncComputeTaskPool::get().spawn(async move { AtmosphereLuts::generate(params) }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luts() -> AtmosphereLuts {
+        AtmosphereLuts::generate(AtmosphereParams::default())
+    }
+
+    #[test]
+    fn transmittance_uv_mapping_round_trips() {
+        let params = AtmosphereParams::default();
+        for &(u, v) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.05), (0.3, 0.95)] {
+            let (r, mu) = transmittance_uv_to_r_mu(&params, Vec2::new(u, v));
+            let uv = transmittance_r_mu_to_uv(&params, r, mu);
+            assert!((uv - Vec2::new(u, v)).abs().max_element() < 1e-3, "{:?} -> {:?}", (u, v), uv);
+        }
+    }
+
+    #[test]
+    fn transmittance_is_physical() {
+        let params = AtmosphereParams::default();
+        let luts = luts();
+        let ground = params.bottom_radius + 0.01;
+
+        // nothing left to cross at the top
+        let top = transmittance(&params, &luts.transmittance, params.top_radius, 1.0);
+        assert!(top.min_element() > 0.999, "{:?}", top);
+
+        // straight up from the ground: some loss, blue scattered more than red
+        let zenith = transmittance(&params, &luts.transmittance, ground, 1.0);
+        assert!(zenith.max_element() < 1.0 && zenith.min_element() > 0.5, "{:?}", zenith);
+        assert!(zenith.z < zenith.x, "{:?}", zenith);
+
+        // the horizon crosses far more air, below it the ground blocks everything
+        let horizon = transmittance(&params, &luts.transmittance, ground, 0.01);
+        assert!(horizon.x < zenith.x && horizon.z < zenith.z, "{:?} {:?}", horizon, zenith);
+        assert_eq!(transmittance(&params, &luts.transmittance, ground, -0.5), Vec3::ZERO);
+    }
+
+    #[test]
+    fn transmittance_lut_matches_direct_integration() {
+        let params = AtmosphereParams::default();
+        let luts = luts();
+        let (r, mu) = (params.bottom_radius + 2.0, 0.3);
+
+        let direct = (-optical_depth(&params, r, mu)).exp();
+        let sampled = transmittance(&params, &luts.transmittance, r, mu);
+        assert!((direct - sampled).abs().max_element() < 0.01, "{:?} {:?}", direct, sampled);
+    }
+
+    #[test]
+    fn multi_scattering_is_finite_and_lit_by_day() {
+        let luts = luts();
+        let lut = &luts.multi_scattering;
+
+        for y in 0..lut.height {
+            for x in 0..lut.width {
+                let texel = lut.texel(x, y);
+                assert!(texel.is_finite() && texel.min_element() >= 0.0, "{:?} at {} {}", texel, x, y);
+            }
+        }
+
+        // near the ground, noon beats a sun well below the horizon
+        let day = lut.sample(Vec2::new(1.0, 0.0));
+        let night = lut.sample(Vec2::new(0.0, 0.0));
+        assert!(day.z > night.z, "{:?} {:?}", day, night);
+        assert!(day.max_element() < 1.0, "{:?}", day);
+    }
+}
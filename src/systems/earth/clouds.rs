@@ -0,0 +1,207 @@
+//! clouds.rs
+//!
+//! Time-varying cloud layer
+//! dated cloud images in `CLOUD_SEQUENCE_DIR` are picked by sim time and cross-faded,
+//! file names carry the timestamp, e.g. `20250101T1200Z.png` or `2025-01-01_12-00.tif`.
+//! without any, the single global `clouds.tif` is used.
+//! only the two frames around the current time are kept loaded
+//!
+//! clouds also drift around the earth's axis at their own rate, opacity comes from `RenderSettings`
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::config::{CLOUD_DRIFT_DEG_PER_HOUR, CLOUD_SEQUENCE_DIR};
+use crate::systems::earth::Clouds;
+use crate::systems::earth::materials::CloudMaterial;
+use crate::systems::settings::RenderSettings;
+use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+
+const FRAME_EXTENSIONS: [&str; 3] = ["png", "tif", "tiff"];
+const OPACITY_STEP: f32 = 0.1;
+
+/// timestamp formats accepted in file names, tried in order
+const TIMESTAMP_FORMATS: [&str; 6] = [
+    "%Y%m%dT%H%M%SZ",
+    "%Y%m%dT%H%MZ",
+    "%Y%m%dT%H%M",
+    "%Y%m%d_%H%M",
+    "%Y-%m-%dT%H-%M-%SZ",
+    "%Y-%m-%d_%H-%M",
+];
+
+/// one dated cloud image
+#[derive(Clone, Debug)]
+pub struct CloudFrame {
+    pub time: DateTime<Utc>,
+    pub path: String, // relative to assets
+}
+
+#[derive(Resource)]
+pub struct CloudLayer {
+    frames: Vec<CloudFrame>, // sorted by time
+    loaded: HashMap<usize, Handle<Image>>,
+    pub drift_deg_per_hour: f32,
+    drift_angle: f32,                     // radians around the earth's axis
+    last_sim_time: Option<DateTime<Utc>>, // drift follows sim time, including running backwards
+}
+
+impl Default for CloudLayer {
+    fn default() -> Self {
+        Self {
+            frames: discover_frames(CLOUD_SEQUENCE_DIR),
+            loaded: HashMap::new(),
+            drift_deg_per_hour: CLOUD_DRIFT_DEG_PER_HOUR,
+            drift_angle: 0.0,
+            last_sim_time: None,
+        }
+    }
+}
+
+impl CloudLayer {
+    /// frames either side of `time` and how far between them it is
+    fn bracket(&self, time: DateTime<Utc>) -> Option<(usize, usize, f32)> {
+        let last = self.frames.len().checked_sub(1)?;
+        let next = self.frames.partition_point(|frame| frame.time <= time);
+
+        Some(match next {
+            0 => (0, 0, 0.0),                 // before the first frame
+            n if n > last => (last, last, 0.0), // after the last
+            n => {
+                let (a, b) = (&self.frames[n - 1], &self.frames[n]);
+                let span = (b.time - a.time).num_milliseconds().max(1) as f32;
+                let blend = (time - a.time).num_milliseconds() as f32 / span;
+                (n - 1, n, blend.clamp(0.0, 1.0))
+            }
+        })
+    }
+
+    fn handle(&mut self, index: usize, asset_server: &AssetServer) -> Handle<Image> {
+        let path = &self.frames[index].path;
+        self.loaded.entry(index).or_insert_with(|| asset_server.load(path.clone())).clone()
+    }
+}
+
+/// list dated images in a directory under assets/
+fn discover_frames(dir: &str) -> Vec<CloudFrame> {
+    let Ok(entries) = std::fs::read_dir(format!("assets/{}", dir)) else {
+        return Vec::new();
+    };
+
+    let mut frames: Vec<CloudFrame> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            if !FRAME_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+
+            let stem = path.file_stem()?.to_str()?;
+            let Some(time) = parse_timestamp(stem) else {
+                warn!("Skipping cloud image without a timestamp in its name: {:?}", path);
+                return None;
+            };
+            let name = path.file_name()?.to_str()?;
+            Some(CloudFrame { time, path: format!("{}/{}", dir, name) })
+        })
+        .collect();
+
+    frames.sort_by_key(|frame| frame.time);
+    if !frames.is_empty() {
+        info!(
+            "Loaded {} cloud frames, {} to {}",
+            frames.len(),
+            frames[0].time,
+            frames[frames.len() - 1].time
+        );
+    }
+    frames
+}
+
+/// timestamp from a file stem, date-only names are taken as midnight UTC
+fn parse_timestamp(stem: &str) -> Option<DateTime<Utc>> {
+    TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(stem, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(stem, "%Y%m%d")
+                .or_else(|_| NaiveDate::parse_from_str(stem, "%Y-%m-%d"))
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+        .map(|naive| naive.and_utc())
+}
+
+/// adjust cloud opacity with [ and ]
+pub fn adjust_opacity(
+    actions: Res<Actions>,
+    mut settings: ResMut<RenderSettings>,
+) {
+    if actions.just_pressed(Action::CloudOpacityDown) {
+        settings.cloud_opacity = (settings.cloud_opacity - OPACITY_STEP).max(0.0);
+    }
+    if actions.just_pressed(Action::CloudOpacityUp) {
+        settings.cloud_opacity = (settings.cloud_opacity + OPACITY_STEP).min(1.0);
+    }
+}
+
+/// pick and blend cloud frames for the current sim time, apply drift and opacity
+pub fn update(
+    time_state: Res<TimeState>,
+    settings: Res<RenderSettings>,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    mut layer: ResMut<CloudLayer>,
+    mut clouds: Query<(&MeshMaterial3d<CloudMaterial>, &mut Transform), With<Clouds>>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+) {
+    let Ok((material_handle, mut transform)) = clouds.single_mut() else { return; };
+
+    // drift by however much sim time moved since last frame
+    let sim_time = time_state.sim_time;
+    if let Some(last) = layer.last_sim_time {
+        let hours = (sim_time - last).num_milliseconds() as f32 / 3_600_000.0;
+        layer.drift_angle = (layer.drift_angle + layer.drift_deg_per_hour.to_radians() * hours)
+            .rem_euclid(std::f32::consts::TAU);
+    }
+    layer.last_sim_time = Some(sim_time);
+
+    // the sphere mesh has its poles on z, tilted onto the earth's axis
+    let rotation = Quat::from_rotation_y(layer.drift_angle) * Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2);
+    transform.set_if_neq(transform.with_rotation(rotation));
+
+    let Some(material) = materials.get(&material_handle.0) else { return; };
+    let mut target = (material.cloud_texture.clone(), material.next_cloud_texture.clone(), material.cloud_uniform.blend);
+
+    if let Some((current, next, blend)) = layer.bracket(sim_time) {
+        let current_handle = layer.handle(current, &asset_server);
+        let next_handle = layer.handle(next, &asset_server);
+
+        // drop frames that are no longer around the current time
+        layer.loaded.retain(|&index, _| index == current || index == next);
+
+        // keep showing what's there until the new frames finish loading
+        if images.contains(&current_handle) {
+            target = if images.contains(&next_handle) {
+                (current_handle, next_handle, blend)
+            } else {
+                (current_handle.clone(), current_handle, 0.0)
+            };
+        }
+    }
+
+    // only touch the material when something changed, edits re-upload it
+    let unchanged = material.cloud_uniform.opacity == settings.cloud_opacity
+        && (&material.cloud_texture, &material.next_cloud_texture, material.cloud_uniform.blend)
+            == (&target.0, &target.1, target.2);
+    if unchanged {
+        return;
+    }
+
+    let Some(material) = materials.get_mut(&material_handle.0) else { return; };
+    material.cloud_uniform.opacity = settings.cloud_opacity;
+    (material.cloud_texture, material.next_cloud_texture, material.cloud_uniform.blend) = target;
+}
@@ -0,0 +1,101 @@
+//! cursor.rs
+//!
+//! Geographic location under the mouse cursor
+//! the cursor ray is intersected with the globe (terrain included), the hit is taken into
+//! the earth's rotating frame and converted to latitude/longitude for the UI readout
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::{DISPLACEMENT_SCALE, EARTH_RADIUS};
+use crate::systems::earth::Earth;
+use crate::systems::earth::heightmap::HeightFilter;
+use crate::systems::earth::lod::TerrainLod;
+use crate::systems::earth::uv::LatLon;
+
+const TERRAIN_ITERATIONS: usize = 3; // refinements of the hit against the displaced surface
+
+/// a point on the surface
+#[derive(Clone, Copy, Debug)]
+pub struct GeoPoint {
+    pub latitude: f32,  // degrees
+    pub longitude: f32, // degrees, east positive
+    pub height: f32,    // km of terrain above EARTH_RADIUS
+}
+
+impl GeoPoint {
+    /// local mean solar time, UTC shifted by 4 minutes per degree of longitude
+    pub fn solar_time(&self, utc: DateTime<Utc>) -> DateTime<Utc> {
+        utc + Duration::milliseconds((self.longitude as f64 / 15.0 * 3_600_000.0) as i64)
+    }
+}
+
+/// what the cursor is over, None when it's off the globe
+#[derive(Resource, Default)]
+pub struct CursorLocation(pub Option<GeoPoint>);
+
+/// nearest positive distance along a ray to a sphere at the origin
+pub fn ray_sphere(origin: Vec3, direction: Vec3, radius: f32) -> Option<f32> {
+    let b = origin.dot(direction);
+    let c = origin.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    [-b - root, -b + root].into_iter().find(|&t| t > 0.0)
+}
+
+pub fn update(
+    mut location: ResMut<CursorLocation>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    earth: Query<&GlobalTransform, With<Earth>>,
+    lod: Option<Res<TerrainLod>>,
+) {
+    let (Ok(window), Ok((camera, camera_transform)), Ok(earth)) = (window.single(), camera.single(), earth.single())
+    else {
+        return;
+    };
+
+    // the globe may only have part of the window, or none of it, see map.rs
+    let hit = window
+        .cursor_position()
+        .filter(|&cursor| camera.is_active && camera.logical_viewport_rect().is_some_and(|rect| rect.contains(cursor)))
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+        .and_then(|ray| {
+            // work in the earth's frame so longitude follows its rotation
+            let to_local = earth.affine().inverse();
+            let origin = to_local.transform_point3(ray.origin);
+            let direction = to_local.transform_vector3(*ray.direction).normalize();
+
+            // start on the bare sphere, then settle onto the terrain height there
+            let mut t = ray_sphere(origin, direction, EARTH_RADIUS)?;
+            let mut height = 0.0;
+            if let Some(heightmap) = lod.as_ref().map(|lod| lod.displacement()) {
+                for _ in 0..TERRAIN_ITERATIONS {
+                    let (u, v) = LatLon::from(origin + direction * t).to_uv();
+                    height = heightmap.sample(u, v, HeightFilter::Bilinear) * DISPLACEMENT_SCALE;
+                    t = ray_sphere(origin, direction, EARTH_RADIUS + height).unwrap_or(t);
+                }
+            }
+
+            let (latitude, longitude) = LatLon::from(origin + direction * t).as_degrees();
+            Some(GeoPoint { latitude, longitude, height })
+        });
+
+    location.0 = hit;
+}
+
+/// "12.34°N 56.78°W"
+pub fn format_lat_lon(point: &GeoPoint) -> String {
+    format!(
+        "{:.2}°{} {:.2}°{}",
+        point.latitude.abs(),
+        if point.latitude >= 0.0 { "N" } else { "S" },
+        point.longitude.abs(),
+        if point.longitude >= 0.0 { "E" } else { "W" },
+    )
+}
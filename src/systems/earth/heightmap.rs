@@ -0,0 +1,287 @@
+//! heightmap.rs
+//!
+//! Height field decoded from the image formats Bevy loads for PNG and TIFF
+//! (8/16-bit grayscale, RGBA, half/full float), normalised to 0..1.
+//! shared by mesh displacement and normal map generation
+//!
+//! sampling treats the map as equirectangular: longitude (u) wraps, latitude (v) clamps
+
+use std::hash::Hasher;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightFilter {
+    Bilinear,
+    Bicubic,
+}
+
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub width: usize,
+    pub height: usize,
+    data: Vec<f32>, // row-major, first channel only
+}
+
+impl Heightmap {
+    /// flat map, used when no displacement is available
+    pub fn flat() -> Self {
+        Self { width: 1, height: 1, data: vec![0.0] }
+    }
+
+    /// decode the first channel of an image into normalised heights
+    /// None if the format isn't supported or the data is missing
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let width = image.texture_descriptor.size.width as usize;
+        let height = image.texture_descriptor.size.height as usize;
+        let bytes = image.data.as_ref()?;
+        let format = image.texture_descriptor.format;
+
+        // decoders get the whole texel and read its first channel
+        let decode: fn(&[u8]) -> f32 = match format {
+            // 8-bit, colour spaces are ignored, heights are stored raw
+            TextureFormat::R8Unorm
+            | TextureFormat::Rg8Unorm
+            | TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb => |b| b[0] as f32 / 255.0,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => |b| b[2] as f32 / 255.0, // red is third
+
+            // 16-bit, bevy loads grayscale PNG/TIFF as Uint and RGBA as Unorm
+            TextureFormat::R16Unorm
+            | TextureFormat::R16Uint
+            | TextureFormat::Rg16Unorm
+            | TextureFormat::Rg16Uint
+            | TextureFormat::Rgba16Unorm
+            | TextureFormat::Rgba16Uint => decode_u16,
+            TextureFormat::R16Float | TextureFormat::Rg16Float | TextureFormat::Rgba16Float => decode_f16,
+
+            // 32-bit float, assumed to already be normalised
+            TextureFormat::R32Float | TextureFormat::Rg32Float | TextureFormat::Rgba32Float => decode_f32,
+
+            _ => {
+                warn!("Unsupported heightmap format: {:?}", format);
+                return None;
+            }
+        };
+
+        let texel_size = format.block_copy_size(None)? as usize;
+        if bytes.len() < width * height * texel_size {
+            return None;
+        }
+
+        let data = bytes.chunks_exact(texel_size).take(width * height).map(decode).collect();
+
+        Some(Self { width, height, data })
+    }
+
+    /// hash of the decoded heights, stable between runs
+    /// used to tell whether cached data derived from the map is still valid
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write_u64(self.width as u64);
+        hasher.write_u64(self.height as u64);
+        for value in &self.data {
+            hasher.write_u32(value.to_bits());
+        }
+        hasher.finish()
+    }
+
+    /// texel lookup, x wraps around (longitude), y clamps (latitude)
+    pub fn texel(&self, x: i64, y: i64) -> f32 {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    /// sample at UV coordinates
+    pub fn sample(&self, u: f32, v: f32, filter: HeightFilter) -> f32 {
+        // texel centres sit at half-integer coordinates
+        let x = u * self.width as f32 - 0.5;
+        let y = v.clamp(0.0, 1.0) * self.height as f32 - 0.5;
+
+        match filter {
+            HeightFilter::Bilinear => self.sample_bilinear(x, y),
+            HeightFilter::Bicubic => self.sample_bicubic(x, y),
+        }
+    }
+
+    fn sample_bilinear(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), tx);
+        let bottom = lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+
+    /// Catmull-Rom over the surrounding 4x4 texels
+    /// can overshoot slightly, so the result is clamped to the map's range
+    fn sample_bicubic(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let mut rows = [0.0; 4];
+        for (j, row) in rows.iter_mut().enumerate() {
+            let yy = y0 + j as i64 - 1;
+            *row = catmull_rom(
+                self.texel(x0 - 1, yy),
+                self.texel(x0, yy),
+                self.texel(x0 + 1, yy),
+                self.texel(x0 + 2, yy),
+                tx,
+            );
+        }
+
+        catmull_rom(rows[0], rows[1], rows[2], rows[3], ty).clamp(0.0, 1.0)
+    }
+}
+
+/// FNV-1a, unlike std's DefaultHasher the output doesn't change between Rust versions
+/// https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// https://en.wikipedia.org/wiki/Cubic_Hermite_spline#Catmull%E2%80%93Rom_spline
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+fn decode_u16(b: &[u8]) -> f32 {
+    u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0
+}
+
+fn decode_f16(b: &[u8]) -> f32 {
+    half::f16::from_bits(u16::from_le_bytes([b[0], b[1]])).to_f32()
+}
+
+fn decode_f32(b: &[u8]) -> f32 {
+    f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    fn image(width: u32, height: u32, format: TextureFormat, bytes: Vec<u8>) -> Image {
+        Image::new(
+            Extent3d { width, height, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            bytes,
+            format,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    /// 4x3 map from one R32Float texel per height, row-major
+    fn map(heights: &[f32]) -> Heightmap {
+        let bytes = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+        Heightmap::from_image(&image(4, 3, TextureFormat::R32Float, bytes)).unwrap()
+    }
+
+    const HEIGHTS: [f32; 12] = [0.0, 0.25, 0.5, 1.0, 0.75, 0.1, 0.9, 0.3, 0.2, 0.6, 0.4, 0.8];
+
+    #[test]
+    fn decodes_first_channel_of_each_format() {
+        // two texels, first channel 0 then full scale, other channels are noise that must be ignored
+        let f16 = |v: f32| half::f16::from_f32(v).to_le_bytes();
+        let cases: Vec<(TextureFormat, Vec<u8>, f32)> = vec![
+            (TextureFormat::R8Unorm, vec![0, 255], 1.0),
+            (TextureFormat::Rg8Unorm, vec![0, 9, 255, 9], 1.0),
+            (TextureFormat::Rgba8Unorm, vec![0, 9, 9, 9, 51, 9, 9, 9], 0.2),
+            (TextureFormat::Rgba8UnormSrgb, vec![0, 9, 9, 9, 255, 9, 9, 9], 1.0),
+            (TextureFormat::Bgra8Unorm, vec![9, 9, 0, 9, 9, 9, 255, 9], 1.0), // red is the third byte
+            (TextureFormat::R16Unorm, [0u16, 65535].iter().flat_map(|v| v.to_le_bytes()).collect(), 1.0),
+            (TextureFormat::R16Uint, [0u16, 13107].iter().flat_map(|v| v.to_le_bytes()).collect(), 0.2),
+            (TextureFormat::Rg16Uint, [0u16, 9, 65535, 9].iter().flat_map(|v| v.to_le_bytes()).collect(), 1.0),
+            (TextureFormat::Rgba16Unorm, [0u16, 9, 9, 9, 65535, 9, 9, 9].iter().flat_map(|v| v.to_le_bytes()).collect(), 1.0),
+            (TextureFormat::R16Float, [f16(0.0), f16(0.5)].concat(), 0.5),
+            (TextureFormat::Rgba16Float, [f16(0.0), f16(9.0), f16(9.0), f16(9.0), f16(0.75), f16(9.0), f16(9.0), f16(9.0)].concat(), 0.75),
+            (TextureFormat::R32Float, [0.0f32, 0.3].iter().flat_map(|v| v.to_le_bytes()).collect(), 0.3),
+            (TextureFormat::Rg32Float, [0.0f32, 9.0, 0.6, 9.0].iter().flat_map(|v| v.to_le_bytes()).collect(), 0.6),
+        ];
+
+        for (format, bytes, expected) in cases {
+            let heightmap = Heightmap::from_image(&image(2, 1, format, bytes))
+                .unwrap_or_else(|| panic!("{format:?} should decode"));
+            assert_eq!(heightmap.texel(0, 0), 0.0, "{format:?}");
+            assert!((heightmap.texel(1, 0) - expected).abs() < 1e-6, "{format:?}: {}", heightmap.texel(1, 0));
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_or_short_images() {
+        assert!(Heightmap::from_image(&image(2, 1, TextureFormat::R32Uint, vec![0; 8])).is_none());
+
+        let mut short = image(2, 2, TextureFormat::R8Unorm, vec![0; 4]);
+        short.data = Some(vec![0; 3]);
+        assert!(Heightmap::from_image(&short).is_none());
+    }
+
+    #[test]
+    fn exact_at_texel_centres() {
+        let heightmap = map(&HEIGHTS);
+        for y in 0..3 {
+            for x in 0..4 {
+                let (u, v) = ((x as f32 + 0.5) / 4.0, (y as f32 + 0.5) / 3.0);
+                let expected = HEIGHTS[y * 4 + x];
+                for filter in [HeightFilter::Bilinear, HeightFilter::Bicubic] {
+                    let sampled = heightmap.sample(u, v, filter);
+                    assert!((sampled - expected).abs() < 1e-6, "{filter:?} at ({x}, {y}): {sampled} vs {expected}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wraps_across_the_antimeridian() {
+        let heightmap = map(&HEIGHTS);
+        for v in [0.1, 0.5, 0.9] {
+            for filter in [HeightFilter::Bilinear, HeightFilter::Bicubic] {
+                // u = 0 and u = 1 are the same meridian
+                let (left, right) = (heightmap.sample(0.0, v, filter), heightmap.sample(1.0, v, filter));
+                assert!((left - right).abs() < 1e-6, "{filter:?} v {v}: {left} vs {right}");
+
+                // and nothing jumps either side of it
+                let (before, after) = (heightmap.sample(1.0 - 1e-4, v, filter), heightmap.sample(1e-4, v, filter));
+                assert!((before - after).abs() < 1e-2, "{filter:?} v {v}: {before} vs {after}");
+            }
+        }
+
+        // halfway between the last column and the first
+        let row = 4;
+        let seam = heightmap.sample(0.0, 1.5 / 3.0, HeightFilter::Bilinear);
+        assert!((seam - (HEIGHTS[row + 3] + HEIGHTS[row]) / 2.0).abs() < 1e-6);
+    }
+}
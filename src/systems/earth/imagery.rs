@@ -0,0 +1,205 @@
+//! imagery.rs
+//!
+//! Tiled high-resolution imagery streamed per terrain chunk
+//! tiles are read from slippy-map style directories on disk, `{root}/{z}/{x}/{y}.png`
+//! (or .jpg/.jpeg/.webp) in Web Mercator, XYZ or TMS row order
+//!
+//! once a LOD chunk is deep enough, the tiles covering it are reprojected into a small
+//! equirectangular texture for that chunk alone. pixels without a tile are left transparent,
+//! and the shader blends the detail over the global texture, so gaps fall back to it
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use image::RgbaImage;
+
+use crate::config::{IMAGERY_CHUNK_TEXTURE_SIZE, IMAGERY_DAY_TILES, IMAGERY_NIGHT_TILES, IMAGERY_TILES_TMS};
+use crate::systems::earth::lod::Chunk;
+use crate::systems::earth::mesh::cube_point_to_sphere_point;
+use crate::systems::earth::uv::LatLon;
+
+const TILE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+const MAX_MERCATOR_LATITUDE: f64 = 85.05112878; // web mercator stops here
+
+/// row numbering of a tile directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileScheme {
+    Xyz, // row 0 at the north, slippy-map convention
+    Tms, // row 0 at the south
+}
+
+/// one tiled layer on disk
+#[derive(Clone, Debug)]
+pub struct TileSource {
+    root: PathBuf,
+    scheme: TileScheme,
+    min_zoom: u32,
+    max_zoom: u32,
+}
+
+impl TileSource {
+    /// None if the directory doesn't exist or has no zoom levels
+    pub fn open(root: impl AsRef<Path>, scheme: TileScheme) -> Option<Self> {
+        let root = root.as_ref().to_path_buf();
+
+        // zoom levels are numbered subdirectories, pyramids don't always start at 0
+        let zooms: Vec<u32> = std::fs::read_dir(&root)
+            .ok()?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .collect();
+        let (min_zoom, max_zoom) = (*zooms.iter().min()?, *zooms.iter().max()?);
+
+        info!("Imagery tiles found in {:?}, zoom {}-{}", root, min_zoom, max_zoom);
+        Some(Self { root, scheme, min_zoom, max_zoom })
+    }
+
+    fn load_tile(&self, z: u32, x: u32, y: u32) -> Option<RgbaImage> {
+        let row = match self.scheme {
+            TileScheme::Xyz => y,
+            TileScheme::Tms => (1 << z) - 1 - y,
+        };
+        let base = self.root.join(z.to_string()).join(x.to_string()).join(row.to_string());
+
+        TILE_EXTENSIONS
+            .iter()
+            .map(|ext| base.with_extension(ext))
+            .find(|path| path.exists())
+            .and_then(|path| match image::open(&path) {
+                Ok(tile) => Some(tile.to_rgba8()),
+                Err(e) => {
+                    warn!("Failed to read imagery tile {:?}: {:?}", path, e);
+                    None
+                }
+            })
+    }
+
+    /// reproject the tiles covering `uv_rect` into a `size` x `size` equirectangular image
+    /// None if no tile covers any of it
+    pub fn compose(&self, uv_rect: Vec4, size: u32) -> Option<Image> {
+        let zoom = self.zoom_for(uv_rect, size);
+        let tiles_per_axis = 1u32 << zoom;
+        let mut tiles: HashMap<(u32, u32), Option<RgbaImage>> = HashMap::new();
+
+        let mut data = vec![0u8; (size * size * 4) as usize];
+        let mut covered = false;
+
+        for j in 0..size {
+            let v = uv_rect.y + (j as f32 + 0.5) / size as f32 * uv_rect.w;
+            let latitude = (90.0 - v as f64 * 180.0).clamp(-90.0, 90.0);
+            if latitude.abs() > MAX_MERCATOR_LATITUDE {
+                continue;
+            }
+
+            // web mercator row, 0..1 from north to south
+            let lat_rad = latitude.to_radians();
+            let mercator_y = (1.0 - (lat_rad.tan() + 1.0 / lat_rad.cos()).ln() / std::f64::consts::PI) / 2.0;
+            let tile_y = mercator_y * tiles_per_axis as f64;
+
+            for i in 0..size {
+                let u = uv_rect.x + (i as f32 + 0.5) / size as f32 * uv_rect.z;
+                let tile_x = (u as f64).rem_euclid(1.0) * tiles_per_axis as f64;
+
+                let key = (
+                    (tile_x as u32).min(tiles_per_axis - 1),
+                    (tile_y as u32).min(tiles_per_axis - 1),
+                );
+                let tile = tiles.entry(key).or_insert_with(|| self.load_tile(zoom, key.0, key.1));
+                let Some(tile) = tile else { continue; };
+
+                // nearest pixel within the tile
+                let px = ((tile_x.fract() * tile.width() as f64) as u32).min(tile.width() - 1);
+                let py = ((tile_y.fract() * tile.height() as f64) as u32).min(tile.height() - 1);
+
+                let index = ((j * size + i) * 4) as usize;
+                data[index..index + 4].copy_from_slice(&tile.get_pixel(px, py).0);
+                covered = true;
+            }
+        }
+
+        covered.then(|| Image::new(
+            Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        ))
+    }
+
+    /// coarsest zoom with at least as many pixels as the output over the rect
+    fn zoom_for(&self, uv_rect: Vec4, size: u32) -> u32 {
+        // span as a fraction of a full turn, latitude covers half a turn
+        let span = uv_rect.z.min(uv_rect.w * 0.5).max(1e-6);
+        let tile_size = 256.0;
+        let zoom = (size as f32 / (span * tile_size)).log2().ceil().max(0.0) as u32;
+        zoom.clamp(self.min_zoom, self.max_zoom)
+    }
+}
+
+/// all the tiled layers found at startup
+#[derive(Clone, Debug, Default)]
+pub struct ImagerySources {
+    pub day: Option<TileSource>,
+    pub night: Option<TileSource>,
+}
+
+impl ImagerySources {
+    /// look for the directories in config, None if there are none
+    pub fn from_config() -> Option<Self> {
+        let scheme = if IMAGERY_TILES_TMS { TileScheme::Tms } else { TileScheme::Xyz };
+        let sources = Self {
+            day: TileSource::open(IMAGERY_DAY_TILES, scheme),
+            night: TileSource::open(IMAGERY_NIGHT_TILES, scheme),
+        };
+        (sources.day.is_some() || sources.night.is_some()).then_some(sources)
+    }
+}
+
+/// detail textures for one chunk
+pub struct ChunkImagery {
+    pub uv_rect: Vec4, // u, v, width, height in the earth's global uv space
+    pub day: Option<Image>,
+    pub night: Option<Image>,
+}
+
+/// uv bounds of a chunk, unwrapped the same way as the chunk mesh (see `generate_face`)
+pub fn chunk_uv_rect(chunk: &Chunk) -> Vec4 {
+    const SAMPLES: u32 = 16;
+
+    let axis_a = Vec3::new(chunk.normal.y, chunk.normal.z, chunk.normal.x);
+    let axis_b = axis_a.cross(chunk.normal);
+    let (reference_u, _) = LatLon::from(chunk.center_direction()).to_uv();
+
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for y in 0..=SAMPLES {
+        for x in 0..=SAMPLES {
+            let percent = Vec2::new(x as f32, y as f32) / SAMPLES as f32;
+            let cube_point = chunk.normal
+                + (percent.x * chunk.size - chunk.x_offset) * axis_a
+                + (percent.y * chunk.size - chunk.y_offset) * axis_b;
+            let (u, v) = LatLon::from(cube_point_to_sphere_point(cube_point).normalize()).to_uv_near(reference_u);
+            min = min.min(Vec2::new(u, v));
+            max = max.max(Vec2::new(u, v));
+        }
+    }
+
+    // edges between samples bulge slightly, pad by a little
+    let pad = (max - min) * 0.02;
+    let (min, max) = (min - pad, max + pad);
+    Vec4::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
+
+pub fn spawn_imagery_task(chunk: Chunk, sources: Arc<ImagerySources>) -> Task<Option<ChunkImagery>> {
+    AsyncComputeTaskPool::get().spawn(async move {
+        let uv_rect = chunk_uv_rect(&chunk);
+        let day = sources.day.as_ref().and_then(|s| s.compose(uv_rect, IMAGERY_CHUNK_TEXTURE_SIZE));
+        let night = sources.night.as_ref().and_then(|s| s.compose(uv_rect, IMAGERY_CHUNK_TEXTURE_SIZE));
+
+        (day.is_some() || night.is_some()).then_some(ChunkImagery { uv_rect, day, night })
+    })
+}
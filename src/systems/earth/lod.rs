@@ -0,0 +1,362 @@
+//! lod.rs
+//!
+//! Quadtree level-of-detail for the cube-sphere terrain
+//! each cube face starts as the usual 2x2 chunks, chunks split into four as the camera
+//! gets close and merge back as it leaves. subdivision reuses `generate_face`'s
+//! offset scheme: a child covers half the size, offset by half a parent size.
+//! meshes are built on the async compute pool, a parent stays on screen until
+//! all four children are ready so there are no holes while streaming in
+//! deeper chunks also stream tiled imagery (see imagery.rs) into a material of their own
+
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+
+use crate::config::{
+    EARTH_RADIUS, IMAGERY_MIN_DEPTH, TERRAIN_CHUNK_RESOLUTION, TERRAIN_MAX_DEPTH, TERRAIN_SKIRT_DEPTH,
+    TERRAIN_SPLIT_FACTOR,
+};
+use crate::systems::earth::heightmap::Heightmap;
+use crate::systems::earth::imagery::{spawn_imagery_task, ChunkImagery, ImagerySources};
+use crate::systems::earth::materials::EarthMaterial;
+use crate::systems::earth::mesh::{cube_point_to_sphere_point, generate_face};
+
+/// merge only once the camera is this much further than the split distance
+/// stops chunks flickering between levels at the boundary
+const MERGE_HYSTERESIS: f32 = 1.25;
+
+/// a square region of one cube face, same parameters as `generate_face`
+#[derive(Clone, Copy, Debug)]
+pub struct Chunk {
+    pub normal: Vec3,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub size: f32,
+}
+
+impl Chunk {
+    /// the four quarter-face chunks of every cube face
+    pub fn roots() -> Vec<Chunk> {
+        let faces = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+        let offsets = [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 1.0)];
+
+        faces
+            .iter()
+            .flat_map(|&normal| offsets.iter().map(move |&(x_offset, y_offset)| Chunk {
+                normal,
+                x_offset,
+                y_offset,
+                size: 1.0,
+            }))
+            .collect()
+    }
+
+    pub fn children(&self) -> [Chunk; 4] {
+        let half = self.size / 2.0;
+        let child = |i: f32, j: f32| Chunk {
+            normal: self.normal,
+            x_offset: self.x_offset - i * half,
+            y_offset: self.y_offset - j * half,
+            size: half,
+        };
+        [child(0.0, 0.0), child(1.0, 0.0), child(0.0, 1.0), child(1.0, 1.0)]
+    }
+
+    /// chunk centre on the unit sphere
+    pub fn center_direction(&self) -> Vec3 {
+        let axis_a = Vec3::new(self.normal.y, self.normal.z, self.normal.x);
+        let axis_b = axis_a.cross(self.normal);
+        let mid = 0.5 * self.size;
+        let cube_point = self.normal + (mid - self.x_offset) * axis_a + (mid - self.y_offset) * axis_b;
+        cube_point_to_sphere_point(cube_point).normalize()
+    }
+
+    /// rough edge length on the surface (km)
+    /// a whole face (size 2) spans a quarter of a great circle
+    pub fn extent(&self) -> f32 {
+        self.size * EARTH_RADIUS * std::f32::consts::FRAC_PI_4
+    }
+
+    /// whole chunk is hidden behind the planet's limb as seen from `camera`
+    /// conservative: uses the chunk's bounding cap, so edges are never wrongly culled
+    pub fn beyond_horizon(&self, camera: Vec3) -> bool {
+        let camera_distance = camera.length();
+        if camera_distance <= EARTH_RADIUS {
+            return false;
+        }
+
+        let horizon_angle = (EARTH_RADIUS / camera_distance).acos();
+        let chunk_radius_angle = self.extent() / EARTH_RADIUS; // half-diagonal, with margin
+        let angle = self.center_direction().angle_between(camera / camera_distance);
+
+        angle > horizon_angle + chunk_radius_angle
+    }
+}
+
+struct QuadNode {
+    chunk: Chunk,
+    depth: u32,
+    mesh: Option<Handle<Mesh>>,
+    task: Option<Task<Mesh>>,
+    imagery_task: Option<Task<Option<ChunkImagery>>>,
+    imagery_requested: bool,
+    material: Option<Handle<EarthMaterial>>, // own material once tiles have streamed in
+    entity: Option<Entity>, // spawned while this node is the one being drawn
+    culled: bool,           // entity hidden behind the horizon
+    children: Option<Box<[QuadNode; 4]>>,
+}
+
+impl QuadNode {
+    fn new(chunk: Chunk, depth: u32) -> Self {
+        Self {
+            chunk,
+            depth,
+            mesh: None,
+            task: None,
+            imagery_task: None,
+            imagery_requested: false,
+            material: None,
+            entity: None,
+            culled: false,
+            children: None,
+        }
+    }
+
+    /// this node's area can be drawn, either by itself or by its descendants
+    fn covered(&self) -> bool {
+        self.mesh.is_some() || self.children_cover()
+    }
+
+    fn children_cover(&self) -> bool {
+        self.children.as_ref().is_some_and(|children| children.iter().all(QuadNode::covered))
+    }
+
+    /// despawn every entity in this subtree, dropping pending tasks cancels them
+    fn clear(&mut self, commands: &mut Commands) {
+        if let Some(entity) = self.entity.take() {
+            commands.entity(entity).despawn();
+        }
+        if let Some(children) = self.children.as_mut() {
+            for child in children.iter_mut() {
+                child.clear(commands);
+            }
+        }
+    }
+}
+
+/// quadtree state, inserted once the displacement map and material exist
+#[derive(Resource)]
+pub struct TerrainLod {
+    roots: Vec<QuadNode>,
+    displacement: Arc<Heightmap>,
+    imagery: Option<Arc<ImagerySources>>, // None when there are no tile directories
+    material: Handle<EarthMaterial>,
+    earth_entity: Entity,
+}
+
+impl TerrainLod {
+    pub fn new(
+        displacement: Arc<Heightmap>,
+        imagery: Option<ImagerySources>,
+        material: Handle<EarthMaterial>,
+        earth_entity: Entity,
+    ) -> Self {
+        Self {
+            roots: Chunk::roots().into_iter().map(|chunk| QuadNode::new(chunk, 0)).collect(),
+            displacement,
+            imagery: imagery.map(Arc::new),
+            material,
+            earth_entity,
+        }
+    }
+
+    /// heightmap the terrain is displaced by, shared with anything draped over it
+    pub fn displacement(&self) -> Arc<Heightmap> {
+        self.displacement.clone()
+    }
+}
+
+/// per-frame inputs shared by the whole tree walk
+struct LodContext<'a> {
+    camera_local: Vec3, // camera position in the earth's rotating frame
+    displacement: &'a Arc<Heightmap>,
+    imagery: Option<&'a Arc<ImagerySources>>,
+    material: &'a Handle<EarthMaterial>,
+    earth_entity: Entity,
+}
+
+pub fn update(
+    mut commands: Commands,
+    mut lod: ResMut<TerrainLod>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<EarthMaterial>>,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    earth: Query<&GlobalTransform, With<super::Earth>>,
+) {
+    let (Ok(camera), Ok(earth)) = (camera.single(), earth.single()) else { return; };
+
+    let lod = lod.as_mut();
+    let ctx = LodContext {
+        camera_local: earth.affine().inverse().transform_point3(camera.translation()),
+        displacement: &lod.displacement,
+        imagery: lod.imagery.as_ref(),
+        material: &lod.material,
+        earth_entity: lod.earth_entity,
+    };
+    let mut assets = LodAssets {
+        meshes: &mut meshes,
+        images: &mut images,
+        materials: &mut materials,
+    };
+
+    for root in lod.roots.iter_mut() {
+        refine(root, &ctx, &mut commands, &mut assets);
+        display(root, &ctx, &mut commands);
+    }
+}
+
+/// assets written to while walking the tree
+struct LodAssets<'a> {
+    meshes: &'a mut Assets<Mesh>,
+    images: &'a mut Assets<Image>,
+    materials: &'a mut Assets<EarthMaterial>,
+}
+
+/// poll mesh tasks, and split or merge based on camera distance
+fn refine(node: &mut QuadNode, ctx: &LodContext, commands: &mut Commands, assets: &mut LodAssets) {
+    // collect a finished mesh
+    if let Some(task) = node.task.as_mut()
+        && let Some(mesh) = block_on(future::poll_once(task))
+    {
+        node.mesh = Some(assets.meshes.add(mesh));
+        node.task = None;
+    }
+
+    // start building if nothing is there yet
+    if node.mesh.is_none() && node.task.is_none() {
+        node.task = Some(spawn_mesh_task(node.chunk, ctx.displacement.clone()));
+    }
+
+    stream_imagery(node, ctx, commands, assets);
+
+    let center = node.chunk.center_direction() * EARTH_RADIUS;
+    let distance = ctx.camera_local.distance(center);
+    let split_distance = node.chunk.extent() * TERRAIN_SPLIT_FACTOR;
+
+    match node.children {
+        None if distance < split_distance && node.depth < TERRAIN_MAX_DEPTH && node.mesh.is_some() => {
+            let children = node.chunk.children().map(|chunk| QuadNode::new(chunk, node.depth + 1));
+            node.children = Some(Box::new(children));
+        }
+        Some(_) if distance > split_distance * MERGE_HYSTERESIS => {
+            if let Some(mut children) = node.children.take() {
+                for child in children.iter_mut() {
+                    child.clear(commands);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(children) = node.children.as_mut() {
+        for child in children.iter_mut() {
+            refine(child, ctx, commands, assets);
+        }
+    }
+}
+
+/// request tiles for deep enough chunks, and switch to a chunk material when they arrive
+fn stream_imagery(node: &mut QuadNode, ctx: &LodContext, commands: &mut Commands, assets: &mut LodAssets) {
+    if let Some(sources) = ctx.imagery
+        && node.depth >= IMAGERY_MIN_DEPTH
+        && !node.imagery_requested
+    {
+        node.imagery_task = Some(spawn_imagery_task(node.chunk, sources.clone()));
+        node.imagery_requested = true;
+    }
+
+    let Some(task) = node.imagery_task.as_mut() else { return; };
+    let Some(result) = block_on(future::poll_once(task)) else { return; };
+    node.imagery_task = None;
+
+    // no tiles here, keep the global material
+    let Some(imagery) = result else { return; };
+    let Some(mut material) = assets.materials.get(ctx.material).cloned() else { return; };
+
+    if let Some(day) = imagery.day {
+        material.detail_day = assets.images.add(day);
+    }
+    if let Some(night) = imagery.night {
+        material.detail_night = assets.images.add(night);
+    }
+    material.imagery.uv_rect = imagery.uv_rect;
+
+    let handle = assets.materials.add(material);
+    if let Some(entity) = node.entity {
+        commands.entity(entity).insert(MeshMaterial3d(handle.clone()));
+    }
+    node.material = Some(handle);
+}
+
+/// spawn entities for the nodes that should be drawn, despawn the rest
+/// a node hands over to its children only once all four are ready
+fn display(node: &mut QuadNode, ctx: &LodContext, commands: &mut Commands) {
+    if node.children_cover() {
+        if let Some(entity) = node.entity.take() {
+            commands.entity(entity).despawn();
+        }
+        if let Some(children) = node.children.as_mut() {
+            for child in children.iter_mut() {
+                display(child, ctx, commands);
+            }
+        }
+        return;
+    }
+
+    // children still streaming in, keep drawing this node
+    if let Some(children) = node.children.as_mut() {
+        for child in children.iter_mut() {
+            child.clear(commands);
+        }
+    }
+
+    if node.entity.is_none()
+        && let Some(mesh) = node.mesh.clone()
+    {
+        let entity = commands
+            .spawn((
+                Mesh3d(mesh),
+                MeshMaterial3d(node.material.clone().unwrap_or_else(|| ctx.material.clone())),
+                ChildOf(ctx.earth_entity),
+            ))
+            .id();
+        node.entity = Some(entity);
+        node.culled = false;
+    }
+
+    // far side of the planet, skip drawing
+    let culled = node.chunk.beyond_horizon(ctx.camera_local);
+    if let Some(entity) = node.entity
+        && culled != node.culled
+    {
+        let visibility = if culled { Visibility::Hidden } else { Visibility::Inherited };
+        commands.entity(entity).insert(visibility);
+        node.culled = culled;
+    }
+}
+
+fn spawn_mesh_task(chunk: Chunk, displacement: Arc<Heightmap>) -> Task<Mesh> {
+    AsyncComputeTaskPool::get().spawn(async move {
+        generate_face(
+            chunk.normal,
+            TERRAIN_CHUNK_RESOLUTION,
+            chunk.x_offset,
+            chunk.y_offset,
+            chunk.size,
+            TERRAIN_SKIRT_DEPTH,
+            Some(displacement.as_ref()),
+        )
+    })
+}
@@ -8,6 +8,8 @@ use bevy::render::render_resource::*;
 use bevy::reflect::TypePath;
 use bevy::asset::Asset;
 
+use crate::systems::earth::atmosphere::AtmosphereParams;
+
 /// sun direction data (needs to be in a struct)
 /// this is how to pass sun vector3 data to the earth shader (local only)
 /// https://www.w3.org/TR/WGSL/#address-space-layout-constraints
@@ -19,16 +21,91 @@ pub struct SunUniform {
 }
 
 /// atmosphere uniform data
+/// physical parameters mirror `AtmosphereParams`, the shader needs them for the view ray
 #[derive(ShaderType, Clone, Copy, Debug)]
 #[repr(C)]
 pub struct AtmosphereUniform {
     pub sun_direction: Vec3,
-    pub camera_position: Vec3,
-    pub rayleigh_coeff: Vec3,
-    pub mie_coeff: f32,
     pub sun_intensity: f32,
-    pub atmosphere_radius: f32,
-    pub _padding: f32,
+    pub camera_position: Vec3,
+    pub bottom_radius: f32,
+    pub rayleigh_scattering: Vec3,
+    pub top_radius: f32,
+    pub ozone_absorption: Vec3,
+    pub rayleigh_scale_height: f32,
+    pub mie_scattering: f32,
+    pub mie_extinction: f32,
+    pub mie_scale_height: f32,
+    pub mie_g: f32,
+    pub ozone_center: f32,
+    pub ozone_width: f32,
+    pub _padding: Vec2,
+}
+
+impl AtmosphereUniform {
+    pub fn new(params: &AtmosphereParams, sun_direction: Vec3, sun_intensity: f32) -> Self {
+        let mut uniform = Self {
+            sun_direction,
+            sun_intensity,
+            camera_position: Vec3::ZERO, // updated at runtime
+            bottom_radius: 0.0,
+            rayleigh_scattering: Vec3::ZERO,
+            top_radius: 0.0,
+            ozone_absorption: Vec3::ZERO,
+            rayleigh_scale_height: 1.0,
+            mie_scattering: 0.0,
+            mie_extinction: 0.0,
+            mie_scale_height: 1.0,
+            mie_g: 0.0,
+            ozone_center: 0.0,
+            ozone_width: 1.0,
+            _padding: Vec2::ZERO,
+        };
+        uniform.set_params(params);
+        uniform
+    }
+
+    /// copy the physical parameters, kept in step with the lookup tables
+    pub fn set_params(&mut self, params: &AtmosphereParams) {
+        self.bottom_radius = params.bottom_radius;
+        self.top_radius = params.top_radius;
+        self.rayleigh_scattering = params.rayleigh_scattering;
+        self.rayleigh_scale_height = params.rayleigh_scale_height;
+        self.mie_scattering = params.mie_scattering;
+        self.mie_extinction = params.mie_extinction;
+        self.mie_scale_height = params.mie_scale_height;
+        self.mie_g = params.mie_g;
+        self.ozone_absorption = params.ozone_absorption;
+        self.ozone_center = params.ozone_center;
+        self.ozone_width = params.ozone_width;
+    }
+}
+
+/// where a chunk's detail textures sit in the earth's uv space
+#[derive(ShaderType, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ImageryUniform {
+    pub uv_rect: Vec4, // u, v, width, height
+}
+
+impl Default for ImageryUniform {
+    fn default() -> Self {
+        Self { uv_rect: Vec4::new(0.0, 0.0, 1.0, 1.0) }
+    }
+}
+
+/// runtime layer switches for the earth shader, see settings.rs
+#[derive(ShaderType, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct LayerUniform {
+    pub night_lights: f32, // 0 hides city lights
+    pub _padding: Vec3,
+}
+
+impl Default for LayerUniform {
+    fn default() -> Self {
+        Self { night_lights: 1.0, _padding: Vec3::ZERO }
+    }
 }
 
 /// earth material
@@ -51,6 +128,17 @@ pub struct EarthMaterial {
     pub normal_map: Handle<Image>,
     #[uniform(10)]
     pub sun_uniform: SunUniform,
+    // streamed tile imagery for one chunk, transparent where there are no tiles (see imagery.rs)
+    #[texture(11)]
+    #[sampler(12)]
+    pub detail_day: Handle<Image>,
+    #[texture(13)]
+    #[sampler(14)]
+    pub detail_night: Handle<Image>,
+    #[uniform(15)]
+    pub imagery: ImageryUniform,
+    #[uniform(16)]
+    pub layers: LayerUniform,
 }
 
 impl Material for EarthMaterial {
@@ -64,10 +152,17 @@ impl Material for EarthMaterial {
 }
 
 /// atmosphere material
+/// lookup tables come from atmosphere.rs
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct AtmosphereMaterial {
     #[uniform(0)]
     pub atmosphere_uniform: AtmosphereUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    pub transmittance_lut: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub multi_scattering_lut: Handle<Image>,
 }
 
 impl Material for AtmosphereMaterial {
@@ -75,8 +170,9 @@ impl Material for AtmosphereMaterial {
         "shaders/atmosphere.wgsl".into()
     }
 
+    // scattered light is added, the background is dimmed by the view transmittance
     fn alpha_mode(&self) -> AlphaMode {
-        AlphaMode::Blend
+        AlphaMode::Premultiplied
     }
     
     fn specialize(
@@ -85,11 +181,21 @@ impl Material for AtmosphereMaterial {
             _layout: &bevy::render::mesh::MeshVertexBufferLayoutRef,
             _key: bevy::pbr::MaterialPipelineKey<Self>,
         ) -> Result<(), SpecializedMeshPipelineError> {
-        descriptor.primitive.cull_mode = None;
+        // back faces only, each view ray is marched once whether the camera is inside or out
+        descriptor.primitive.cull_mode = Some(Face::Front);
         Ok(())
     }
 }
 
+/// cloud layer settings, see clouds.rs
+#[derive(ShaderType, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CloudUniform {
+    pub opacity: f32, // runtime adjustments
+    pub blend: f32,   // cross-fade from cloud_texture (0) to next_cloud_texture (1)
+    pub _padding: Vec2,
+}
+
 /// cloud material
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct CloudMaterial {
@@ -99,7 +205,10 @@ pub struct CloudMaterial {
     #[uniform(2)]
     pub sun_uniform: SunUniform,
     #[uniform(3)]
-    pub cloud_opacity: f32, // runtime adjustments
+    pub cloud_uniform: CloudUniform,
+    #[texture(4)]
+    #[sampler(5)]
+    pub next_cloud_texture: Handle<Image>,
 }
 
 impl Material for CloudMaterial {
@@ -6,18 +6,26 @@ use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
 
+use crate::systems::earth::heightmap::{HeightFilter, Heightmap};
 use crate::systems::earth::uv::LatLon;
-use crate::config::{DISPLACEMENT_SCALE, EARTH_RADIUS};
+use crate::config::{DISPLACEMENT_SCALE, EARTH_RADIUS, HEIGHTMAP_BICUBIC};
 
 
 /// Generates a spherical mesh face by projecting a flat grid onto a sphere
 /// Based on Sebastian Lague and Grayson Head's implementation
+///
+/// the grid covers `size` units of the cube face starting at (-x_offset, -y_offset),
+/// so size 1.0 with offsets 0/1 gives the four quarter-face chunks, and halving
+/// size/offsets subdivides further (see `lod.rs`)
+/// a non-zero `skirt_depth` hangs a skirt below the chunk edges to hide LOD cracks
 pub fn generate_face(
     normal: Vec3,
     resolution: u32,
     x_offset: f32,
     y_offset: f32,
-    displacement: Option<&Image>, // optional displacement map
+    size: f32,
+    skirt_depth: f32,
+    displacement: Option<&Heightmap>, // optional displacement map
 ) -> Mesh {
     // this creates two perpendicular axes on the cube face
     let axis_a = Vec3::new(normal.y, normal.z, normal.x);
@@ -29,7 +37,12 @@ pub fn generate_face(
     let mut normals: Vec<Vec3> = Vec::new();
     let mut uvs: Vec<Vec2> = Vec::new();
 
-    let mut first_longitude = 0.0;
+    // u is unwrapped around the chunk centre's longitude, so a chunk crossing the
+    // antimeridian gets continuous u (e.g. 0.98..1.02) instead of triangles spanning the texture
+    // each chunk owns its vertices, so this effectively duplicates the seam per chunk
+    let mid = 0.5 * size;
+    let center = cube_point_to_sphere_point(normal + (mid - x_offset) * axis_a + (mid - y_offset) * axis_b);
+    let (reference_u, _) = LatLon::from(center.normalize()).to_uv();
 
     // create a grid of vertices
     for y in 0..resolution {
@@ -38,40 +51,18 @@ pub fn generate_face(
             let i = x + y * resolution;
 
             let percent = Vec2::new(x as f32, y as f32) / (resolution - 1) as f32;
-            let point_on_unit_cube = 
-                normal + (percent.x - x_offset) * axis_a + (percent.y - y_offset) * axis_b;
+            let point_on_unit_cube =
+                normal + (percent.x * size - x_offset) * axis_a + (percent.y * size - y_offset) * axis_b;
             let point_on_unit_sphere = cube_point_to_sphere_point(point_on_unit_cube);
 
             // uv
+            // longitude is undefined at the poles, the chunk's reference keeps u in range there
             let point_coords = LatLon::from(point_on_unit_sphere.normalize());
-            let (lat, lon) = point_coords.as_degrees();
-
-            // scale to size
-            // let final_point = point_on_unit_sphere.normalize() * EARTH_RADIUS; // 'normalize' makes it spherical
-            // vertices.push(final_point);
-            // normals.push(point_on_unit_sphere.normalize());
-
-            let (mut u, v) = point_coords.to_uv();
-
-            // handle UV seam cases to prevent texture distortion
-            if y == 0 && x == 0 {
-                first_longitude = lon;
-            }
-
-            // implement wrapping, as polygons may have to cross texture edge
-            // if starting on negative -long crossing into +long, set u to 0.0
-            if first_longitude < 0.0 && lon > 0.0 && lat < 89.0 && lat > -89.0 {
-                u = 0.0;
-            }
-
-            // if below -40 degrees lat and tile starts at 180, set u to 0.0
-            if x == 0 && lon == 180.0 && lat < -40.0 {
-                u = 0.0;
-            }
+            let (u, v) = point_coords.to_uv_near(reference_u);
 
             // sample displacement
-            let displacement = if let Some(disp_map) = displacement {
-                sample_displacement(disp_map, u, v) * DISPLACEMENT_SCALE
+            let displacement = if let Some(heightmap) = displacement {
+                heightmap.sample(u, v, displacement_filter()) * DISPLACEMENT_SCALE
             } else {
                 0.0
             };
@@ -99,6 +90,10 @@ pub fn generate_face(
         }
     }
 
+    if skirt_depth > 0.0 {
+        add_skirts(&mut vertices, &mut normals, &mut uvs, &mut indices, resolution, skirt_depth);
+    }
+
     // after generating vertices, recalculate normals
     // this is to make sure the normals account for the displacement
     // recalculate_normals(&mut normals, &vertices, &indices);
@@ -120,10 +115,62 @@ pub fn generate_face(
     mesh
 }
 
+/// Hang a strip of triangles below each edge of the grid
+/// neighbouring chunks at different LOD levels don't share edge vertices,
+/// the skirt fills the gap so no background shows through
+fn add_skirts(
+    vertices: &mut Vec<Vec3>,
+    normals: &mut Vec<Vec3>,
+    uvs: &mut Vec<Vec2>,
+    indices: &mut Vec<u32>,
+    resolution: u32,
+    depth: f32,
+) {
+    let last = resolution - 1;
+    let edges: [Vec<u32>; 4] = [
+        (0..resolution).collect(),                                  // top row
+        (0..resolution).map(|y| last + y * resolution).collect(),   // right column
+        (0..resolution).map(|x| x + last * resolution).collect(),   // bottom row
+        (0..resolution).map(|y| y * resolution).collect(),          // left column
+    ];
+
+    // skirts face away from the middle of the chunk, towards the neighbour whose crack they cover
+    // from inside the chunk they're under its own surface anyway, so one side is enough
+    let grid = (resolution * resolution) as usize;
+    let middle = vertices[..grid].iter().sum::<Vec3>() / grid as f32;
+
+    for edge in edges {
+        let base = vertices.len() as u32;
+
+        // duplicate edge vertices, pushed down towards the centre
+        for &i in &edge {
+            let v = vertices[i as usize];
+            vertices.push(v.normalize() * (v.length() - depth));
+            normals.push(normals[i as usize]);
+            uvs.push(uvs[i as usize]);
+        }
+
+        for k in 0..edge.len() as u32 - 1 {
+            let (a, b) = (edge[k as usize], edge[k as usize + 1]);
+            let (a_skirt, b_skirt) = (base + k, base + k + 1);
+
+            // edges run in different directions around the grid, pick the winding facing out
+            let (pa, pb, pb_skirt) = (vertices[a as usize], vertices[b as usize], vertices[b_skirt as usize]);
+            let midpoint = (pa + pb) / 2.0;
+            let outward = (midpoint - middle).reject_from(midpoint);
+            if (pb - pa).cross(pb_skirt - pa).dot(outward) >= 0.0 {
+                indices.extend_from_slice(&[a, b, b_skirt, a, b_skirt, a_skirt]);
+            } else {
+                indices.extend_from_slice(&[a, b_skirt, b, a, a_skirt, b_skirt]);
+            }
+        }
+    }
+}
+
 /// Converts a point on a unit cube to the corresponding point on a unit sphere
 /// creates more even distribution of sphere surface
 /// https://mathproofs.blogspot.com/2005/07/mapping-cube-to-sphere.html
-fn cube_point_to_sphere_point(p: Vec3) -> Vec3 {
+pub fn cube_point_to_sphere_point(p: Vec3) -> Vec3 {
     let x2 = p.x * p.x;
     let y2 = p.y * p.y;
     let z2 = p.z * p.z;
@@ -141,7 +188,7 @@ fn cube_point_to_sphere_point(p: Vec3) -> Vec3 {
 
 /// Recalculate normals based on actual mesh geometry
 #[allow(dead_code)]
-fn recalculate_normals(normals: &mut Vec<Vec3>, vertices: &[Vec3], indices: &[u32]) {
+fn recalculate_normals(normals: &mut [Vec3], vertices: &[Vec3], indices: &[u32]) {
     // reset normals
     normals.fill(Vec3::ZERO);
 
@@ -163,7 +210,7 @@ fn recalculate_normals(normals: &mut Vec<Vec3>, vertices: &[Vec3], indices: &[u3
             // check for degenrate triangle
             let face_normal_length = face_normal.length();
             if face_normal_length > 1e-6 {
-                face_normal = face_normal / face_normal_length;
+                face_normal /= face_normal_length;
 
                 // add face normal to each vertex normal
                 normals[i0] += face_normal;
@@ -177,7 +224,7 @@ fn recalculate_normals(normals: &mut Vec<Vec3>, vertices: &[Vec3], indices: &[u3
     for normal in normals.iter_mut() {
         let length = normal.length();
         if length > 1e-6 {
-            *normal = *normal / length;
+            *normal /= length;
         } else {
             // fallback for isolated vertices
             *normal = Vec3::Y;
@@ -185,29 +232,105 @@ fn recalculate_normals(normals: &mut Vec<Vec3>, vertices: &[Vec3], indices: &[u3
     }
 }
 
-/// Sample displacement value from image at UV coordinates
-fn sample_displacement(image: &Image, u: f32, v: f32) -> f32 {
-    // clamp UV coordinates
-    let u = u.clamp(0.0, 1.0);
-    let v = v.clamp(0.0, 1.0);
+/// filter used for displacement, see config
+fn displacement_filter() -> HeightFilter {
+    if HEIGHTMAP_BICUBIC { HeightFilter::Bicubic } else { HeightFilter::Bilinear }
+}
 
-    let width = image.texture_descriptor.size.width as usize;
-    let height = image.texture_descriptor.size.height as usize;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+    use crate::config::TERRAIN_CHUNK_RESOLUTION;
+
+    /// largest u range a single triangle may cover, scaled by cos(latitude)
+    /// meridians converge towards the poles so raw spans grow there;
+    /// a triangle wrapping across the seam would span close to 1.0
+    const MAX_TRIANGLE_U_SPAN: f32 = 0.01;
+    /// the pole vertices themselves have no longitude
+    const POLAR_CAP_DEGREES: f32 = 89.0;
+
+    fn max_u_span(mesh: &Mesh) -> f32 {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            panic!("mesh has no positions");
+        };
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) else {
+            panic!("mesh has no uvs");
+        };
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("mesh has no indices");
+        };
+
+        let mut max_span: f32 = 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let max_lat = triangle
+                .iter()
+                .map(|&i| LatLon::from(Vec3::from(positions[i as usize])).as_degrees().0.abs())
+                .fold(0.0, f32::max);
+            if max_lat > POLAR_CAP_DEGREES {
+                continue;
+            }
 
+            let us = triangle.iter().map(|&i| uvs[i as usize][0]);
+            let span = us.clone().fold(f32::MIN, f32::max) - us.fold(f32::MAX, f32::min);
+            max_span = max_span.max(span * max_lat.to_radians().cos());
+        }
+        max_span
+    }
 
-    // UV to pixel coordinates
-    let x = (u * (width - 1) as f32).round() as usize;
-    let y = (v * (height - 1) as f32).round() as usize;
+    #[test]
+    fn no_triangle_spans_the_uv_seam() {
+        let faces = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+        let offsets = [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 1.0)];
+
+        for normal in faces {
+            for (x_offset, y_offset) in offsets {
+                // root chunks, with skirts since they copy edge uvs
+                let mesh = generate_face(normal, TERRAIN_CHUNK_RESOLUTION, x_offset, y_offset, 1.0, 20.0, None);
+                let span = max_u_span(&mesh);
+                assert!(
+                    span < MAX_TRIANGLE_U_SPAN,
+                    "face {normal} offset ({x_offset}, {y_offset}): triangle spans {span} in u",
+                );
+            }
+        }
+    }
 
-    // get pixel data slice
-    if let Some(data) = image.data.as_ref() {
-        let pixel_index = (y * width + x) * 4; // 4 bytes per pixel (RGBA)
+    #[test]
+    fn skirts_face_out_of_the_chunk() {
+        let resolution = 8;
+        let grid = (resolution * resolution) as usize;
+        let surface_triangles = ((resolution - 1) * (resolution - 1) * 2) as usize;
 
-        if pixel_index + 3 < data.len() {
-            // just used red channel
-            return data[pixel_index] as f32 / 255.0;
+        for normal in [Vec3::X, Vec3::NEG_Y, Vec3::Z] {
+            let mesh = generate_face(normal, resolution, 0.5, 0.0, 0.5, 20.0, None);
+            let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+                panic!("mesh has no positions");
+            };
+            let Some(Indices::U32(indices)) = mesh.indices() else {
+                panic!("mesh has no indices");
+            };
+            let positions: Vec<Vec3> = positions.iter().map(|&p| Vec3::from(p)).collect();
+            let middle = positions[..grid].iter().sum::<Vec3>() / grid as f32;
+
+            // one quad (two triangles) per edge segment, four edges
+            let skirts = &indices[surface_triangles * 3..];
+            assert_eq!(skirts.len() / 3, 4 * (resolution as usize - 1) * 2, "face {normal}");
+
+            for triangle in skirts.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|k| positions[triangle[k] as usize]);
+                let centroid = (a + b + c) / 3.0;
+                let outward = (centroid - middle).reject_from(centroid);
+                assert!((b - a).cross(c - a).dot(outward) > 0.0, "face {normal}: skirt triangle faces inward");
+            }
         }
     }
 
-    0.0 // default if not available, or out of bounds
-}
\ No newline at end of file
+    #[test]
+    fn subdivided_chunk_on_antimeridian_is_continuous() {
+        // -Z face centre sits on the antimeridian, this chunk straddles it
+        let mesh = generate_face(Vec3::NEG_Z, TERRAIN_CHUNK_RESOLUTION, 0.25, 0.25, 0.5, 0.0, None);
+        let span = max_u_span(&mesh);
+        assert!(span < MAX_TRIANGLE_U_SPAN, "triangle spans {span} in u");
+    }
+}
@@ -4,24 +4,48 @@
 //! Handles mesh generation and dynamic updates of earth-related entities:
 //! - The planet's surface mesh from displacement map
 //! - Precomputed normal maps
-//! - Atmospheric scattering effects
+//! - Atmospheric scattering effects (precomputed lookup tables)
 //! - Cloud layer
+//! - Vector overlays (coastlines, borders, cities, graticule)
+//! - Ground station markers
 //! - Real-time lighting updates (for future seasons implementation)
 
 use bevy::prelude::*;
 
+#[allow(dead_code)] // ShaderType derive emits unused layout check fns
 pub mod materials;
 pub mod mesh;
 pub mod uv;
 pub mod normal;
-
-use mesh::generate_face;
-use materials::{EarthMaterial, AtmosphereMaterial, CloudMaterial, SunUniform, AtmosphereUniform};
-use normal::{generate_normal_map, save_image_as_png};
+pub mod lod;
+pub mod heightmap;
+pub mod imagery;
+pub mod clouds;
+pub mod atmosphere;
+pub mod overlays;
+pub mod cursor;
+pub mod stations;
+
+use std::sync::Arc;
+
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+
+use atmosphere::{AtmosphereLutState, AtmosphereParams};
+use heightmap::Heightmap;
+use lod::TerrainLod;
+use imagery::ImagerySources;
+use materials::{EarthMaterial, AtmosphereMaterial, CloudMaterial, CloudUniform, SunUniform, AtmosphereUniform, ImageryUniform, LayerUniform};
+use normal::cached_normal_map;
 use crate::{config::{
-    ATMOSPHERE_RADIUS, CLOUD_RADIUS, EARTH_CLOUDS_TEXTURE, EARTH_DIFFUSE_TEXTURE, EARTH_DISPLACEMENT_TEXTURE, EARTH_NIGHT_TEXTURE, EARTH_OCEAN_MASK_TEXTURE, EARTH_ROTATION_SPEED, EARTH_SPECULAR_TEXTURE, MIE_COEFF, RAYLEIGH_COEFF, SUN_INTENSITY, USE_SAVED_NORMAL_MAP, SAVED_NORMAL_MAP_PATH
+    ATMOSPHERE_RADIUS, CLOUD_OPACITY, CLOUD_RADIUS, EARTH_CLOUDS_TEXTURE, EARTH_DIFFUSE_TEXTURE, EARTH_DISPLACEMENT_TEXTURE, EARTH_NIGHT_TEXTURE, EARTH_OCEAN_MASK_TEXTURE, EARTH_SPECULAR_TEXTURE, SUN_INTENSITY
 }, Sun};
+use crate::systems::settings::RenderSettings;
+use crate::systems::map::subsolar_point;
+use crate::systems::satellites::orbital::earth_rotation;
 use crate::systems::time::TimeState;
+use uv::LatLon;
 
 pub struct EarthPlugin;
 
@@ -30,11 +54,26 @@ impl Plugin for EarthPlugin {
         app.add_plugins(MaterialPlugin::<EarthMaterial>::default())
             .add_plugins(MaterialPlugin::<AtmosphereMaterial>::default())
             .add_plugins(MaterialPlugin::<CloudMaterial>::default())
+            .init_resource::<clouds::CloudLayer>()
+            .init_resource::<AtmosphereLutState>()
+            .init_resource::<overlays::OverlayState>()
+            .init_resource::<cursor::CursorLocation>()
+            .init_resource::<stations::GroundStations>()
             .add_systems(Startup, setup)
             .add_systems(Update, (
                 generate_earth_faces.run_if(resource_exists::<EarthData>),
-                update_shaders, 
-                rotate
+                lod::update.run_if(resource_exists::<TerrainLod>),
+                update_shaders.after(rotate),
+                apply_layer_visibility,
+                atmosphere::update_luts,
+                overlays::build.run_if(resource_exists::<TerrainLod>),
+                overlays::toggle_graticule,
+                overlays::apply_visibility.after(overlays::toggle_graticule),
+                cursor::update,
+                stations::draw,
+                rotate.after(crate::systems::time::update),
+                clouds::adjust_opacity,
+                clouds::update.after(clouds::adjust_opacity),
             ));
     }
 }
@@ -55,7 +94,9 @@ pub struct Clouds;
 #[derive(Resource)]
 struct EarthData {
     displacement_handle: Handle<Image>,
-    normal_map_handle: Option<Handle<Image>>, // generated normal map
+    preparing: Option<Task<(Arc<Heightmap>, Image)>>, // heightmap decode + normal map, off the main thread
+    heightmap: Option<Arc<Heightmap>>,
+    normal_map_handle: Option<Handle<Image>>, // generated or cached normal map
     earth_entity: Entity,
     earth_material: Option<Handle<EarthMaterial>>, // created after normal map generation
 }
@@ -65,6 +106,7 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut atmosphere_materials: ResMut<Assets<AtmosphereMaterial>>,
     mut cloud_materials: ResMut<Assets<CloudMaterial>>,
+    mut images: ResMut<Assets<Image>>,
     asset_server: Res<AssetServer>,
 ) {
     // sun direction
@@ -84,21 +126,26 @@ fn setup(
         .id();
 
     // create atmosphere
-    let mut atmosphere_sphere = Sphere::new(ATMOSPHERE_RADIUS * 1.2).mesh().uv(16, 16);
+    // the shader finds the exact shell itself, the mesh only has to cover it
+    let mut atmosphere_sphere = Sphere::new(ATMOSPHERE_RADIUS * 1.02).mesh().uv(32, 32);
     atmosphere_sphere.generate_tangents().unwrap();
 
+    // lookup tables are built in the background, see atmosphere.rs
+    // until then: no sunlight lost, no multiple scattering
+    let mut placeholder_lut = |value: f32| images.add(Image::new_fill(
+        Extent3d::default(),
+        TextureDimension::D2,
+        &[value; 4].map(|c| half::f16::from_f32(c).to_le_bytes()).concat(),
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::RENDER_WORLD,
+    ));
+
     commands.spawn((
         Mesh3d(meshes.add(atmosphere_sphere)),
         MeshMaterial3d(atmosphere_materials.add(AtmosphereMaterial {
-            atmosphere_uniform: AtmosphereUniform {
-                sun_direction, 
-                camera_position: Vec3::ZERO, // will be updated on runtime
-                rayleigh_coeff: Vec3::from(RAYLEIGH_COEFF),
-                mie_coeff: MIE_COEFF,
-                sun_intensity: SUN_INTENSITY,
-                atmosphere_radius: ATMOSPHERE_RADIUS,
-                _padding: 0.0,
-            },
+            atmosphere_uniform: AtmosphereUniform::new(&AtmosphereParams::default(), sun_direction, SUN_INTENSITY),
+            transmittance_lut: placeholder_lut(1.0),
+            multi_scattering_lut: placeholder_lut(0.0),
         })),
         Transform::from_xyz(0.0, 0.0, 0.0)
             .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
@@ -113,12 +160,17 @@ fn setup(
     commands.spawn((
         Mesh3d(meshes.add(cloud_sphere)),
         MeshMaterial3d(cloud_materials.add(CloudMaterial {
-            cloud_texture,
+            cloud_texture: cloud_texture.clone(),
             sun_uniform: SunUniform {
                 direction: sun_direction,
                 _padding: 0.0,
             },
-            cloud_opacity: 0.7,
+            cloud_uniform: CloudUniform {
+                opacity: CLOUD_OPACITY,
+                blend: 0.0,
+                _padding: Vec2::ZERO,
+            },
+            next_cloud_texture: cloud_texture, // dated frames swap in here, see clouds.rs
         })),
         Transform::from_xyz(0.0, 0.0, 0.0)
             .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
@@ -129,6 +181,8 @@ fn setup(
     // store data for mesh and material generation once displacement loads
     commands.insert_resource(EarthData {
         displacement_handle,
+        preparing: None,
+        heightmap: None,
         normal_map_handle: None,
         earth_entity,
         earth_material: None,
@@ -139,52 +193,39 @@ fn setup(
 /// only runs after displacement map is loaded
 fn generate_earth_faces(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
     mut earth_materials: ResMut<Assets<EarthMaterial>>,
     mut earth_data: ResMut<EarthData>,
     asset_server: Res<AssetServer>,
 ) {
-    info!("Generating earth mesh...");
-
-    // check if displacement map is loaded
-    let displacement_image = {
-        let maybe_image = images.get(&earth_data.displacement_handle);
-        if maybe_image.is_none() {
+    // decode the heightmap and build the normal map in the background
+    if earth_data.heightmap.is_none() && earth_data.preparing.is_none() {
+        let Some(displacement_image) = images.get(&earth_data.displacement_handle) else {
             return; // wait for image to load
-        }
-        maybe_image.unwrap().clone()
-    };
+        };
+
+        info!("Generating earth mesh...");
+        let displacement_image = displacement_image.clone();
+        earth_data.preparing = Some(AsyncComputeTaskPool::get().spawn(async move {
+            // decode once, shared by the normal map and every terrain chunk
+            let heightmap = Arc::new(Heightmap::from_image(&displacement_image).unwrap_or_else(|| {
+                error!("Could not decode displacement map, terrain will be flat");
+                Heightmap::flat()
+            }));
+            let normal_map = cached_normal_map(heightmap.clone()).await;
+            (heightmap, normal_map)
+        }));
+    }
 
     // handle normal map
-    if earth_data.normal_map_handle.is_none() {
-        let normal_map_handle = if USE_SAVED_NORMAL_MAP {
-            // try to load the saved normal map first
-            match load_saved_normal_map(&asset_server) {
-                Some(handle) => {
-                    info!("Using normal maps...");
-                    handle
-                }
-                None => {
-                    // fallback
-                    info!("No normal maps found, creating new ones...");
-                    let normal_map_image = generate_normal_map(&displacement_image);
-                    save_image_as_png(&normal_map_image, &format!("assets/{}", SAVED_NORMAL_MAP_PATH));
-                    info!("Normal maps generated, saved to: assets/{}", SAVED_NORMAL_MAP_PATH);
-                    images.add(normal_map_image)
-                }
-            }
-        } else {
-            // always generate fresh
-            info!("Creating normal maps...");
-            let normal_map_image = generate_normal_map(&displacement_image);
-            
-            save_image_as_png(&normal_map_image, &format!("assets/{}", SAVED_NORMAL_MAP_PATH));
-            info!("Normal map generated");
-            images.add(normal_map_image)
+    if let Some(task) = earth_data.preparing.as_mut() {
+        let Some((heightmap, normal_map)) = block_on(future::poll_once(task)) else {
+            return; // still working
         };
-        
-        earth_data.normal_map_handle = Some(normal_map_handle);
+
+        earth_data.normal_map_handle = Some(images.add(normal_map));
+        earth_data.heightmap = Some(heightmap);
+        earth_data.preparing = None;
     }
 
     // create earth material
@@ -197,6 +238,15 @@ fn generate_earth_faces(
         let ocean_mask_texture = asset_server.load(EARTH_OCEAN_MASK_TEXTURE);
         let specular_texture = asset_server.load(EARTH_SPECULAR_TEXTURE);
 
+        // chunks without streamed tiles use a transparent detail texture, see imagery.rs
+        let no_detail = images.add(Image::new_fill(
+            Extent3d::default(),
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        ));
+
         let earth_material = earth_materials.add(EarthMaterial {
             day_texture: diffuse_texture,
             night_texture,
@@ -207,70 +257,45 @@ fn generate_earth_faces(
                 direction: sun_direction,
                 _padding: 0.0,
             },
+            detail_day: no_detail.clone(),
+            detail_night: no_detail,
+            imagery: ImageryUniform::default(),
+            layers: LayerUniform::default(),
         });
 
         earth_data.earth_material = Some(earth_material);
     }
 
-    // generate earth mesh faces
-    if let Some(ref earth_material) = earth_data.earth_material {
-        let faces = vec![
-            Vec3::X,        // right
-            Vec3::NEG_X,    // left
-            Vec3::Y,        // top
-            Vec3::NEG_Y,    // bottom
-            Vec3::Z,        // front
-            Vec3::NEG_Z,    // back
-        ];
-
-        let offsets = vec![(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 1.0)];
-
-        for direction in faces {
-            for offset in &offsets {
-                commands.spawn((
-                    Mesh3d(meshes.add(
-                        // you can modify displacement resolution here
-                        generate_face(
-                            direction, 
-                            84,
-                            offset.0, 
-                            offset.1,
-                            Some(&displacement_image),
-                        ),
-                    )),
-                    MeshMaterial3d(earth_material.clone()),
-                ))
-                .insert(ChildOf(earth_data.earth_entity));
-            }
-        }
+    // hand the surface over to the LOD quadtree
+    // chunk meshes are built in the background, see lod.rs
+    if let Some(ref earth_material) = earth_data.earth_material
+        && let Some(heightmap) = earth_data.heightmap.clone()
+    {
+        commands.insert_resource(TerrainLod::new(
+            heightmap,
+            ImagerySources::from_config(),
+            earth_material.clone(),
+            earth_data.earth_entity,
+        ));
 
         // cleanup
         commands.remove_resource::<EarthData>();
 
-        info!("Earth mesh generation complete...");
-    }
-}
-
-/// helper function to try loading a saved normal map
-fn load_saved_normal_map(asset_server: &AssetServer) -> Option<Handle<Image>> {
-    // check if the file exists before trying to load it
-    if std::path::Path::new(&format!("assets/{}", SAVED_NORMAL_MAP_PATH)).exists() {
-        Some(asset_server.load(SAVED_NORMAL_MAP_PATH))
-    } else {
-        None
+        info!("Earth terrain LOD initialised...");
     }
 }
 
 /// update shaders
+#[allow(clippy::too_many_arguments)]
 fn update_shaders(
     sun_query: Query<&Transform, With<Sun>>,
     camera_query: Query<&Transform, (With<Camera3d>, Without<Sun>)>,
-    earth_query: Query<&MeshMaterial3d<EarthMaterial>, With<Earth>>,
     atmosphere_query: Query<&MeshMaterial3d<AtmosphereMaterial>, With<Atmosphere>>,
-    cloud_query: Query<&MeshMaterial3d<CloudMaterial>, With<Earth>>,
+    cloud_query: Query<&MeshMaterial3d<CloudMaterial>, With<Clouds>>,
     mut earth_materials: ResMut<Assets<EarthMaterial>>,
     mut atmosphere_materials: ResMut<Assets<AtmosphereMaterial>>,
     mut cloud_materials: ResMut<Assets<CloudMaterial>>,
+    settings: Res<RenderSettings>,
 ) {
     let sun_direction = if let Ok(sun_transform) = sun_query.single() {
         -sun_transform.forward()
@@ -285,42 +310,73 @@ fn update_shaders(
     };
     
     // update earth material uniforms
-    if let Ok(earth_material_handle) = earth_query.single() {
-        if let Some(earth_material) = earth_materials.get_mut(&earth_material_handle.0) {
+    // terrain chunks with streamed imagery have their own materials, update them all
+    // only touch the ones that differ so unchanged materials aren't re-uploaded
+    let night_lights = if settings.night_lights { 1.0 } else { 0.0 };
+    let outdated: Vec<_> = earth_materials
+        .iter()
+        .filter(|(_, material)| {
+            material.sun_uniform.direction != Vec3::from(sun_direction) || material.layers.night_lights != night_lights
+        })
+        .map(|(id, _)| id)
+        .collect();
+    for id in outdated {
+        if let Some(earth_material) = earth_materials.get_mut(id) {
             earth_material.sun_uniform.direction = sun_direction.into();
+            earth_material.layers.night_lights = night_lights;
         }
     }
     
     // update atmosphere material uniforms
-    if let Ok(atmosphere_material_handle) = atmosphere_query.single() {
-        if let Some(atmosphere_material) = atmosphere_materials.get_mut(&atmosphere_material_handle.0) {
-            atmosphere_material.atmosphere_uniform.sun_direction = sun_direction.into();
-            atmosphere_material.atmosphere_uniform.camera_position = camera_position;
-        }
+    if let Ok(atmosphere_material_handle) = atmosphere_query.single()
+        && let Some(atmosphere_material) = atmosphere_materials.get_mut(&atmosphere_material_handle.0) {
+        atmosphere_material.atmosphere_uniform.sun_direction = sun_direction.into();
+        atmosphere_material.atmosphere_uniform.camera_position = camera_position;
+        atmosphere_material.atmosphere_uniform.sun_intensity = settings.sun_intensity;
     }
 
     // update cloud material uniforms
-    if let Ok(cloud_material_handle) = cloud_query.single() {
-        if let Some(cloud_material) = cloud_materials.get_mut(&cloud_material_handle.0) {
-            cloud_material.sun_uniform.direction = sun_direction.into();
-        }
+    if let Ok(cloud_material_handle) = cloud_query.single()
+        && let Some(cloud_material) = cloud_materials.get_mut(&cloud_material_handle.0) {
+        cloud_material.sun_uniform.direction = sun_direction.into();
     }
 }
 
-/// rotate earth
+/// show or hide the atmosphere and cloud shells
+fn apply_layer_visibility(
+    settings: Res<RenderSettings>,
+    mut atmosphere_query: Query<&mut Visibility, (With<Atmosphere>, Without<Clouds>)>,
+    mut cloud_query: Query<&mut Visibility, (With<Clouds>, Without<Atmosphere>)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    let visibility = |shown: bool| if shown { Visibility::Inherited } else { Visibility::Hidden };
+    for mut atmosphere_visibility in &mut atmosphere_query {
+        atmosphere_visibility.set_if_neq(visibility(settings.atmosphere));
+    }
+    for mut cloud_visibility in &mut cloud_query {
+        cloud_visibility.set_if_neq(visibility(settings.clouds));
+    }
+}
+
+/// turn the earth to the sim time, and put the sun over the subsolar point
+/// the map works from the same time, see map.rs
 fn rotate(
     time_state: Res<TimeState>,
     mut earth_query: Query<&mut Transform, With<Earth>>,
+    mut sun_query: Query<&mut Transform, (With<Sun>, Without<Earth>)>,
 ) {
-    if !time_state.is_paused {
-        let rotation_speed = EARTH_ROTATION_SPEED * time_state.speed_mult as f32;
-
-        // keep rotation smooth
-        let delta_rotation = Quat::from_rotation_y(rotation_speed * (1.0 / 60.0)); // 60fps equiv
+    let rotation = earth_rotation(time_state.sim_time);
+    if let Ok(mut transform) = earth_query.single_mut() {
+        transform.rotation = rotation;
+    }
 
-        // rotate earth
-        if let Ok(mut transform) = earth_query.single_mut() {
-            transform.rotation = transform.rotation * delta_rotation;
-        }
+    let (latitude, longitude) = subsolar_point(time_state.sim_time);
+    let sun_direction = rotation * LatLon::from_degrees(latitude, longitude).to_direction();
+    if let Ok(mut transform) = sun_query.single_mut() {
+        *transform = Transform::from_translation(sun_direction * transform.translation.length())
+            .looking_at(Vec3::ZERO, Vec3::Y);
     }
 }
\ No newline at end of file
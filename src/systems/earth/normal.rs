@@ -1,77 +1,56 @@
 //! normal.rs
-//! 
+//!
 //! Normal map generator for height textures on a sphere
+//! normals are stored in tangent space (x east, y north, z up) as linear data,
+//! so they stay valid however the earth mesh is rotated
 
 use bevy::prelude::*;
 use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::AsyncComputeTaskPool;
 use image::{ImageBuffer, Rgba};
 
-use crate::config::EARTH_RADIUS;
+use std::f32::consts::PI;
+use std::hash::Hasher;
+use std::ops::Range;
+use std::sync::Arc;
 
-/// Generates a normal map from a height map
-/// each pixel's normal is calculated by sampling neighboring heights
-/// and computing tanget vectors in world space
-pub fn generate_normal_map(height_map: &Image) -> Image {
-    let width = height_map.texture_descriptor.size.width as usize;
-    let height = height_map.texture_descriptor.size.height as usize;
-
-    // create output buffer
-    let mut normal_data = vec![0u8; width * height * 4]; // RGBA
-
-    // parse height data
-    let height_data = match height_map.data.as_ref() {
-        Some(data) => data.as_slice(),
-        None => {
-            // if no data, return blank
-            return Image::new(
-                Extent3d {
-                    width: width as u32,
-                    height: height as u32,
-                    depth_or_array_layers: 1,
-                },
-                TextureDimension::D2,
-                normal_data,
-                bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
-                RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
-            );
-        }
-    };
+use crate::config::{
+    DISPLACEMENT_SCALE, EARTH_RADIUS, NORMAL_MAP_16BIT, SAVED_NORMAL_MAP_KEY_PATH, SAVED_NORMAL_MAP_PATH,
+    USE_SAVED_NORMAL_MAP,
+};
+use crate::systems::earth::heightmap::{Fnv1a, Heightmap};
 
-    for y in 0..height {
-        for x in 0..width {
-            // sample neighboring heights
-            let h_north = sample_height(height_data, width, height, x, y + 1);
-            let h_south = sample_height(height_data, width, height, x, y.wrapping_sub(1));
-            let h_east = sample_height(height_data, width, height, x + 1, y);
-            let h_west = sample_height(height_data, width, height, x.wrapping_sub(1), y);
-            
-            let u = x as f32 / (width - 1) as f32;
-            let v = y as f32 / (height - 1) as f32;
-
-            // calculate world positions
-            let pos_north = height_to_world_position(u, wrap_v(v + 1.0 / height as f32), h_north);
-            let pos_south = height_to_world_position(u, wrap_v(v - 1.0 / height as f32), h_south);
-            let pos_east = height_to_world_position(wrap_u(u + 1.0 / width as f32), v, h_east);
-            let pos_west = height_to_world_position(wrap_u(u - 1.0 / width as f32), v, h_west);
-            
-            // calculate tangent vectors
-            let tangent_ns = (pos_north - pos_south).normalize();
-            let tangent_ew = (pos_east - pos_west).normalize();
-
-            let normal = tangent_ns.cross(tangent_ew).normalize();
-
-            // to 255 range
-            let r = ((normal.x + 1.0) * 0.5 * 255.0) as u8;
-            let g = ((normal.y + 1.0) * 0.5 * 255.0) as u8; 
-            let b = ((normal.z + 1.0) * 0.5 * 255.0) as u8;
-
-            let pixel_index = (y * width + x) * 4;
-            normal_data[pixel_index] = r;     // X component
-            normal_data[pixel_index + 1] = g; // Y component  
-            normal_data[pixel_index + 2] = b; // Z component
-            normal_data[pixel_index + 3] = 255; // Alpha
-        }
+/// bump when the encoding changes so old saved maps are regenerated
+const NORMAL_MAP_VERSION: u64 = 2;
+
+/// Generates a normal map from a height map
+/// each pixel's normal comes from the height slope to its neighbours,
+/// measured in km on the sphere's surface
+/// rows are split into bands that run in parallel on the async compute pool
+pub async fn generate_normal_map(height_map: Arc<Heightmap>) -> Image {
+    let width = height_map.width;
+    let height = height_map.height;
+    let format = normal_map_format();
+
+    let pool = AsyncComputeTaskPool::get();
+    let bands = pool.thread_num().max(1) * 4; // a few per thread to balance uneven bands
+    let rows_per_band = height.div_ceil(bands).max(1);
+
+    let tasks: Vec<_> = (0..height)
+        .step_by(rows_per_band)
+        .map(|first_row| {
+            let height_map = height_map.clone();
+            let rows = first_row..(first_row + rows_per_band).min(height);
+            pool.spawn(async move { normal_rows(&height_map, rows, format) })
+        })
+        .collect();
+
+    // create output buffer, bands come back in order
+    let texel_size = format.block_copy_size(None).unwrap_or(4) as usize;
+    let mut normal_data = Vec::with_capacity(width * height * texel_size);
+    for task in tasks {
+        normal_data.extend(task.await);
     }
 
     // create new image
@@ -83,87 +62,133 @@ pub fn generate_normal_map(height_map: &Image) -> Image {
         },
         TextureDimension::D2,
         normal_data,
-        bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
+        format,
         RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
     )
 }
 
+/// linear, 8 or 16 bits per channel depending on config
+fn normal_map_format() -> TextureFormat {
+    if NORMAL_MAP_16BIT { TextureFormat::Rgba16Unorm } else { TextureFormat::Rgba8Unorm }
+}
+
+/// encoded normal data for a range of rows
+fn normal_rows(height_map: &Heightmap, rows: Range<usize>, format: TextureFormat) -> Vec<u8> {
+    let width = height_map.width;
+    let mut normal_data = Vec::with_capacity(rows.len() * width * 8);
 
-/// Sample height from a texture
-/// wrapping implemented
-fn sample_height(
-    height_data: &[u8], 
-    width: usize, 
-    height: usize, 
-    x: usize, 
-    y: usize
-) -> f32 {
-    // wrap X coordinate
-    let wrapped_x = x % width;
-    let wrapped_y = y.min(height - 1);
-
-    let pixel_index = (wrapped_y * width + wrapped_x) * 4;
-
-    if pixel_index < height_data.len() {
-        height_data[pixel_index] as f32 / 255.0
-    } else {
-        0.0
+    for y in rows {
+        for x in 0..width {
+            let normal = tangent_space_normal(height_map, x, y);
+
+            // -1..1 to 0..1
+            let encoded = (normal * 0.5 + 0.5).extend(1.0).to_array();
+            if format == TextureFormat::Rgba16Unorm {
+                for c in encoded {
+                    normal_data.extend_from_slice(&((c * 65535.0).round() as u16).to_le_bytes());
+                }
+            } else {
+                normal_data.extend(encoded.map(|c| (c * 255.0).round() as u8));
+            }
+        }
     }
+
+    normal_data
 }
 
-/// Convert UV coordinates and height to world position on sphere
-fn height_to_world_position(u: f32, v: f32, height: f32) -> Vec3 {
-    // UV to longitude/latitude
-    let longitude = (u * 2.0 - 1.0) * std::f32::consts::PI; // -π to π
-    let latitude = (0.5 - v) * std::f32::consts::PI; // -π/2 to π/2
-    
-    // apply height displacement  
-    let radius = EARTH_RADIUS + height * crate::config::DISPLACEMENT_SCALE;
-    
-    let x = radius * latitude.cos() * longitude.cos();
-    let y = radius * latitude.sin();
-    let z = radius * latitude.cos() * longitude.sin();
-    
-    Vec3::new(x, y, z)
+/// surface normal at a texel in tangent space: x east, y north, z up
+fn tangent_space_normal(height_map: &Heightmap, x: usize, y: usize) -> Vec3 {
+    let width = height_map.width as f32;
+    let height = height_map.height as f32;
+
+    // sample neighboring heights, x wraps and y clamps
+    // rows run from north to south
+    let (xi, yi) = (x as i64, y as i64);
+    let h_north = height_map.texel(xi, yi - 1);
+    let h_south = height_map.texel(xi, yi + 1);
+    let h_east = height_map.texel(xi + 1, yi);
+    let h_west = height_map.texel(xi - 1, yi);
+
+    // texel spacing on the surface (km), east-west shrinks towards the poles
+    let latitude = (0.5 - (y as f32 + 0.5) / height) * PI;
+    let east_spacing = (2.0 * PI * EARTH_RADIUS * latitude.cos() / width).max(1e-6);
+    let north_spacing = PI * EARTH_RADIUS / height;
+
+    let slope_east = (h_east - h_west) * DISPLACEMENT_SCALE / (2.0 * east_spacing);
+    let slope_north = (h_north - h_south) * DISPLACEMENT_SCALE / (2.0 * north_spacing);
+
+    Vec3::new(-slope_east, -slope_north, 1.0).normalize()
 }
 
-/// Wrap U coordinate (longitude)
-fn wrap_u(u: f32) -> f32 {
-    if u < 0.0 {
-        u + 1.0
-    } else if u > 1.0 {
-        u - 1.0
-    } else {
-        u
+/// reuse the saved normal map if it was built from the same heightmap and settings,
+/// otherwise generate a new one and save it along with its cache key
+pub async fn cached_normal_map(height_map: Arc<Heightmap>) -> Image {
+    let key = cache_key(&height_map);
+    let image_path = format!("assets/{}", SAVED_NORMAL_MAP_PATH);
+    let key_path = format!("assets/{}", SAVED_NORMAL_MAP_KEY_PATH);
+
+    let saved_key = std::fs::read_to_string(&key_path).ok().and_then(|s| u64::from_str_radix(s.trim(), 16).ok());
+    if USE_SAVED_NORMAL_MAP && saved_key == Some(key) {
+        // read directly rather than through the asset server, which would treat the PNG as sRGB
+        match image::open(&image_path) {
+            Ok(saved) => {
+                info!("Using saved normal map...");
+                return Image::from_dynamic(saved, false, RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD);
+            }
+            Err(e) => warn!("Failed to read saved normal map: {:?}", e),
+        }
     }
+
+    info!("Heightmap or normal map settings changed, creating normal map...");
+    let image = generate_normal_map(height_map).await;
+
+    if let Err(e) = save_image_as_png(&image, &image_path) {
+        warn!("Failed to save normal map: {:?}", e);
+        return image;
+    }
+    if let Err(e) = std::fs::write(&key_path, format!("{:016x}\n", key)) {
+        warn!("Failed to write normal map cache key: {:?}", e);
+    }
+    info!("Normal map generated, saved to: {}", image_path);
+
+    image
 }
 
-/// Clamp V coordinate (latitude)
-fn wrap_v(v: f32) -> f32 {
-    v.clamp(0.0, 1.0)
+/// identifies everything the normal map depends on
+fn cache_key(height_map: &Heightmap) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write_u64(NORMAL_MAP_VERSION);
+    hasher.write_u64(height_map.content_hash());
+    hasher.write_u64(DISPLACEMENT_SCALE.to_bits() as u64);
+    hasher.write_u64(EARTH_RADIUS.to_bits() as u64);
+    hasher.write_u8(NORMAL_MAP_16BIT as u8);
+    hasher.finish()
 }
 
 // HELPERS
 
-/// save normal map to an image file 
-pub fn save_image_as_png(image: &Image, path: &str) {
+/// save an RGBA image as PNG, 16-bit images are written as 16-bit PNGs
+pub fn save_image_as_png(image: &Image, path: &str) -> image::ImageResult<()> {
     let width = image.texture_descriptor.size.width;
     let height = image.texture_descriptor.size.height;
+    let format = image.texture_descriptor.format;
 
-    // check if image format compatible
-    assert!(
-        image.texture_descriptor.format == TextureFormat::Rgba8Unorm
-            || image.texture_descriptor.format == TextureFormat::Rgba8UnormSrgb,
-        "Unsupported texture format for saving"
-    );
-
-    // extract raw RGBA8 data
+    // extract raw data
     let data = image.data.as_ref().expect("Image data is missing");
 
     // convert to ImageBuffer
-    let buffer: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(width, height, data.to_vec())
-        .expect("Failed to create ImageBuffer");
-
-    // save to disk
-    buffer.save(path).expect("Failed to save image");
-}
\ No newline at end of file
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+            let buffer: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(width, height, data.to_vec())
+                .expect("Failed to create ImageBuffer");
+            buffer.save(path)
+        }
+        TextureFormat::Rgba16Unorm => {
+            let samples = data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+            let buffer: ImageBuffer<Rgba<u16>, Vec<u16>> = ImageBuffer::from_raw(width, height, samples)
+                .expect("Failed to create ImageBuffer");
+            buffer.save(path)
+        }
+        _ => panic!("Unsupported texture format for saving: {:?}", format),
+    }
+}
@@ -0,0 +1,589 @@
+//! overlays.rs
+//!
+//! Vector map overlays draped over the globe: coastlines, borders, cities and a lat/lon graticule
+//! read from GeoJSON or Shapefile (.shp) in WGS84 lon/lat, the .dbf/.prj sidecars are ignored
+//!
+//! lines are cut into short segments and lifted onto the displaced terrain,
+//! each layer becomes one LineList mesh parented to the earth, toggled from the settings panel
+
+use std::path::Path;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use serde_json::Value;
+
+use crate::config::{
+    DISPLACEMENT_SCALE, EARTH_RADIUS, GRATICULE_SPACING_DEG, OVERLAY_BORDERS, OVERLAY_CITIES, OVERLAY_COASTLINES,
+    OVERLAY_LIFT,
+};
+use crate::systems::earth::Earth;
+use crate::systems::earth::heightmap::{HeightFilter, Heightmap};
+use crate::systems::earth::lod::TerrainLod;
+use crate::systems::earth::uv::LatLon;
+use crate::systems::satellites::orbits::LineBuffers;
+use crate::systems::settings::RenderSettings;
+use crate::systems::controls::{Action, Actions};
+
+const MAX_SEGMENT_DEG: f32 = 0.5; // longer segments would cut through the curve of the globe
+const CITY_MARKER_SIZE: f32 = 12.0; // km, half the width of a city cross
+
+/// one toggleable layer
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlayLayer {
+    Coastlines,
+    Borders,
+    Cities,
+    Graticule,
+}
+
+impl OverlayLayer {
+    const ALL: [Self; 4] = [Self::Coastlines, Self::Borders, Self::Cities, Self::Graticule];
+
+    fn color(self) -> Color {
+        match self {
+            Self::Coastlines => Color::srgba(0.6, 0.85, 1.0, 0.8),
+            Self::Borders => Color::srgba(1.0, 0.85, 0.4, 0.7),
+            Self::Cities => Color::srgba(1.0, 0.95, 0.7, 0.9),
+            Self::Graticule => Color::srgba(1.0, 1.0, 1.0, 0.25),
+        }
+    }
+
+    fn shown(self, settings: &RenderSettings) -> bool {
+        match self {
+            Self::Coastlines => settings.coastlines,
+            Self::Borders => settings.borders,
+            Self::Cities => settings.cities,
+            Self::Graticule => settings.graticule,
+        }
+    }
+
+    /// features for this layer, None if its file is missing or unreadable
+    fn load(self) -> Option<VectorFeatures> {
+        match self {
+            Self::Coastlines => VectorFeatures::load(OVERLAY_COASTLINES),
+            Self::Borders => VectorFeatures::load(OVERLAY_BORDERS),
+            Self::Cities => VectorFeatures::load(OVERLAY_CITIES),
+            Self::Graticule => Some(VectorFeatures::graticule(GRATICULE_SPACING_DEG)),
+        }
+    }
+}
+
+/// geometry in degrees, x longitude and y latitude
+#[derive(Clone, Debug, Default)]
+pub struct VectorFeatures {
+    pub lines: Vec<Vec<Vec2>>, // polylines and polygon rings
+    pub points: Vec<Vec2>,
+}
+
+impl VectorFeatures {
+    pub fn load(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            info!("No overlay data at {:?}, skipping", path);
+            return None;
+        }
+
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let result = match extension.as_str() {
+            "geojson" | "json" => std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str::<Value>(&text).map_err(|e| e.to_string()))
+                .map(|json| Self::from_geojson(&json)),
+            "shp" => std::fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| Self::from_shapefile(&bytes)),
+            _ => Err(format!("unsupported overlay format: {}", extension)),
+        };
+
+        match result {
+            Ok(features) => {
+                info!("Loaded overlay {:?}: {} lines, {} points", path, features.lines.len(), features.points.len());
+                Some(features)
+            }
+            Err(e) => {
+                warn!("Failed to read overlay {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// parallels and meridians every `spacing` degrees
+    pub fn graticule(spacing: f32) -> Self {
+        let spacing = spacing.max(1.0);
+        let mut lines = Vec::new();
+
+        // meridians stop short of the poles where they all meet
+        let mut lon = -180.0;
+        while lon < 180.0 {
+            lines.push(vec![Vec2::new(lon, -90.0 + spacing), Vec2::new(lon, 90.0 - spacing)]);
+            lon += spacing;
+        }
+
+        // parallels, subdivided along the latitude when tessellated
+        let mut lat = -90.0 + spacing;
+        while lat < 90.0 - 1e-3 {
+            lines.push((0..=4).map(|i| Vec2::new(-180.0 + i as f32 * 90.0, lat)).collect());
+            lat += spacing;
+        }
+
+        Self { lines, points: Vec::new() }
+    }
+
+    /// FeatureCollection, Feature, GeometryCollection or a bare geometry
+    pub fn from_geojson(json: &Value) -> Self {
+        let mut features = Self::default();
+        features.add_geojson(json);
+        features
+    }
+
+    fn add_geojson(&mut self, json: &Value) {
+        let coordinates = json.get("coordinates");
+        match json.get("type").and_then(Value::as_str) {
+            Some("FeatureCollection") => {
+                for feature in json.get("features").and_then(Value::as_array).into_iter().flatten() {
+                    self.add_geojson(feature);
+                }
+            }
+            Some("Feature") => {
+                if let Some(geometry) = json.get("geometry") {
+                    self.add_geojson(geometry);
+                }
+            }
+            Some("GeometryCollection") => {
+                for geometry in json.get("geometries").and_then(Value::as_array).into_iter().flatten() {
+                    self.add_geojson(geometry);
+                }
+            }
+            Some("Point") => self.points.extend(coordinates.and_then(geojson_position)),
+            Some("MultiPoint") => self.points.extend(geojson_positions(coordinates)),
+            Some("LineString") => self.lines.push(geojson_positions(coordinates)),
+            Some("MultiLineString") | Some("Polygon") => {
+                self.lines.extend(geojson_array(coordinates).map(|line| geojson_positions(Some(line))));
+            }
+            Some("MultiPolygon") => {
+                for polygon in geojson_array(coordinates) {
+                    self.lines.extend(geojson_array(Some(polygon)).map(|ring| geojson_positions(Some(ring))));
+                }
+            }
+            _ => {} // null geometry or something we can't draw
+        }
+    }
+
+    /// ESRI shapefile main file, points, multipoints, polylines and polygons (Z/M variants too)
+    /// https://www.esri.com/content/dam/esrisites/sitecore-archive/Files/Pdfs/library/whitepapers/pdfs/shapefile.pdf
+    pub fn from_shapefile(bytes: &[u8]) -> Result<Self, String> {
+        const HEADER_SIZE: usize = 100;
+        if bytes.len() < HEADER_SIZE || be_i32(bytes, 0) != Some(9994) {
+            return Err("not a shapefile".to_string());
+        }
+
+        let mut features = Self::default();
+        let mut offset = HEADER_SIZE;
+        while offset + 8 <= bytes.len() {
+            // record header is big endian, content length in 16-bit words
+            let length = be_i32(bytes, offset + 4).and_then(non_negative).ok_or("bad record length")? * 2;
+            let content = bytes.get(offset + 8..offset + 8 + length).ok_or("truncated record")?;
+            offset += 8 + length;
+
+            let shape_type = le_i32(content, 0).ok_or("empty record")?;
+            match shape_type {
+                0 => {} // null shape
+                1 | 11 | 21 => features.points.push(le_point(content, 4).ok_or("truncated point")?),
+                8 | 18 | 28 => {
+                    // bounding box, then point count
+                    let count = le_i32(content, 36).and_then(non_negative).ok_or("bad multipoint count")?;
+                    if 40 + count * 16 > content.len() {
+                        return Err("multipoint runs past its record".to_string());
+                    }
+                    features.points.extend((0..count).filter_map(|i| le_point(content, 40 + i * 16)));
+                }
+                3 | 13 | 23 | 5 | 15 | 25 => {
+                    // bounding box, part and point counts, part starts, then points
+                    let parts = le_i32(content, 36).and_then(non_negative).ok_or("bad polyline part count")?;
+                    let count = le_i32(content, 40).and_then(non_negative).ok_or("bad polyline point count")?;
+                    let points_offset = 44 + parts * 4;
+                    if points_offset + count * 16 > content.len() {
+                        return Err("polyline runs past its record".to_string());
+                    }
+                    let starts = (0..parts)
+                        .map(|i| le_i32(content, 44 + i * 4).and_then(non_negative).filter(|&start| start <= count))
+                        .collect::<Option<Vec<usize>>>()
+                        .ok_or("bad polyline part start")?;
+
+                    for (i, &start) in starts.iter().enumerate() {
+                        let end = starts.get(i + 1).copied().unwrap_or(count).min(count);
+                        features
+                            .lines
+                            .push((start..end).filter_map(|p| le_point(content, points_offset + p * 16)).collect());
+                    }
+                }
+                other => return Err(format!("unsupported shape type {}", other)),
+            }
+        }
+
+        Ok(features)
+    }
+}
+
+fn geojson_array(value: Option<&Value>) -> impl Iterator<Item = &Value> {
+    value.and_then(Value::as_array).into_iter().flatten()
+}
+
+/// [lon, lat] or [lon, lat, altitude]
+fn geojson_position(value: &Value) -> Option<Vec2> {
+    let position = value.as_array()?;
+    Some(Vec2::new(position.first()?.as_f64()? as f32, position.get(1)?.as_f64()? as f32))
+}
+
+fn geojson_positions(value: Option<&Value>) -> Vec<Vec2> {
+    geojson_array(value).filter_map(geojson_position).collect()
+}
+
+fn be_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn le_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// counts and offsets are signed in the file, a negative one is corrupt
+fn non_negative(value: i32) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+fn le_point(bytes: &[u8], offset: usize) -> Option<Vec2> {
+    let x = f64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?);
+    let y = f64::from_le_bytes(bytes.get(offset + 8..offset + 16)?.try_into().ok()?);
+    Some(Vec2::new(x as f32, y as f32))
+}
+
+// TESSELLATION
+
+/// point on the terrain in the earth's frame, lifted a little so lines stay visible
+fn surface_point(direction: Vec3, heightmap: &Heightmap) -> Vec3 {
+    let (u, v) = LatLon::from(direction).to_uv();
+    let height = heightmap.sample(u, v, HeightFilter::Bilinear) * DISPLACEMENT_SCALE;
+    direction * (EARTH_RADIUS + height + OVERLAY_LIFT)
+}
+
+fn direction(lon_lat: Vec2) -> Vec3 {
+    LatLon::from_degrees(lon_lat.y, lon_lat.x).to_direction()
+}
+
+/// lines and point markers as line segments draped over the terrain
+pub fn tessellate(features: &VectorFeatures, heightmap: &Heightmap, color: LinearRgba) -> LineBuffers {
+    let mut buffers = LineBuffers::default();
+
+    for line in &features.lines {
+        let mut points = Vec::new();
+        for pair in line.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+
+            // GeoJSON edges are straight in lon/lat (RFC 7946), so subdivide there
+            let steps = ((b - a).abs().max_element() / MAX_SEGMENT_DEG).ceil().max(1.0) as usize;
+            if points.is_empty() {
+                points.push(surface_point(direction(a), heightmap));
+            }
+            for i in 1..=steps {
+                let t = i as f32 / steps as f32;
+                points.push(surface_point(direction(a.lerp(b, t)), heightmap));
+            }
+        }
+
+        let colors = vec![color; points.len()];
+        buffers.push_strip(&points, &colors);
+    }
+
+    // a small cross on each point, in the local east/north plane
+    for &lon_lat in &features.points {
+        let up = direction(lon_lat);
+        let east = Vec3::Y.cross(up).try_normalize().unwrap_or(Vec3::X);
+        let north = up.cross(east);
+        let center = surface_point(up, heightmap);
+
+        for axis in [east, north] {
+            buffers.push_strip(
+                &[center - axis * CITY_MARKER_SIZE, center + axis * CITY_MARKER_SIZE],
+                &[color, color],
+            );
+        }
+    }
+
+    buffers
+}
+
+// BEVY
+
+/// overlays are built once, after the terrain's heightmap is ready
+#[derive(Resource, Default)]
+pub struct OverlayState {
+    task: Option<Task<Vec<(OverlayLayer, LineBuffers)>>>,
+    started: bool,
+}
+
+pub fn build(
+    mut commands: Commands,
+    mut state: ResMut<OverlayState>,
+    lod: Res<TerrainLod>,
+    settings: Res<RenderSettings>,
+    earth_query: Query<Entity, With<Earth>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !state.started {
+        state.started = true;
+        let heightmap: Arc<Heightmap> = lod.displacement();
+        state.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+            OverlayLayer::ALL
+                .into_iter()
+                .filter_map(|layer| {
+                    let features = layer.load()?;
+                    Some((layer, tessellate(&features, &heightmap, LinearRgba::from(layer.color()))))
+                })
+                .filter(|(_, buffers)| !buffers.is_empty())
+                .collect()
+        }));
+    }
+
+    let Some(task) = state.task.as_mut() else { return; };
+    let Some(layers) = block_on(future::poll_once(task)) else { return; };
+    state.task = None;
+    let Ok(earth) = earth_query.single() else { return; };
+
+    for (layer, buffers) in layers {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD);
+        buffers.write_to(&mut mesh);
+
+        // vertex colours carry the layer colour
+        let material = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+
+        commands.spawn((
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(material),
+            Transform::default(),
+            if layer.shown(&settings) { Visibility::Inherited } else { Visibility::Hidden },
+            layer,
+            ChildOf(earth),
+        ));
+    }
+}
+
+/// toggle the lat/lon grid with G
+pub fn toggle_graticule(
+    actions: Res<Actions>,
+    mut settings: ResMut<RenderSettings>,
+) {
+    if actions.just_pressed(Action::ToggleGraticule) {
+        settings.graticule = !settings.graticule;
+    }
+}
+
+/// show or hide layers from the settings panel
+pub fn apply_visibility(
+    settings: Res<RenderSettings>,
+    mut layers: Query<(&OverlayLayer, &mut Visibility)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (layer, mut visibility) in &mut layers {
+        visibility.set_if_neq(if layer.shown(&settings) { Visibility::Inherited } else { Visibility::Hidden });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // SHAPEFILE FIXTURES
+
+    /// 100 byte main file header, only the file code and shape type matter here
+    fn shapefile(shape_type: i32, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0; 100];
+        bytes[0..4].copy_from_slice(&9994i32.to_be_bytes());
+        bytes[28..32].copy_from_slice(&1000i32.to_le_bytes());
+        bytes[32..36].copy_from_slice(&shape_type.to_le_bytes());
+        for (i, content) in records.iter().enumerate() {
+            bytes.extend(((i + 1) as i32).to_be_bytes());
+            bytes.extend(((content.len() / 2) as i32).to_be_bytes());
+            bytes.extend(content);
+        }
+        let words = (bytes.len() / 2) as i32;
+        bytes[24..28].copy_from_slice(&words.to_be_bytes());
+        bytes
+    }
+
+    fn point_record(x: f64, y: f64) -> Vec<u8> {
+        let mut content = 1i32.to_le_bytes().to_vec();
+        content.extend(x.to_le_bytes());
+        content.extend(y.to_le_bytes());
+        content
+    }
+
+    /// shape type, bounding box, part and point counts, part starts, points
+    fn polyline_record(parts: &[i32], count: i32, points: &[(f64, f64)]) -> Vec<u8> {
+        let mut content = 3i32.to_le_bytes().to_vec();
+        content.extend([0u8; 32]);
+        content.extend((parts.len() as i32).to_le_bytes());
+        content.extend(count.to_le_bytes());
+        for part in parts {
+            content.extend(part.to_le_bytes());
+        }
+        for (x, y) in points {
+            content.extend(x.to_le_bytes());
+            content.extend(y.to_le_bytes());
+        }
+        content
+    }
+
+    fn set_i32(content: &mut [u8], offset: usize, value: i32) {
+        content[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn shapefile_points_and_polylines() {
+        let points = [(-10.0, 20.0), (0.0, 0.0), (5.5, 51.5), (170.0, -45.0), (179.0, -46.0)];
+        let bytes = shapefile(3, &[
+            point_record(2.35, 48.85),
+            vec![0, 0, 0, 0], // null shape
+            polyline_record(&[0, 3], 5, &points),
+        ]);
+
+        let features = VectorFeatures::from_shapefile(&bytes).unwrap();
+        assert_eq!(features.points, vec![Vec2::new(2.35, 48.85)]);
+        assert_eq!(features.lines.len(), 2);
+        assert_eq!(features.lines[0], vec![Vec2::new(-10.0, 20.0), Vec2::new(0.0, 0.0), Vec2::new(5.5, 51.5)]);
+        assert_eq!(features.lines[1], vec![Vec2::new(170.0, -45.0), Vec2::new(179.0, -46.0)]);
+    }
+
+    #[test]
+    fn shapefile_multipoint() {
+        let mut content = 8i32.to_le_bytes().to_vec();
+        content.extend([0u8; 32]);
+        content.extend(2i32.to_le_bytes());
+        for value in [1.0f64, 2.0, 3.0, 4.0] {
+            content.extend(value.to_le_bytes());
+        }
+
+        let features = VectorFeatures::from_shapefile(&shapefile(8, &[content])).unwrap();
+        assert_eq!(features.points, vec![Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0)]);
+    }
+
+    #[test]
+    fn shapefile_rejects_bad_headers() {
+        assert!(VectorFeatures::from_shapefile(&[0; 50]).is_err());
+        assert!(VectorFeatures::from_shapefile(&[0; 100]).is_err());
+
+        // record length says more than there is
+        let mut bytes = shapefile(1, &[point_record(1.0, 2.0)]);
+        bytes[104..108].copy_from_slice(&100i32.to_be_bytes());
+        assert!(VectorFeatures::from_shapefile(&bytes).is_err());
+
+        // negative record length
+        bytes[104..108].copy_from_slice(&(-10i32).to_be_bytes());
+        assert!(VectorFeatures::from_shapefile(&bytes).is_err());
+    }
+
+    #[test]
+    fn shapefile_rejects_bad_counts() {
+        let points = [(0.0, 0.0), (1.0, 1.0)];
+
+        // negative part and point counts
+        let mut negative_parts = polyline_record(&[0], 2, &points);
+        set_i32(&mut negative_parts, 36, -1);
+        let mut negative_count = polyline_record(&[0], 2, &points);
+        set_i32(&mut negative_count, 40, -2);
+        // negative and out of range part starts
+        let negative_start = polyline_record(&[-4], 2, &points);
+        let late_start = polyline_record(&[0, 3], 2, &points);
+        // counts claiming more than the record holds
+        let many_points = polyline_record(&[0], 1_000_000, &points);
+        let many_parts = polyline_record(&[0], 2, &points).into_iter().enumerate()
+            .map(|(i, byte)| if (36..40).contains(&i) { 0x7f } else { byte })
+            .collect();
+
+        for record in [negative_parts, negative_count, negative_start, late_start, many_points, many_parts] {
+            assert!(VectorFeatures::from_shapefile(&shapefile(3, &[record])).is_err());
+        }
+
+        let mut multipoint = 8i32.to_le_bytes().to_vec();
+        multipoint.extend([0u8; 32]);
+        multipoint.extend((-1i32).to_le_bytes());
+        assert!(VectorFeatures::from_shapefile(&shapefile(8, &[multipoint.clone()])).is_err());
+        set_i32(&mut multipoint, 36, 3);
+        assert!(VectorFeatures::from_shapefile(&shapefile(8, &[multipoint])).is_err());
+    }
+
+    // GEOJSON FIXTURES
+
+    #[test]
+    fn geojson_geometry_types() {
+        let json = json!({
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature", "properties": { "name": "Paris" },
+                  "geometry": { "type": "Point", "coordinates": [2.35, 48.85] } },
+                { "type": "Feature", "properties": {}, "geometry": null },
+                { "type": "Feature", "geometry": { "type": "MultiPoint", "coordinates": [[1, 2], [3, 4, 100]] } },
+                { "type": "Feature", "geometry": { "type": "LineString", "coordinates": [[0, 0], [10, 5]] } },
+                { "type": "Feature", "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [
+                        [[0, 0], [4, 0], [4, 4], [0, 0]],
+                        [[1, 1], [2, 1], [2, 2], [1, 1]]
+                    ]
+                } },
+                { "type": "Feature", "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [[[[10, 10], [11, 10], [10, 11], [10, 10]]], [[[-5, -5], [-6, -5], [-5, -6], [-5, -5]]]]
+                } },
+                { "type": "Feature", "geometry": {
+                    "type": "GeometryCollection",
+                    "geometries": [
+                        { "type": "MultiLineString", "coordinates": [[[20, 20], [21, 21]], [[30, 30], [31, 31]]] },
+                        { "type": "Point", "coordinates": [-75.0, 45.0] }
+                    ]
+                } }
+            ]
+        });
+
+        let features = VectorFeatures::from_geojson(&json);
+        assert_eq!(features.points, vec![
+            Vec2::new(2.35, 48.85),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(3.0, 4.0),
+            Vec2::new(-75.0, 45.0),
+        ]);
+        // line, polygon ring and hole, two multipolygon rings, two multilinestring lines
+        assert_eq!(features.lines.len(), 7);
+        assert_eq!(features.lines[0], vec![Vec2::new(0.0, 0.0), Vec2::new(10.0, 5.0)]);
+        assert_eq!(features.lines[2].len(), 4);
+        assert_eq!(features.lines[4][1], Vec2::new(-6.0, -5.0));
+        assert_eq!(features.lines[6], vec![Vec2::new(30.0, 30.0), Vec2::new(31.0, 31.0)]);
+    }
+
+    #[test]
+    fn geojson_skips_what_it_cannot_draw() {
+        // bare geometry, bad positions dropped, unknown types ignored
+        let line = VectorFeatures::from_geojson(&json!({
+            "type": "LineString",
+            "coordinates": [[0, 0], ["a", 1], [5], [2, 2]]
+        }));
+        assert_eq!(line.lines, vec![vec![Vec2::new(0.0, 0.0), Vec2::new(2.0, 2.0)]]);
+
+        let unknown = VectorFeatures::from_geojson(&json!({ "type": "Circle", "coordinates": [0, 0] }));
+        assert!(unknown.lines.is_empty() && unknown.points.is_empty());
+
+        let empty = VectorFeatures::from_geojson(&json!({ "type": "FeatureCollection" }));
+        assert!(empty.lines.is_empty() && empty.points.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+//! earth/stations.rs
+//!
+//! Ground stations, named lat/lon markers on the globe (and on the map, see map.rs)
+//! there are none by default, scenario files bring them, see scenario.rs
+
+use bevy::prelude::*;
+
+use crate::config::{EARTH_RADIUS, OVERLAY_LIFT};
+use crate::systems::earth::Earth;
+use crate::systems::earth::uv::LatLon;
+
+const MARKER_RADIUS: f32 = 60.0; // km
+const MARKER_HEIGHT: f32 = 300.0; // km, the mast above the marker
+pub const STATION_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroundStation {
+    pub name: String,
+    pub latitude: f32, // degrees
+    pub longitude: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct GroundStations(pub Vec<GroundStation>);
+
+/// a ring on the surface with a mast, turning with the globe
+pub fn draw(
+    stations: Res<GroundStations>,
+    earth: Query<&GlobalTransform, With<Earth>>,
+    mut gizmos: Gizmos,
+) {
+    let Ok(earth) = earth.single() else { return; };
+
+    for station in &stations.0 {
+        let up = earth.rotation() * LatLon::from_degrees(station.latitude, station.longitude).to_direction();
+        let base = earth.translation() + up * (EARTH_RADIUS + OVERLAY_LIFT);
+
+        let isometry = Isometry3d::new(base, Quat::from_rotation_arc(Vec3::Z, up));
+        gizmos.circle(isometry, MARKER_RADIUS, STATION_COLOR).resolution(16);
+        gizmos.line(base, base + up * MARKER_HEIGHT, STATION_COLOR);
+    }
+}
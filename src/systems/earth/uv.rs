@@ -26,6 +26,20 @@ impl From<Vec3> for LatLon {
 }
 
 impl LatLon {
+    pub fn from_degrees(latitude: f32, longitude: f32) -> Self {
+        LatLon {
+            latitude: latitude.to_radians(),
+            longitude: longitude.to_radians(),
+        }
+    }
+
+    /// unit vector in the earth's frame, inverse of `From<Vec3>`
+    pub fn to_direction(&self) -> Vec3 {
+        let (sin_lat, cos_lat) = self.latitude.sin_cos();
+        let (sin_lon, cos_lon) = self.longitude.sin_cos();
+        Vec3::new(cos_lat * sin_lon, sin_lat, cos_lat * cos_lon)
+    }
+
     pub fn as_degrees(&self) -> (f32, f32) {
         let latitude = self.latitude * (180.0 / PI);
         let longitude = self.longitude * (180.0 / PI);
@@ -38,4 +52,13 @@ impl LatLon {
         let u = (lon + 180.0) / 360.0;
         (u, v)
     }
-}
\ No newline at end of file
+
+    /// like `to_uv`, but u is shifted by whole turns to lie within half a turn of `reference_u`
+    /// keeps u continuous across the antimeridian, so values can leave 0..1 slightly
+    /// and must be wrapped when sampling (the earth shader uses `fract`)
+    pub fn to_uv_near(&self, reference_u: f32) -> (f32, f32) {
+        let (u, v) = self.to_uv();
+        let u = reference_u + (u - reference_u + 0.5).rem_euclid(1.0) - 0.5;
+        (u, v)
+    }
+}
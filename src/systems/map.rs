@@ -0,0 +1,540 @@
+//! map.rs
+//!
+//! Flat 2D world map, shown instead of or beside the globe (M cycles globe / split / map)
+//! the diffuse texture is laid out in map coordinates, x is longitude and y latitude in degrees
+//! (or the Mercator equivalent, P switches), and satellites, ground tracks, footprints and the
+//! day/night terminator are drawn on top from the same `Satellite` entities and `TimeState`
+//! the map has its own camera on its own render layer, the UI gets a full-window camera of its own
+
+use bevy::asset::RenderAssetUsages;
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy::render::camera::{ScalingMode, Viewport};
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::view::RenderLayers;
+use bevy::window::PrimaryWindow;
+use chrono::{DateTime, Utc};
+
+use crate::config::{DISPLACEMENT_SCALE, EARTH_DIFFUSE_TEXTURE, EARTH_RADIUS, MAP_NIGHT_ALPHA, MAP_TRACK_LIMIT, MAP_TRACK_SAMPLES};
+use crate::systems::earth::cursor::{self, CursorLocation, GeoPoint};
+use crate::systems::earth::heightmap::HeightFilter;
+use crate::systems::earth::lod::TerrainLod;
+use crate::systems::earth::stations::{GroundStations, STATION_COLOR};
+use crate::systems::earth::uv::LatLon;
+use crate::systems::satellites::instancing::SatelliteColor;
+use crate::systems::satellites::orbital::{julian_days_since_j2000, gmst, sub_satellite_point, world_to_teme};
+use crate::systems::satellites::{Satellite, SelectedSatellite};
+use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+
+const MAP_LAYER: usize = 1;
+const MAP_BACKGROUND: Color = Color::srgb(0.02, 0.03, 0.06);
+const MERCATOR_MAX_LATITUDE: f32 = 85.051_13; // where the Mercator map is square
+const NIGHT_STEP_DEG: f32 = 2.0; // longitude columns of the night shading
+const FOOTPRINT_SEGMENTS: usize = 64;
+const ICON_RADIUS: f32 = 1.2; // degrees
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapView>()
+            .init_gizmo_group::<MapGizmos>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, (
+                cycle_mode,
+                toggle_projection,
+                apply_layout.after(cycle_mode).after(toggle_projection),
+                rebuild_plane.after(toggle_projection),
+                update_night.after(toggle_projection),
+                draw.after(toggle_projection),
+                update_cursor.after(cursor::update),
+            ));
+    }
+}
+
+/// what takes up the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MapMode {
+    #[default]
+    Globe,
+    Split, // globe on the left, map on the right
+    Map,
+}
+
+impl MapMode {
+    fn next(self) -> Self {
+        match self {
+            MapMode::Globe => MapMode::Split,
+            MapMode::Split => MapMode::Map,
+            MapMode::Map => MapMode::Globe,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MapProjection {
+    #[default]
+    Equirectangular,
+    Mercator,
+}
+
+impl MapProjection {
+    /// latitude/longitude (degrees) to map coordinates
+    pub fn project(self, latitude: f32, longitude: f32) -> Vec2 {
+        match self {
+            MapProjection::Equirectangular => Vec2::new(longitude, latitude),
+            MapProjection::Mercator => {
+                let latitude = latitude.clamp(-MERCATOR_MAX_LATITUDE, MERCATOR_MAX_LATITUDE).to_radians();
+                let y = (std::f32::consts::FRAC_PI_4 + latitude / 2.0).tan().ln();
+                Vec2::new(longitude, y.to_degrees())
+            }
+        }
+    }
+
+    /// map coordinates back to latitude/longitude, None off the map
+    pub fn unproject(self, point: Vec2) -> Option<(f32, f32)> {
+        let latitude = match self {
+            MapProjection::Equirectangular => point.y,
+            MapProjection::Mercator => (2.0 * point.y.to_radians().exp().atan() - std::f32::consts::FRAC_PI_2).to_degrees(),
+        };
+        (point.x.abs() <= 180.0 && latitude.abs() <= self.max_latitude()).then_some((latitude, point.x))
+    }
+
+    /// latitude the map stops at
+    pub fn max_latitude(self) -> f32 {
+        match self {
+            MapProjection::Equirectangular => 90.0,
+            MapProjection::Mercator => MERCATOR_MAX_LATITUDE,
+        }
+    }
+
+    /// height of the whole map in map units, it's always 360 wide
+    fn height(self) -> f32 {
+        2.0 * self.project(self.max_latitude(), 0.0).y
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MapView {
+    pub mode: MapMode,
+    pub projection: MapProjection,
+}
+
+/// gizmos that only the map camera sees
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct MapGizmos;
+
+#[derive(Component)]
+pub struct MapCamera;
+
+#[derive(Component)]
+struct MapPlane;
+
+#[derive(Component)]
+struct MapNight;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut gizmo_config: ResMut<GizmoConfigStore>,
+    asset_server: Res<AssetServer>,
+    view: Res<MapView>,
+) {
+    let (config, _) = gizmo_config.config_mut::<MapGizmos>();
+    config.render_layers = RenderLayers::layer(MAP_LAYER);
+    config.line.width = 1.5;
+
+    // off until a map mode is picked
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: 1,
+            is_active: false,
+            clear_color: ClearColorConfig::Custom(MAP_BACKGROUND),
+            ..default()
+        },
+        Projection::Orthographic(map_projection(view.projection)),
+        RenderLayers::layer(MAP_LAYER),
+        MapCamera,
+    ));
+
+    // the UI would otherwise follow the highest camera and be squeezed into the map viewport
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: 2,
+            clear_color: ClearColorConfig::None,
+            ..default()
+        },
+        RenderLayers::none(),
+        IsDefaultUiCamera,
+    ));
+
+    commands.spawn((
+        Mesh2d(meshes.add(plane_mesh(view.projection))),
+        MeshMaterial2d(materials.add(ColorMaterial {
+            texture: Some(asset_server.load(EARTH_DIFFUSE_TEXTURE)),
+            ..default()
+        })),
+        RenderLayers::layer(MAP_LAYER),
+        MapPlane,
+    ));
+
+    commands.spawn((
+        Mesh2d(meshes.add(night_mesh(&[]))),
+        MeshMaterial2d(materials.add(ColorMaterial::from(Color::srgba(0.0, 0.0, 0.02, MAP_NIGHT_ALPHA)))),
+        Transform::from_xyz(0.0, 0.0, 1.0),
+        RenderLayers::layer(MAP_LAYER),
+        MapNight,
+    ));
+}
+
+/// fit the whole map in the viewport
+fn map_projection(projection: MapProjection) -> OrthographicProjection {
+    OrthographicProjection {
+        scaling_mode: ScalingMode::AutoMin { min_width: 360.0, min_height: projection.height() },
+        ..OrthographicProjection::default_2d()
+    }
+}
+
+/// the texture on a grid of rows, one per degree so Mercator can stretch it
+fn plane_mesh(projection: MapProjection) -> Mesh {
+    let max_latitude = projection.max_latitude();
+    let rows = (2.0 * max_latitude).ceil() as u32;
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    for row in 0..=rows {
+        let latitude = -max_latitude + 2.0 * max_latitude * row as f32 / rows as f32;
+        let v = (90.0 - latitude) / 180.0;
+        for (longitude, u) in [(-180.0, 0.0), (180.0, 1.0)] {
+            positions.push(projection.project(latitude, longitude).extend(0.0));
+            uvs.push(Vec2::new(u, v));
+        }
+    }
+
+    let indices = (0..rows)
+        .flat_map(|row| {
+            let i = row * 2;
+            [i, i + 1, i + 3, i, i + 3, i + 2]
+        })
+        .collect();
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+/// one quad per column, from the terminator to the dark pole
+/// `columns` holds the column's longitude and the latitude range that's in night
+fn night_mesh(columns: &[(f32, f32, f32)]) -> Mesh {
+    let positions: Vec<Vec3> = columns
+        .iter()
+        .flat_map(|&(x, bottom, top)| [Vec3::new(x, bottom, 0.0), Vec3::new(x, top, 0.0)])
+        .collect();
+    let indices = (0..columns.len().saturating_sub(1) as u32)
+        .flat_map(|column| {
+            let i = column * 2;
+            [i, i + 2, i + 3, i, i + 3, i + 1]
+        })
+        .collect();
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+/// point on the ground with the sun overhead (latitude, longitude in degrees)
+/// low precision solar coordinates, good to about 0.01°
+/// https://aa.usno.navy.mil/faq/sun_approx
+pub fn subsolar_point(time: DateTime<Utc>) -> (f32, f32) {
+    let days = julian_days_since_j2000(time);
+    let mean_anomaly = (357.529 + 0.98560028 * days).to_radians();
+    let mean_longitude = 280.459 + 0.98564736 * days;
+    let ecliptic_longitude = (mean_longitude
+        + 1.915 * mean_anomaly.sin()
+        + 0.020 * (2.0 * mean_anomaly).sin())
+    .to_radians();
+    let obliquity = (23.439 - 0.00000036 * days).to_radians();
+
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    let longitude = (right_ascension - gmst(time)).to_degrees();
+    (declination.to_degrees() as f32, ((longitude + 180.0).rem_euclid(360.0) - 180.0) as f32)
+}
+
+/// latitude of the terminator at a longitude
+/// on the great circle 90° from the subsolar point
+fn terminator_latitude(subsolar: (f32, f32), longitude: f32) -> f32 {
+    let (sun_latitude, sun_longitude) = (subsolar.0.to_radians(), subsolar.1.to_radians());
+    let tan = -sun_latitude.cos() * (longitude.to_radians() - sun_longitude).cos() / sun_latitude.sin();
+    tan.atan().to_degrees()
+}
+
+/// point at an angular distance and bearing from another (all degrees)
+/// https://www.movable-type.co.uk/scripts/latlong.html#dest-point
+fn destination(latitude: f32, longitude: f32, distance: f32, bearing: f32) -> (f32, f32) {
+    let (lat, distance, bearing) = (latitude.to_radians(), distance.to_radians(), bearing.to_radians());
+    let end_lat = (lat.sin() * distance.cos() + lat.cos() * distance.sin() * bearing.cos()).asin();
+    let delta_lon = (bearing.sin() * distance.sin() * lat.cos()).atan2(distance.cos() - lat.sin() * end_lat.sin());
+    (end_lat.to_degrees(), longitude + delta_lon.to_degrees())
+}
+
+/// satellite's ground point at a time, from the same interpolated path the globe uses
+fn ground_point(satellite: &Satellite, time: DateTime<Utc>) -> (f32, f32, f32) {
+    let position: DVec3 = world_to_teme(satellite.get_position(time));
+    let (latitude, longitude, altitude) = sub_satellite_point(position, time);
+    (latitude as f32, longitude as f32, altitude as f32)
+}
+
+/// polyline over latitude/longitude points, broken where it crosses the antimeridian
+fn draw_wrapped(
+    gizmos: &mut Gizmos<MapGizmos>,
+    projection: MapProjection,
+    points: impl IntoIterator<Item = (f32, f32)>,
+    color: Color,
+) {
+    let mut segment: Vec<Vec2> = Vec::new();
+    let mut last_longitude: Option<f32> = None;
+    for (latitude, longitude) in points {
+        let longitude = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+        if last_longitude.is_some_and(|last| (longitude - last).abs() > 180.0) {
+            gizmos.linestrip_2d(segment.drain(..), color);
+        }
+        segment.push(projection.project(latitude, longitude));
+        last_longitude = Some(longitude);
+    }
+    gizmos.linestrip_2d(segment, color);
+}
+
+/// cycle globe / split / map with M
+fn cycle_mode(
+    actions: Res<Actions>,
+    mut view: ResMut<MapView>,
+) {
+    if actions.just_pressed(Action::CycleView) {
+        view.mode = view.mode.next();
+        info!("View: {:?}", view.mode);
+    }
+}
+
+/// equirectangular / Mercator with P
+fn toggle_projection(
+    actions: Res<Actions>,
+    mut view: ResMut<MapView>,
+) {
+    if actions.just_pressed(Action::ToggleProjection) && view.mode != MapMode::Globe {
+        view.projection = match view.projection {
+            MapProjection::Equirectangular => MapProjection::Mercator,
+            MapProjection::Mercator => MapProjection::Equirectangular,
+        };
+    }
+}
+
+/// turn cameras on and off and split the window between them
+/// runs every frame so window resizes are picked up
+fn apply_layout(
+    view: Res<MapView>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut globe_camera: Query<&mut Camera, (With<Camera3d>, Without<MapCamera>)>,
+    mut map_camera: Query<(&mut Camera, &mut Projection), With<MapCamera>>,
+) {
+    let (Ok(window), Ok(mut globe), Ok((mut map, mut projection))) =
+        (window.single(), globe_camera.single_mut(), map_camera.single_mut())
+    else {
+        return;
+    };
+
+    let size = window.physical_size();
+    let half = UVec2::new(size.x / 2, size.y);
+    let left = Viewport { physical_position: UVec2::ZERO, physical_size: half, ..default() };
+    let right = Viewport { physical_position: UVec2::new(half.x, 0), physical_size: UVec2::new(size.x - half.x, size.y), ..default() };
+
+    let (globe_layout, map_layout) = match view.mode {
+        MapMode::Globe => ((true, None), (false, None)),
+        MapMode::Split => ((true, Some(left)), (true, Some(right))),
+        MapMode::Map => ((false, None), (true, None)),
+    };
+    set_layout(&mut globe, globe_layout);
+    set_layout(&mut map, map_layout);
+
+    if view.is_changed() {
+        *projection = Projection::Orthographic(map_projection(view.projection));
+    }
+}
+
+/// only write when something changed, cameras recompute their projection on change
+fn set_layout(camera: &mut Mut<Camera>, (active, viewport): (bool, Option<Viewport>)) {
+    let rect = |viewport: &Option<Viewport>| viewport.as_ref().map(|v| (v.physical_position, v.physical_size));
+    if camera.is_active != active || rect(&camera.viewport) != rect(&viewport) {
+        camera.is_active = active;
+        camera.viewport = viewport;
+    }
+}
+
+fn rebuild_plane(
+    view: Res<MapView>,
+    plane: Query<&Mesh2d, With<MapPlane>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !view.is_changed() {
+        return;
+    }
+    if let Ok(plane) = plane.single()
+        && let Some(mesh) = meshes.get_mut(&plane.0)
+    {
+        *mesh = plane_mesh(view.projection);
+    }
+}
+
+/// darken the night side
+fn update_night(
+    view: Res<MapView>,
+    time_state: Res<TimeState>,
+    night: Query<&Mesh2d, With<MapNight>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut last: Local<Option<((f32, f32), MapProjection)>>,
+) {
+    if view.mode == MapMode::Globe {
+        return;
+    }
+
+    // the sun moves a quarter degree a minute, no need to rebuild every frame
+    let subsolar = subsolar_point(time_state.sim_time);
+    if last.is_some_and(|(previous, projection)| {
+        projection == view.projection && (previous.0 - subsolar.0).abs() < 0.05 && (previous.1 - subsolar.1).abs() < 0.05
+    }) {
+        return;
+    }
+    *last = Some((subsolar, view.projection));
+
+    let max_latitude = view.projection.max_latitude();
+    let columns: Vec<(f32, f32, f32)> = (0..=(360.0 / NIGHT_STEP_DEG) as u32)
+        .map(|column| {
+            let longitude = -180.0 + column as f32 * NIGHT_STEP_DEG;
+            let terminator = terminator_latitude(subsolar, longitude).clamp(-max_latitude, max_latitude);
+            // the pole tilted away from the sun is dark
+            let (bottom, top) = if subsolar.0 >= 0.0 { (-max_latitude, terminator) } else { (terminator, max_latitude) };
+            let project = |latitude| view.projection.project(latitude, longitude).y;
+            (longitude, project(bottom), project(top))
+        })
+        .collect();
+
+    if let Ok(night) = night.single()
+        && let Some(mesh) = meshes.get_mut(&night.0)
+    {
+        *mesh = night_mesh(&columns);
+    }
+}
+
+/// satellites, ground tracks, footprints and the terminator
+fn draw(
+    view: Res<MapView>,
+    time_state: Res<TimeState>,
+    selected: Res<SelectedSatellite>,
+    stations: Res<GroundStations>,
+    satellites: Query<(Entity, &Satellite, &SatelliteColor)>,
+    mut gizmos: Gizmos<MapGizmos>,
+) {
+    if view.mode == MapMode::Globe {
+        return;
+    }
+    let projection = view.projection;
+    let now = time_state.sim_time;
+
+    // terminator and the sun
+    let subsolar = subsolar_point(now);
+    let steps = (360.0 / NIGHT_STEP_DEG) as u32;
+    draw_wrapped(
+        &mut gizmos,
+        projection,
+        (0..=steps).map(|step| {
+            let longitude = -180.0 + step as f32 * NIGHT_STEP_DEG;
+            (terminator_latitude(subsolar, longitude), longitude)
+        }),
+        Color::srgba(1.0, 0.85, 0.4, 0.6),
+    );
+    gizmos.circle_2d(projection.project(subsolar.0, subsolar.1), 2.0, Color::srgb(1.0, 0.85, 0.2));
+
+    for station in &stations.0 {
+        let position = projection.project(station.latitude, station.longitude);
+        gizmos.cross_2d(Isometry2d::from_translation(position), ICON_RADIUS * 2.0, STATION_COLOR);
+    }
+
+    let all_tracks = satellites.iter().len() <= MAP_TRACK_LIMIT;
+    for (entity, satellite, color) in &satellites {
+        let (latitude, longitude, altitude) = ground_point(satellite, now);
+        let position = projection.project(latitude, longitude);
+        let is_selected = selected.0 == Some(entity);
+
+        gizmos.circle_2d(position, ICON_RADIUS, color.0).resolution(8);
+        if is_selected {
+            gizmos.circle_2d(position, ICON_RADIUS * 2.5, Color::WHITE).resolution(16);
+        }
+
+        if !(all_tracks || is_selected) || satellite.orbit_duration_m <= 0.0 {
+            continue;
+        }
+
+        // half an orbit behind, dimmed, and one ahead
+        let period_ms = satellite.orbit_duration_m * 60_000.0;
+        let sample = |fraction: f64| {
+            let (latitude, longitude, _) = ground_point(satellite, now + chrono::Duration::milliseconds((period_ms * fraction) as i64));
+            (latitude, longitude)
+        };
+        let behind = MAP_TRACK_SAMPLES / 3;
+        let ahead = MAP_TRACK_SAMPLES - behind;
+        draw_wrapped(&mut gizmos, projection, (0..=behind).map(|i| sample(-0.5 + 0.5 * i as f64 / behind as f64)), color.0.with_alpha(0.3));
+        draw_wrapped(&mut gizmos, projection, (0..=ahead).map(|i| sample(i as f64 / ahead as f64)), color.0.with_alpha(0.8));
+
+        // everywhere the satellite is above the horizon
+        let radius = (EARTH_RADIUS / (EARTH_RADIUS + altitude.max(0.0))).acos().to_degrees();
+        draw_wrapped(
+            &mut gizmos,
+            projection,
+            (0..=FOOTPRINT_SEGMENTS).map(|i| destination(latitude, longitude, radius, 360.0 * i as f32 / FOOTPRINT_SEGMENTS as f32)),
+            color.0.with_alpha(0.5),
+        );
+    }
+}
+
+/// location under the cursor when it's over the map
+/// runs after the globe's cursor system, which leaves None when the cursor isn't over the globe
+fn update_cursor(
+    view: Res<MapView>,
+    mut location: ResMut<CursorLocation>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MapCamera>>,
+    lod: Option<Res<TerrainLod>>,
+) {
+    if view.mode == MapMode::Globe {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (window.single(), camera.single()) else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    if !camera.logical_viewport_rect().is_some_and(|rect| rect.contains(cursor)) {
+        return;
+    }
+
+    let hit = camera
+        .viewport_to_world_2d(camera_transform, cursor)
+        .ok()
+        .and_then(|point| view.projection.unproject(point))
+        .map(|(latitude, longitude)| {
+            let height = lod.as_ref().map_or(0.0, |lod| {
+                let (u, v) = LatLon::from_degrees(latitude, longitude).to_uv();
+                lod.displacement().sample(u, v, HeightFilter::Bilinear) * DISPLACEMENT_SCALE
+            });
+            GeoPoint { latitude, longitude, height }
+        });
+
+    location.0 = hit;
+}
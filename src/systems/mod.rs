@@ -1,5 +1,12 @@
 pub mod earth;
 pub mod satellites;
 pub mod camera;
+pub mod bookmarks;
+pub mod camera_modes;
 pub mod ui;
-pub mod time;
\ No newline at end of file
+pub mod time;
+pub mod settings;
+pub mod map;
+pub mod controls;
+pub mod capture;
+pub mod scenario;
\ No newline at end of file
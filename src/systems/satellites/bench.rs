@@ -0,0 +1,177 @@
+//! bench.rs
+//!
+//! Frame-time benchmark for large constellations
+//! run with `cargo run --release -- --bench` (defaults to 1k/10k/30k objects)
+//! or `--bench 500,5000` for custom counts
+//!
+//! synthetic satellites are cloned from the loaded catalogue with randomised
+//! RAAN and mean anomaly, so they spread over realistic orbits.
+//! orbit lines are not drawn in this mode, only the satellites themselves
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::systems::satellites::{Satellite, spawn_satellite};
+use crate::systems::time::TimeState;
+
+const DEFAULT_COUNTS: [usize; 3] = [1_000, 10_000, 30_000];
+const WARMUP_SECONDS: f64 = 2.0;
+const MEASURE_SECONDS: f64 = 5.0;
+const COLLECT_TIMEOUT_SECONDS: f64 = 60.0; // give up if the catalogue never loads
+
+#[derive(Clone, Copy, Debug)]
+enum Phase {
+    Collect(f64),   // waiting for the real catalogue to use as templates
+    Spawn,
+    Warmup(f64),    // elapsed seconds
+    Measure(f64),
+    Done,
+    Failed,         // exiting with an error
+}
+
+/// one finished benchmark stage
+#[derive(Clone, Copy, Debug)]
+pub struct BenchResult {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Resource)]
+pub struct Benchmark {
+    pub counts: Vec<usize>,
+    pub results: Vec<BenchResult>,
+    stage: usize,
+    phase: Phase,
+    templates: Vec<Satellite>,
+    frame_times: Vec<f64>, // ms
+}
+
+impl Benchmark {
+    pub fn new(counts: Vec<usize>) -> Self {
+        Self {
+            counts,
+            results: Vec::new(),
+            stage: 0,
+            phase: Phase::Collect(0.0),
+            templates: Vec::new(),
+            frame_times: Vec::new(),
+        }
+    }
+
+    /// parse `--bench [counts]` from the command line
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let index = args.iter().position(|arg| arg == "--bench")?;
+
+        let counts = args
+            .get(index + 1)
+            .filter(|arg| !arg.starts_with("--"))
+            .map(|list| list.split(',').filter_map(|n| n.trim().parse().ok()).collect())
+            .filter(|counts: &Vec<usize>| !counts.is_empty())
+            .unwrap_or_else(|| DEFAULT_COUNTS.to_vec());
+
+        Some(Self::new(counts))
+    }
+}
+
+/// benchmark state machine, one step per frame
+pub fn run(
+    mut commands: Commands,
+    mut bench: ResMut<Benchmark>,
+    time: Res<Time<Real>>,
+    time_state: Res<TimeState>,
+    satellites: Query<(Entity, &Satellite)>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let dt = time.delta_secs_f64();
+
+    match bench.phase {
+        Phase::Collect(elapsed) => {
+            if satellites.is_empty() {
+                let elapsed = elapsed + dt;
+                if elapsed > COLLECT_TIMEOUT_SECONDS {
+                    error!("Benchmark: no satellites loaded after {}s to use as templates, check the TLE sources", COLLECT_TIMEOUT_SECONDS);
+                    exit.write(AppExit::error());
+                    bench.phase = Phase::Failed;
+                    return;
+                }
+                bench.phase = Phase::Collect(elapsed);
+                return;
+            }
+            bench.templates = satellites.iter().map(|(_, s)| s.clone()).collect();
+            for (entity, _) in satellites.iter() {
+                commands.entity(entity).despawn();
+            }
+            bench.phase = Phase::Spawn;
+        }
+        Phase::Spawn => {
+            let count = bench.counts[bench.stage];
+            info!("Benchmark: spawning {} satellites...", count);
+
+            let mut rng = rand::rng();
+            for i in 0..count {
+                let template = &bench.templates[i % bench.templates.len()];
+
+                let mut elements = template.elements.clone();
+                elements.right_ascension = rng.random_range(0.0..360.0);
+                elements.mean_anomaly = rng.random_range(0.0..360.0);
+
+                let Some(mut satellite) = Satellite::from_elements(elements) else { continue; };
+                satellite.group = template.group.clone();
+                satellite.generate_orbit_path(128, time_state.sim_time);
+                spawn_satellite(&mut commands, satellite, time_state.sim_time);
+            }
+
+            bench.frame_times.clear();
+            bench.phase = Phase::Warmup(0.0);
+        }
+        Phase::Warmup(elapsed) => {
+            let elapsed = elapsed + dt;
+            bench.phase = if elapsed > WARMUP_SECONDS { Phase::Measure(0.0) } else { Phase::Warmup(elapsed) };
+        }
+        Phase::Measure(elapsed) => {
+            bench.frame_times.push(dt * 1000.0);
+            let elapsed = elapsed + dt;
+            if elapsed < MEASURE_SECONDS {
+                bench.phase = Phase::Measure(elapsed);
+                return;
+            }
+
+            let result = summarize(bench.counts[bench.stage], &mut bench.frame_times);
+            info!(
+                "Benchmark: {} satellites: mean {:.2}ms ({:.0} fps), p95 {:.2}ms, max {:.2}ms",
+                result.count, result.mean_ms, 1000.0 / result.mean_ms, result.p95_ms, result.max_ms
+            );
+            bench.results.push(result);
+
+            for (entity, _) in satellites.iter() {
+                commands.entity(entity).despawn();
+            }
+
+            bench.stage += 1;
+            bench.phase = if bench.stage < bench.counts.len() { Phase::Spawn } else { Phase::Done };
+        }
+        Phase::Done => {
+            info!("Benchmark results:");
+            info!("{:>10} {:>10} {:>10} {:>10}", "count", "mean ms", "p95 ms", "max ms");
+            for r in &bench.results {
+                info!("{:>10} {:>10.2} {:>10.2} {:>10.2}", r.count, r.mean_ms, r.p95_ms, r.max_ms);
+            }
+            exit.write(AppExit::Success);
+        }
+        Phase::Failed => {}
+    }
+}
+
+fn summarize(count: usize, frame_times: &mut [f64]) -> BenchResult {
+    frame_times.sort_by(|a, b| a.total_cmp(b));
+
+    let len = frame_times.len().max(1);
+    let mean_ms = frame_times.iter().sum::<f64>() / len as f64;
+    let p95_ms = frame_times.get((len as f64 * 0.95) as usize).or(frame_times.last()).copied().unwrap_or(0.0);
+    let max_ms = frame_times.last().copied().unwrap_or(0.0);
+
+    BenchResult { count, mean_ms, p95_ms, max_ms }
+}
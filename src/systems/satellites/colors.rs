@@ -0,0 +1,288 @@
+//! colors.rs
+//!
+//! Colour-coding for satellites and their orbits
+//! schemes are cycled at runtime with C, a legend in the bottom right explains the current one
+
+use bevy::prelude::*;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use crate::systems::satellites::Satellite;
+use crate::systems::satellites::instancing::SatelliteColor;
+use crate::systems::satellites::regime::{MeanOrbit, OrbitRegime};
+use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+
+/// palette for catalogue groups, indexed by sorted group name
+const GROUP_PALETTE: [Color; 8] = [
+    Color::srgb(0.30, 0.70, 1.00),
+    Color::srgb(1.00, 0.60, 0.20),
+    Color::srgb(0.40, 0.90, 0.40),
+    Color::srgb(0.95, 0.35, 0.45),
+    Color::srgb(0.75, 0.50, 1.00),
+    Color::srgb(1.00, 0.90, 0.30),
+    Color::srgb(0.30, 0.95, 0.85),
+    Color::srgb(0.90, 0.90, 0.90),
+];
+
+// gradient ranges
+const ALTITUDE_RANGE_KM: (f64, f64) = (200.0, 40000.0); // log scale
+const MAX_INCLINATION_DEG: f64 = 180.0;
+const MAX_TLE_AGE_DAYS: f64 = 30.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    #[default]
+    Regime,
+    Group,
+    Altitude,
+    Inclination,
+    TleAge,
+}
+
+impl ColorScheme {
+    pub fn next(self) -> Self {
+        match self {
+            ColorScheme::Regime => ColorScheme::Group,
+            ColorScheme::Group => ColorScheme::Altitude,
+            ColorScheme::Altitude => ColorScheme::Inclination,
+            ColorScheme::Inclination => ColorScheme::TleAge,
+            ColorScheme::TleAge => ColorScheme::Regime,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorScheme::Regime => "Orbit regime",
+            ColorScheme::Group => "Catalogue group",
+            ColorScheme::Altitude => "Mean altitude",
+            ColorScheme::Inclination => "Inclination",
+            ColorScheme::TleAge => "TLE age",
+        }
+    }
+}
+
+/// active colour scheme
+#[derive(Resource, Default)]
+pub struct OrbitColoring {
+    pub scheme: ColorScheme,
+}
+
+/// sorted, deduplicated group names in the scene, rebuilt only when satellites come or go
+#[derive(Resource, Default, PartialEq)]
+pub struct SatelliteGroups(pub Vec<String>);
+
+/// legend panel container
+#[derive(Component)]
+pub struct Legend;
+
+/// the colour a satellite should be drawn with
+/// `groups` is the sorted list of every group in the scene
+pub fn satellite_color(
+    satellite: &Satellite,
+    scheme: ColorScheme,
+    groups: &[String],
+    time: DateTime<Utc>,
+) -> Color {
+    match scheme {
+        ColorScheme::Regime => regime_color(satellite.regime),
+        ColorScheme::Group => {
+            let index = groups.iter().position(|g| *g == satellite.group).unwrap_or(0);
+            GROUP_PALETTE[index % GROUP_PALETTE.len()]
+        }
+        ColorScheme::Altitude => {
+            let altitude = MeanOrbit::from_elements(&satellite.elements).mean_altitude();
+            altitude_color(altitude)
+        }
+        ColorScheme::Inclination => inclination_color(satellite.inclination()),
+        ColorScheme::TleAge => tle_age_color(satellite.tle_age_days(time).abs()),
+    }
+}
+
+pub fn regime_color(regime: OrbitRegime) -> Color {
+    match regime {
+        OrbitRegime::Leo => Color::srgb(0.30, 0.70, 1.00),
+        OrbitRegime::Sso => Color::srgb(0.40, 0.95, 0.60),
+        OrbitRegime::Meo => Color::srgb(1.00, 0.80, 0.25),
+        OrbitRegime::Geo => Color::srgb(1.00, 0.40, 0.30),
+        OrbitRegime::Heo => Color::srgb(0.80, 0.50, 1.00),
+        OrbitRegime::Molniya => Color::srgb(1.00, 0.45, 0.85),
+        OrbitRegime::Decaying => Color::srgb(0.55, 0.55, 0.55),
+    }
+}
+
+/// blue (low) to red (high), log scale
+fn altitude_color(altitude_km: f64) -> Color {
+    let (min, max) = ALTITUDE_RANGE_KM;
+    let t = (altitude_km.max(min).ln() - min.ln()) / (max.ln() - min.ln());
+    gradient(t)
+}
+
+fn inclination_color(inclination_deg: f64) -> Color {
+    gradient(inclination_deg / MAX_INCLINATION_DEG)
+}
+
+/// green (fresh) to red (stale), in whole days
+/// a smooth ramp would change every satellite's colour (and rebuild the orbit mesh) every frame
+fn tle_age_color(age_days: f64) -> Color {
+    let t = (age_days.floor() / MAX_TLE_AGE_DAYS).clamp(0.0, 1.0) as f32;
+    Color::hsl(120.0 * (1.0 - t), 0.85, 0.55)
+}
+
+/// hue sweep from blue to red, t in 0..1
+fn gradient(t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0) as f32;
+    Color::hsl(240.0 * (1.0 - t), 0.85, 0.55)
+}
+
+/// sorted, deduplicated group names
+fn collect_groups<'a>(satellites: impl Iterator<Item = &'a Satellite>) -> Vec<String> {
+    let mut groups: Vec<String> = satellites.map(|s| s.group.clone()).collect();
+    groups.sort();
+    groups.dedup();
+    groups
+}
+
+/// cycle colour schemes with C
+pub fn cycle_scheme(
+    actions: Res<Actions>,
+    mut coloring: ResMut<OrbitColoring>,
+) {
+    if actions.just_pressed(Action::CycleColors) {
+        coloring.scheme = coloring.scheme.next();
+        info!("Colour scheme: {}", coloring.scheme.label());
+    }
+}
+
+/// keep `SatelliteGroups` up to date, only marked changed when the list itself changes
+pub fn update_groups(
+    mut groups: ResMut<SatelliteGroups>,
+    satellites: Query<&Satellite>,
+    added: Query<(), Added<Satellite>>,
+    mut removed: RemovedComponents<Satellite>,
+) {
+    let any_removed = removed.read().count() > 0;
+    if added.is_empty() && !any_removed {
+        return;
+    }
+    groups.set_if_neq(SatelliteGroups(collect_groups(satellites.iter())));
+}
+
+/// write scheme colours into each satellite's `SatelliteColor`
+/// only runs when the scheme, the satellites or their groups change, or (for TLE age) the sim day
+/// ages are taken at the start of the sim day, so they all step together at midnight
+/// orbit lines pick the colour up from there, see `orbits.rs`
+pub fn apply_colors(
+    coloring: Res<OrbitColoring>,
+    groups: Res<SatelliteGroups>,
+    time_state: Res<TimeState>,
+    mut colored_day: Local<Option<NaiveDate>>, // sim day the TLE age colours were last worked out for
+    added: Query<(), Added<Satellite>>,
+    mut satellites: Query<(&Satellite, &mut SatelliteColor)>,
+) {
+    let day = time_state.sim_time.date_naive();
+    let day_changed = coloring.scheme == ColorScheme::TleAge && *colored_day != Some(day);
+    if !coloring.is_changed() && !groups.is_changed() && added.is_empty() && !day_changed {
+        return;
+    }
+    *colored_day = Some(day);
+
+    let time = day.and_time(NaiveTime::MIN).and_utc();
+    for (satellite, mut satellite_color_component) in satellites.iter_mut() {
+        let color = satellite_color(satellite, coloring.scheme, &groups.0, time);
+        satellite_color_component.set_if_neq(SatelliteColor(color));
+    }
+}
+
+/// spawn the (empty) legend panel
+pub fn setup_legend(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.0),
+            bottom: Val::Px(20.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            row_gap: Val::Px(3.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        BorderRadius::all(Val::Px(4.0)),
+        Legend,
+    ));
+}
+
+/// rebuild legend rows when the scheme or the set of groups changes
+pub fn update_legend(
+    mut commands: Commands,
+    coloring: Res<OrbitColoring>,
+    groups: Res<SatelliteGroups>,
+    legend: Query<Entity, With<Legend>>,
+) {
+    if !coloring.is_changed() && !groups.is_changed() {
+        return;
+    }
+    let Ok(legend) = legend.single() else { return; };
+
+    let entries = legend_entries(coloring.scheme, &groups.0);
+
+    commands.entity(legend).despawn_related::<Children>();
+    commands.entity(legend).with_children(|parent| {
+        parent.spawn((
+            Text::new(format!("{} (C)", coloring.scheme.label())),
+            TextFont { font_size: 12.0, ..default() },
+            TextColor(Color::WHITE),
+        ));
+
+        for (label, color) in entries {
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        Node {
+                            width: Val::Px(10.0),
+                            height: Val::Px(10.0),
+                            ..default()
+                        },
+                        BackgroundColor(color),
+                    ));
+                    row.spawn((
+                        Text::new(label),
+                        TextFont { font_size: 10.0, ..default() },
+                        TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                    ));
+                });
+        }
+    });
+}
+
+/// label/colour pairs shown in the legend
+fn legend_entries(scheme: ColorScheme, groups: &[String]) -> Vec<(String, Color)> {
+    match scheme {
+        ColorScheme::Regime => OrbitRegime::ALL
+            .iter()
+            .map(|regime| (regime.label().to_string(), regime_color(*regime)))
+            .collect(),
+        ColorScheme::Group => groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| (group.clone(), GROUP_PALETTE[i % GROUP_PALETTE.len()]))
+            .collect(),
+        ColorScheme::Altitude => [200.0, 1000.0, 5000.0, 20000.0, 40000.0]
+            .iter()
+            .map(|&alt| (format!("{alt:.0} km"), altitude_color(alt)))
+            .collect(),
+        ColorScheme::Inclination => [0.0, 45.0, 90.0, 135.0, 180.0]
+            .iter()
+            .map(|&inc| (format!("{inc:.0}°"), inclination_color(inc)))
+            .collect(),
+        ColorScheme::TleAge => [0.0, 7.0, 15.0, 30.0]
+            .iter()
+            .map(|&age| (format!("{age:.0}+ days"), tle_age_color(age)))
+            .collect(),
+    }
+}
@@ -0,0 +1,333 @@
+//! instancing.rs
+//!
+//! GPU-instanced satellite renderer
+//! every satellite is drawn from one shared sphere mesh in a single draw call,
+//! with per-instance position/size/colour streamed in a vertex buffer each frame
+//!
+//! based on Bevy's custom_shader_instancing example
+//! https://github.com/bevyengine/bevy/blob/v0.16.1/examples/shader/custom_shader_instancing.rs
+
+use bevy::core_pipeline::core_3d::Transparent3d;
+use bevy::ecs::query::QueryItem;
+use bevy::ecs::system::{lifetimeless::*, SystemParamItem};
+use bevy::pbr::{MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup};
+use bevy::prelude::*;
+use bevy::render::extract_component::{ExtractComponent, ExtractComponentPlugin};
+use bevy::render::mesh::allocator::MeshAllocator;
+use bevy::render::mesh::{MeshVertexBufferLayoutRef, RenderMesh, RenderMeshBufferInfo};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_phase::{
+    AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+    RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+};
+use bevy::render::render_resource::*;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::sync_world::MainEntity;
+use bevy::render::view::{ExtractedView, NoFrustumCulling};
+use bevy::render::{Render, RenderApp, RenderSet};
+use bytemuck::{Pod, Zeroable};
+
+use crate::systems::satellites::Satellite;
+
+const SHADER_ASSET_PATH: &str = "shaders/satellites.wgsl";
+const SATELLITE_RADIUS: f32 = 10.0; // km, minimum on-screen size is handled in the shader
+
+pub struct SatelliteInstancingPlugin;
+
+impl Plugin for SatelliteInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<SatelliteInstances>::default())
+            .add_systems(Startup, setup)
+            .add_systems(PostUpdate, sync_instances);
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Transparent3d, DrawSatellites>()
+            .init_resource::<SpecializedMeshPipelines<SatellitePipeline>>()
+            .add_systems(
+                Render,
+                (
+                    queue_satellites.in_set(RenderSet::QueueMeshes),
+                    prepare_instance_buffers.in_set(RenderSet::PrepareResources),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp).init_resource::<SatellitePipeline>();
+    }
+}
+
+/// per-satellite display colour, written by `colors::apply_colors`
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct SatelliteColor(pub Color);
+
+impl Default for SatelliteColor {
+    fn default() -> Self {
+        Self(Color::WHITE)
+    }
+}
+
+/// per-instance GPU data, layout must match `satellites.wgsl`
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct InstanceData {
+    pub position: Vec3,
+    pub scale: f32,
+    pub color: [f32; 4],
+}
+
+/// carrier entity holding every satellite instance
+#[derive(Component, Default)]
+pub struct SatelliteInstances(pub Vec<InstanceData>);
+
+impl ExtractComponent for SatelliteInstances {
+    type QueryData = &'static SatelliteInstances;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(SatelliteInstances(item.0.clone()))
+    }
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.spawn((
+        Mesh3d(meshes.add(Sphere::new(1.0).mesh().ico(2).unwrap())),
+        Transform::default(),
+        SatelliteInstances::default(),
+        // instances are spread over the whole scene, the carrier's Aabb means nothing
+        NoFrustumCulling,
+    ));
+}
+
+/// gather satellite transforms into the instance buffer
+/// runs in PostUpdate so it sees this frame's positions
+fn sync_instances(
+    satellites: Query<(&Transform, &SatelliteColor, &Visibility), With<Satellite>>,
+    mut instances: Query<&mut SatelliteInstances>,
+) {
+    let Ok(mut instances) = instances.single_mut() else { return; };
+
+    instances.0.clear();
+    instances.0.extend(
+        satellites
+            .iter()
+            .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+            .map(|(transform, color, _)| InstanceData {
+                position: transform.translation,
+                scale: SATELLITE_RADIUS * transform.scale.x,
+                color: LinearRgba::from(color.0).to_f32_array(),
+            }),
+    );
+}
+
+// RENDER WORLD
+
+#[allow(clippy::too_many_arguments)]
+fn queue_satellites(
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    satellite_pipeline: Res<SatellitePipeline>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<SatellitePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<RenderMesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    carriers: Query<(Entity, &MainEntity), With<SatelliteInstances>>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+    views: Query<(&ExtractedView, &Msaa)>,
+) {
+    let draw_satellites = transparent_3d_draw_functions.read().id::<DrawSatellites>();
+
+    for (view, msaa) in &views {
+        let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity) else {
+            continue;
+        };
+
+        let view_key = MeshPipelineKey::from_msaa_samples(msaa.samples()) | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+
+        for (entity, main_entity) in &carriers {
+            let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(*main_entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+            let Ok(pipeline) = pipelines.specialize(&pipeline_cache, &satellite_pipeline, key, &mesh.layout) else {
+                continue;
+            };
+
+            transparent_phase.add(Transparent3d {
+                entity: (entity, *main_entity),
+                pipeline,
+                draw_function: draw_satellites,
+                distance: rangefinder.distance_translation(&mesh_instance.translation),
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::None,
+                indexed: true,
+            });
+        }
+    }
+}
+
+/// kept on the render entity between frames, rewritten in place
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    capacity: usize, // instances it has room for
+    length: usize,
+}
+
+fn prepare_instance_buffers(
+    mut commands: Commands,
+    mut query: Query<(Entity, &SatelliteInstances, Option<&mut InstanceBuffer>)>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (entity, instances, instance_buffer) in &mut query {
+        let length = instances.0.len();
+        let contents: &[u8] = bytemuck::cast_slice(instances.0.as_slice());
+
+        match instance_buffer {
+            Some(mut instance_buffer) if instance_buffer.capacity >= length => {
+                if length > 0 {
+                    render_queue.write_buffer(&instance_buffer.buffer, 0, contents);
+                }
+                instance_buffer.length = length;
+            }
+            // first frame, or more satellites than fit: a new buffer with room to spare
+            _ => {
+                let capacity = length.next_power_of_two().max(64);
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("satellite instance buffer"),
+                    size: (capacity * size_of::<InstanceData>()) as u64,
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                if length > 0 {
+                    render_queue.write_buffer(&buffer, 0, contents);
+                }
+                commands.entity(entity).insert(InstanceBuffer { buffer, capacity, length });
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SatellitePipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for SatellitePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let mesh_pipeline = world.resource::<MeshPipeline>();
+
+        SatellitePipeline {
+            shader: world.load_asset(SHADER_ASSET_PATH),
+            mesh_pipeline: mesh_pipeline.clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for SatellitePipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayoutRef,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = self.shader.clone();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: size_of::<InstanceData>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 3, // 0-2 are position, normal and uv
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 4,
+                },
+            ],
+        });
+        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+        Ok(descriptor)
+    }
+}
+
+type DrawSatellites = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
+    type Param = (
+        SRes<RenderAssets<RenderMesh>>,
+        SRes<RenderMeshInstances>,
+        SRes<MeshAllocator>,
+    );
+    type ViewQuery = ();
+    type ItemQuery = Read<InstanceBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        instance_buffer: Option<&'w InstanceBuffer>,
+        (meshes, render_mesh_instances, mesh_allocator): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        // borrow check workaround
+        let mesh_allocator = mesh_allocator.into_inner();
+
+        let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(item.main_entity()) else {
+            return RenderCommandResult::Skip;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Skip;
+        };
+        let Some(instance_buffer) = instance_buffer else {
+            return RenderCommandResult::Skip;
+        };
+        if instance_buffer.length == 0 {
+            return RenderCommandResult::Skip;
+        }
+        let Some(vertex_buffer_slice) = mesh_allocator.mesh_vertex_slice(&mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Skip;
+        };
+
+        pass.set_vertex_buffer(0, vertex_buffer_slice.buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            RenderMeshBufferInfo::Indexed { index_format, count } => {
+                let Some(index_buffer_slice) = mesh_allocator.mesh_index_slice(&mesh_instance.mesh_asset_id) else {
+                    return RenderCommandResult::Skip;
+                };
+
+                pass.set_index_buffer(index_buffer_slice.buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(
+                    index_buffer_slice.range.start..(index_buffer_slice.range.start + count),
+                    vertex_buffer_slice.range.start as i32,
+                    0..instance_buffer.length as u32,
+                );
+            }
+            RenderMeshBufferInfo::NonIndexed => {
+                pass.draw(vertex_buffer_slice.range, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
@@ -4,16 +4,26 @@
 //! 
 //! I couldn't figure out text panels in 3D space, so these are all implemented within the UI layer
 //! It's a bunch of text boxes that track satellites positions using projection magic
+//! nearer (and selected) satellites get first pick of screen space, labels that would overlap them are hidden
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use bevy::prelude::*;
+use bevy::math::Affine3A;
 use bevy::render::camera::Camera;
-use bevy::window::Window;
+use chrono::{DateTime, Utc};
 
-use crate::systems::satellites::Satellite;
+use crate::systems::earth::Earth;
+use crate::systems::earth::heightmap::{HeightFilter, Heightmap};
+use crate::systems::earth::lod::TerrainLod;
+use crate::systems::earth::uv::LatLon;
+use crate::systems::satellites::{Satellite, SelectedSatellite};
+use crate::systems::settings::RenderSettings;
 use crate::systems::time::TimeState;
-use crate::config::EARTH_RADIUS;
+use crate::config::{DISPLACEMENT_SCALE, EARTH_POLAR_RADIUS, EARTH_RADIUS, LABEL_BRIEF_DISTANCE, LABEL_FULL_DISTANCE, LABEL_GRID_CELL, LABEL_REFRESH_SECONDS};
+
+const OCCLUSION_STEPS: usize = 48; // heightmap samples along the part of a line that passes through the terrain
 
 /// full ui screen container component
 #[derive(Component)]
@@ -23,7 +33,35 @@ pub struct LabelContainer;
 #[derive(Component)]
 pub struct SatelliteLabel {
     pub satellite_entity: Entity,
-    // add more attributes here (size, offset, etc)
+    pub detail: LabelDetail,
+}
+
+/// how much a label says, more as the camera gets closer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelDetail {
+    Name,
+    Brief, // name and altitude
+    Full,  // plus speed, inclination and period
+}
+
+impl LabelDetail {
+    fn at_distance(distance: f32) -> Self {
+        if distance < LABEL_FULL_DISTANCE {
+            LabelDetail::Full
+        } else if distance < LABEL_BRIEF_DISTANCE {
+            LabelDetail::Brief
+        } else {
+            LabelDetail::Name
+        }
+    }
+
+    fn lines(self) -> usize {
+        match self {
+            LabelDetail::Name => 1,
+            LabelDetail::Brief => 2,
+            LabelDetail::Full => 5,
+        }
+    }
 }
 
 /// setup UI overlay
@@ -41,59 +79,108 @@ pub fn setup(mut commands: Commands) {
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn update(
     mut commands: Commands,
     satellites: Query<(Entity, &Transform, &Satellite)>,
-    camera: Query<(&Camera, &Transform)>,
-    mut labels: Query<(Entity, &mut Node, &mut Visibility, &SatelliteLabel)>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    earth: Query<&GlobalTransform, With<Earth>>,
+    lod: Option<Res<TerrainLod>>,
+    ui_scale: Res<UiScale>,
+    mut labels: Query<(Entity, &mut Node, &mut Visibility, &mut TextFont, &mut Text, &mut SatelliteLabel)>,
     container: Query<Entity, With<LabelContainer>>,
-    window: Query<&Window>,
     time_state: Res<TimeState>, // use simulation time
+    settings: Res<RenderSettings>,
+    selected: Res<SelectedSatellite>,
+    time: Res<Time>,
+    mut refresh_timer: Local<f32>,
 ) {
-    let (Ok(window), Ok((camera, cam_transform)), Ok(container)) = 
-        (window.single(), camera.single(), container.single()) else { return; };
+    let (Ok((camera, cam_transform)), Ok(earth), Ok(container)) = 
+        (camera.single(), earth.single(), container.single()) else { return; };
+    let cam_pos = cam_transform.translation();
+    let occluder = Occluder::earth(earth, lod.map(|lod| lod.displacement()));
 
-    // map existing labels by satellite entity
-    let existing_labels: HashMap<Entity, Entity> = labels.iter()
-        .map(|(label_entity, _, _, sat_label)| (sat_label.satellite_entity, label_entity))
-        .collect();
+    // rewriting text relayouts it, so values are only refreshed a few times a second
+    *refresh_timer -= time.delta_secs();
+    let refresh = *refresh_timer <= 0.0;
+    if refresh {
+        *refresh_timer = LABEL_REFRESH_SECONDS;
+    }
+
+    // map existing labels by satellite entity, dropping labels whose satellite is gone
+    let mut existing_labels: HashMap<Entity, Entity> = HashMap::new();
+    for (label_entity, _, _, _, _, sat_label) in labels.iter() {
+        if satellites.contains(sat_label.satellite_entity) {
+            existing_labels.insert(sat_label.satellite_entity, label_entity);
+        } else {
+            commands.entity(label_entity).despawn();
+        }
+    }
+
+    // resize existing labels when the size setting moves
+    if settings.is_changed() {
+        for (_, _, _, mut font, _, _) in labels.iter_mut() {
+            if font.font_size != settings.label_size {
+                font.font_size = settings.label_size;
+            }
+        }
+    }
 
-    // process each satellite
-    for (sat_entity, sat_transform, satellite) in satellites.iter() {
-        let sat_pos = sat_transform.translation;
+    // satellites that could have a label: on screen and not behind the earth
+    // the globe may share the window with the map, or be switched off entirely
+    let mut candidates: Vec<(Entity, Vec2, f32)> = if settings.labels && camera.is_active {
+        satellites
+            .iter()
+            .filter_map(|(sat_entity, sat_transform, _)| {
+                let sat_pos = sat_transform.translation;
+                let screen_pos = world_to_screen(sat_pos, camera, cam_transform, ui_scale.0)?;
+                occluder.is_visible(sat_pos, cam_pos).then(|| (sat_entity, screen_pos, sat_pos.distance(cam_pos)))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-        // check visibility, get screen position
-        let visible = is_visible(sat_pos, cam_transform.translation, Vec3::ZERO, EARTH_RADIUS);
-        let screen_pos = world_to_screen(sat_pos, camera, cam_transform, window.width(), window.height());
+    // the selected satellite always wins, then nearest first
+    candidates.sort_by(|a, b| {
+        (selected.0 != Some(a.0))
+            .cmp(&(selected.0 != Some(b.0)))
+            .then(a.2.total_cmp(&b.2))
+    });
 
-        let should_show = visible && screen_pos.is_some();
+    // place labels that don't overlap one already placed
+    let mut grid = LabelGrid::default();
+    let mut shown = HashSet::new();
+    for (sat_entity, pos, distance) in candidates {
+        let Ok((_, _, satellite)) = satellites.get(sat_entity) else { continue; };
+        let detail = LabelDetail::at_distance(distance);
+        if !grid.try_place(Rect::from_corners(pos, pos + label_size(satellite, detail, settings.label_size))) {
+            continue;
+        }
+        shown.insert(sat_entity);
 
         if let Some(&label_entity) = existing_labels.get(&sat_entity) {
             // update existing label
-            if let Ok((_, mut node, mut visibility, _)) = labels.get_mut(label_entity) {
-                if should_show {
-                    let pos = screen_pos.unwrap(); // known Some
-                    *visibility = Visibility::Inherited;
-                    node.left = Val::Px(pos.x);
-                    node.top = Val::Px(pos.y);
-                } else {
-                    *visibility = Visibility::Hidden;
+            if let Ok((_, mut node, mut visibility, _, mut text, mut sat_label)) = labels.get_mut(label_entity) {
+                let was_hidden = *visibility == Visibility::Hidden;
+                visibility.set_if_neq(Visibility::Inherited);
+                node.left = Val::Px(pos.x);
+                node.top = Val::Px(pos.y);
+
+                if refresh || was_hidden || sat_label.detail != detail {
+                    let label_text = label_text(satellite, detail, time_state.sim_time);
+                    if text.0 != label_text {
+                        text.0 = label_text;
+                    }
+                    sat_label.detail = detail;
                 }
             }
-        } else if should_show {
+        } else {
             // create new label
-            let pos = screen_pos.unwrap(); // known Some
-            let (_, _, altitude) = satellite.geodetic_position(time_state.sim_time);
-            
-            let label_text = format!("{}\nAlt: {:.0}km", 
-                satellite.name(), 
-                altitude
-            );
-
             commands.entity(container).with_children(|parent| {
                 parent.spawn((
-                    Text::new(label_text),
-                    TextFont { font_size: 8.0, ..default() },
+                    Text::new(label_text(satellite, detail, time_state.sim_time)),
+                    TextFont { font_size: settings.label_size, ..default() },
                     TextColor(Color::WHITE),
                     Node {
                         position_type: PositionType::Absolute,
@@ -102,51 +189,242 @@ pub fn update(
                         ..default()
                     },
                     BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)), // textbox background
-                    SatelliteLabel { satellite_entity: sat_entity },
+                    SatelliteLabel { satellite_entity: sat_entity, detail },
                 ));
             });
         }
     }
+
+    // everything that lost out or went out of view
+    for (_, _, mut visibility, _, _, sat_label) in labels.iter_mut() {
+        if !shown.contains(&sat_label.satellite_entity) {
+            visibility.set_if_neq(Visibility::Hidden);
+        }
+    }
+}
+
+/// label contents at a level of detail
+fn label_text(satellite: &Satellite, detail: LabelDetail, time: DateTime<Utc>) -> String {
+    let (_, _, altitude) = satellite.geodetic_position(time);
+    match detail {
+        LabelDetail::Name => satellite.name().to_string(),
+        LabelDetail::Brief => format!("{}\nAlt: {:.0}km", satellite.name(), altitude),
+        LabelDetail::Full => format!("{}\nAlt: {:.0}km\nVel: {:.2}km/s\nInc: {:.1}°\nPer: {:.1}min",
+            satellite.name(),
+            altitude,
+            satellite.get_velocity(time).length(),
+            satellite.inclination(),
+            satellite.orbit_duration_m,
+        ),
+    }
+}
+
+/// rough on-screen size of a label, good enough to keep them apart
+/// the real size is only known after layout, which would be a frame late
+fn label_size(satellite: &Satellite, detail: LabelDetail, font_size: f32) -> Vec2 {
+    const CHAR_WIDTH: f32 = 0.6; // of the font size
+    const LINE_HEIGHT: f32 = 1.2;
+    const VALUE_CHARS: usize = 14; // longest value line, "Vel: 7.66km/s"
+
+    let chars = if detail == LabelDetail::Name { satellite.name().len() } else { satellite.name().len().max(VALUE_CHARS) };
+    Vec2::new(chars as f32 * CHAR_WIDTH, detail.lines() as f32 * LINE_HEIGHT) * font_size
+}
+
+/// screen cells covered by labels so far
+#[derive(Default)]
+struct LabelGrid(HashSet<(i32, i32)>);
+
+impl LabelGrid {
+    /// claim the cells under a rect, false if any are already taken
+    fn try_place(&mut self, rect: Rect) -> bool {
+        let min = (rect.min / LABEL_GRID_CELL).floor().as_ivec2();
+        let max = (rect.max / LABEL_GRID_CELL).ceil().as_ivec2();
+        let cells = (min.x..max.x).flat_map(|x| (min.y..max.y).map(move |y| (x, y)));
+
+        if cells.clone().any(|cell| self.0.contains(&cell)) {
+            return false;
+        }
+        self.0.extend(cells);
+        true
+    }
 }
 
 // UTILS
 
-/// convert world coordinates to screen coordinates
+/// convert world coordinates to UI coordinates, None when outside the camera's viewport
+/// viewport coordinates are logical pixels (the window scale factor is already divided out),
+/// UI nodes are additionally scaled by `UiScale`
 fn world_to_screen(
     world_pos: Vec3,
     camera: &Camera,
-    camera_transform: &Transform,
-    screen_width: f32,
-    screen_height: f32,
+    camera_transform: &GlobalTransform,
+    ui_scale: f32,
 ) -> Option<Vec2> {
-    let view_matrix = camera_transform.compute_matrix().inverse();
-    let view_projection = camera.clip_from_view() * view_matrix;
-
-    // transform to clip space
-    let clip_pos = view_projection * Vec4::new(world_pos.x, world_pos.y, world_pos.z, 1.0);
-    
-    if clip_pos.w <= 0.0 { return None; } // behind camera
-
-    // convert to NDC and check bounds
-    let ndc = clip_pos.xyz() / clip_pos.w;
-    if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 { return None; } // offscreen
-
-    // NDC to screen coordinates
-    Some(Vec2::new(
-        (ndc.x + 1.0) * 0.5 * screen_width,
-        (1.0 - ndc.y) * 0.5 * screen_height, // Y is flipped in screen space
-    ))
+    let viewport = camera.logical_viewport_rect()?;
+    let screen_pos = camera.world_to_viewport(camera_transform, world_pos).ok()?; // fails behind the camera
+    viewport.contains(screen_pos).then_some(screen_pos / ui_scale)
+}
+
+/// what can hide a satellite from the camera: the earth's ellipsoid and the terrain on it
+pub struct Occluder {
+    pub to_local: Affine3A, // world to the earth's frame, y is the polar axis
+    pub equatorial_radius: f32,
+    pub polar_radius: f32,
+    pub terrain: Option<Arc<Heightmap>>, // heights on top of the ellipsoid, scaled by DISPLACEMENT_SCALE
+}
+
+impl Occluder {
+    /// the earth as rendered, terrain is only included once the heightmap has loaded
+    pub fn earth(earth: &GlobalTransform, terrain: Option<Arc<Heightmap>>) -> Self {
+        Self {
+            to_local: earth.affine().inverse(),
+            equatorial_radius: EARTH_RADIUS,
+            polar_radius: EARTH_POLAR_RADIUS,
+            terrain,
+        }
+    }
+
+    /// check if a point is visible from the camera (the straight line between them is unblocked)
+    pub fn is_visible(&self, point: Vec3, cam_pos: Vec3) -> bool {
+        let origin = self.to_local.transform_point3(cam_pos);
+        let delta = self.to_local.transform_point3(point) - origin;
+        let (a, b) = (self.equatorial_radius, self.polar_radius);
+
+        // segment parameters are 0 at the camera and 1 at the point
+        let crosses = |(enter, exit): (f32, f32)| enter < 1.0 && exit > 0.0;
+
+        // the bare ellipsoid always blocks
+        if segment_ellipsoid(origin, delta, a, b).is_some_and(crosses) {
+            return false;
+        }
+
+        // then the shell the terrain can reach into, marched against the heightmap
+        let Some(terrain) = &self.terrain else { return true; };
+        let Some((enter, exit)) = segment_ellipsoid(origin, delta, a + DISPLACEMENT_SCALE, b + DISPLACEMENT_SCALE)
+            .filter(|&interval| crosses(interval))
+        else {
+            return true;
+        };
+
+        let (start, end) = (enter.max(0.0), exit.min(1.0));
+        (0..=OCCLUSION_STEPS).all(|step| {
+            let sample = origin + delta * (start + (end - start) * step as f32 / OCCLUSION_STEPS as f32);
+            let (u, v) = LatLon::from(sample).to_uv();
+            let height = terrain.sample(u, v, HeightFilter::Bilinear) * DISPLACEMENT_SCALE;
+            sample.length() > ellipsoid_radius(sample, a, b) + height
+        })
+    }
 }
 
-/// check if satellite is visible from camera (unblocked by earth)
-/// simple ray-sphere intersection test, tbf
-fn is_visible(sat_pos: Vec3, cam_pos: Vec3, earth_center: Vec3, earth_radius: f32) -> bool {
-    let cam_to_sat = sat_pos - cam_pos;
-    let cam_to_earth = earth_center - cam_pos;
-    
-    let projection = cam_to_earth.dot(cam_to_sat.normalize());
-    if projection < 0.0 || projection > cam_to_sat.length() { return true; }
-
-    let closest_point = cam_pos + cam_to_sat.normalize() * projection;
-    (closest_point - earth_center).length() > earth_radius
-}
\ No newline at end of file
+/// where the segment `origin + delta * t` enters and leaves an ellipsoid around the origin
+/// with polar axis y, None if the line misses it
+fn segment_ellipsoid(origin: Vec3, delta: Vec3, equatorial_radius: f32, polar_radius: f32) -> Option<(f32, f32)> {
+    // stretch y so the ellipsoid becomes a sphere, t is unchanged by the scaling
+    let stretch = Vec3::new(1.0, equatorial_radius / polar_radius, 1.0);
+    let (origin, delta) = (origin * stretch, delta * stretch);
+
+    let a = delta.length_squared();
+    let b = origin.dot(delta);
+    let c = origin.length_squared() - equatorial_radius * equatorial_radius;
+    let discriminant = b * b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    Some(((-b - root) / a, (-b + root) / a))
+}
+
+/// distance from the centre to the ellipsoid surface in the direction of a point
+fn ellipsoid_radius(direction: Vec3, equatorial_radius: f32, polar_radius: f32) -> f32 {
+    let d = direction.normalize();
+    1.0 / ((d.x * d.x + d.z * d.z) / (equatorial_radius * equatorial_radius) + d.y * d.y / (polar_radius * polar_radius)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    fn sphere() -> Occluder {
+        Occluder {
+            to_local: Affine3A::IDENTITY,
+            equatorial_radius: EARTH_RADIUS,
+            polar_radius: EARTH_RADIUS,
+            terrain: None,
+        }
+    }
+
+    /// heightmap at the same normalised height everywhere
+    fn uniform_terrain(height: f32) -> Arc<Heightmap> {
+        let image = Image::new_fill(
+            Extent3d::default(),
+            TextureDimension::D2,
+            &height.to_le_bytes(),
+            TextureFormat::R32Float,
+            RenderAssetUsages::default(),
+        );
+        Arc::new(Heightmap::from_image(&image).unwrap())
+    }
+
+    #[test]
+    fn earth_blocks_the_far_side() {
+        let occluder = sphere();
+        let camera = Vec3::new(0.0, 0.0, 20000.0);
+
+        assert!(occluder.is_visible(Vec3::new(0.0, 0.0, 7000.0), camera)); // in front
+        assert!(!occluder.is_visible(Vec3::new(0.0, 0.0, -7000.0), camera)); // directly behind
+        assert!(occluder.is_visible(Vec3::new(0.0, 12000.0, -7000.0), camera)); // behind but clear of the limb
+        assert!(occluder.is_visible(Vec3::new(0.0, 0.0, 30000.0), camera)); // behind the camera, not the earth
+    }
+
+    #[test]
+    fn polar_flattening_uncovers_points_past_the_pole() {
+        // a line grazing the north pole between the polar and equatorial radii
+        let camera = Vec3::new(-20000.0, 6370.0, 0.0);
+        let satellite = Vec3::new(20000.0, 6370.0, 0.0);
+
+        assert!(!sphere().is_visible(satellite, camera));
+        let ellipsoid = Occluder { polar_radius: 6356.752, ..sphere() };
+        assert!(ellipsoid.is_visible(satellite, camera));
+    }
+
+    #[test]
+    fn terrain_blocks_grazing_lines() {
+        // passes 40km over the equator, below terrain at 60km
+        let camera = Vec3::new(-20000.0, 0.0, EARTH_RADIUS + 40.0);
+        let satellite = Vec3::new(20000.0, 0.0, EARTH_RADIUS + 40.0);
+
+        assert!(sphere().is_visible(satellite, camera));
+        let flat = Occluder { terrain: Some(uniform_terrain(0.0)), ..sphere() };
+        assert!(flat.is_visible(satellite, camera));
+        let mountains = Occluder { terrain: Some(uniform_terrain(60.0 / DISPLACEMENT_SCALE)), ..sphere() };
+        assert!(!mountains.is_visible(satellite, camera));
+    }
+
+    #[test]
+    fn occlusion_follows_the_earth_transform() {
+        // moving the earth out of the way uncovers the point
+        let camera = Vec3::new(0.0, 0.0, 20000.0);
+        let satellite = Vec3::new(0.0, 0.0, -7000.0);
+        let earth = GlobalTransform::from_translation(Vec3::new(0.0, 10000.0, 0.0));
+        let moved = Occluder { to_local: earth.affine().inverse(), ..sphere() };
+
+        assert!(!sphere().is_visible(satellite, camera));
+        assert!(moved.is_visible(satellite, camera));
+    }
+
+    #[test]
+    fn segment_ellipsoid_intervals() {
+        // straight through the centre, entering and leaving at the radius
+        let (enter, exit) = segment_ellipsoid(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -4.0), 1.0, 1.0).unwrap();
+        assert!((enter - 0.25).abs() < 1e-6 && (exit - 0.75).abs() < 1e-6);
+
+        // along the polar axis the flattened radius counts
+        let (enter, _) = segment_ellipsoid(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -4.0, 0.0), 1.0, 0.5).unwrap();
+        assert!((enter - 0.375).abs() < 1e-6);
+
+        assert!(segment_ellipsoid(Vec3::new(0.0, 2.0, 2.0), Vec3::new(0.0, 0.0, -4.0), 1.0, 1.0).is_none());
+    }
+}
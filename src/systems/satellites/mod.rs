@@ -6,10 +6,20 @@ use bevy::prelude::*;
 
 pub mod tle;
 pub mod labels;
-
-pub use tle::{Satellite, fetch_satellites};
+pub mod orbital;
+pub mod vectors;
+pub mod regime;
+pub mod colors;
+pub mod instancing;
+pub mod orbits;
+pub mod bench;
+
+pub use tle::{Catalogue, Satellite, fetch_satellites};
 use labels::setup;
+use chrono::{DateTime, Utc};
+use instancing::{SatelliteColor, SatelliteInstancingPlugin};
 use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
 
 /// Main plugin
 pub struct SatellitePlugin;
@@ -17,80 +27,43 @@ pub struct SatellitePlugin;
 impl Plugin for SatellitePlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_plugins(SatelliteInstancingPlugin)
+            .init_resource::<vectors::VelocityArrows>()
+            .init_resource::<SelectedSatellite>()
+            .init_resource::<Catalogue>()
+            .init_resource::<colors::OrbitColoring>()
+            .init_resource::<colors::SatelliteGroups>()
+            .init_resource::<orbits::Trails>()
             .add_systems(Startup, (
                 setup,
+                colors::setup_legend,
+                orbits::setup,
                 start.after(crate::systems::ui::setup),
             ))
             .add_systems(Update, (
                 update,
-                labels::update,
+                cycle_selection,
+                labels::update.run_if(not(resource_exists::<bench::Benchmark>)),
+                vectors::toggle,
+                vectors::draw.after(update),
+                colors::cycle_scheme,
+                colors::update_groups,
+                colors::apply_colors.after(colors::cycle_scheme).after(colors::update_groups),
+                colors::update_legend.after(colors::cycle_scheme).after(colors::update_groups),
+                orbits::cycle_mode,
+                orbits::rebuild
+                    .after(colors::apply_colors)
+                    .after(orbits::cycle_mode)
+                    // benchmarks measure satellite rendering alone
+                    .run_if(not(resource_exists::<bench::Benchmark>)),
+                bench::run.run_if(resource_exists::<bench::Benchmark>),
             ));
     }
 }
 
-/// Create line mesh from a series of points, wraps around
-fn create_trail_mesh(points: &[Vec3]) -> Mesh {
-    let mut positions = Vec::new();
-    let mut indices = Vec::new();
-
-    // add points
-    for point in points {
-        positions.push([point.x, point.y, point.z]);
-    }
-
-    // create line segments
-    for i in 0..points.len() {
-        let next_i = (i + 1) % points.len(); // wrap
-        indices.push(i as u32);
-        indices.push(next_i as u32);
-    }
-
-    // build the mesh
-    let mut mesh = Mesh::new(
-        bevy::render::render_resource::PrimitiveTopology::LineList,
-        bevy::render::render_asset::RenderAssetUsages::default(),
-    );
-
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
-
-    mesh
-}
-
-/// Render orbital meshes
-fn render_orbits(
-    satellites: &[Satellite],
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
-) {
-    // create orbit material, reusable
-    let orbit_material = materials.add(StandardMaterial {
-        base_color: Color::srgba(1.0, 1.0, 1.0, 0.03),
-        alpha_mode: AlphaMode::Blend,
-        unlit: true, // glowing effect
-        ..default()
-    });
-
-    // render orbit for all satellites
-    for satellite in satellites {
-        if !satellite.orbit_path.is_empty() {
-            // extract just the positions from the orbit points
-            let orbit_positions: Vec<Vec3> = satellite.orbit_path
-                .iter()
-                .map(|point| point.position)
-                .collect();
-
-            let orbit_mesh = create_trail_mesh(&orbit_positions);
-
-            commands.spawn((
-                Mesh3d(meshes.add(orbit_mesh)),
-                MeshMaterial3d(orbit_material.clone()),
-                Transform::from_xyz(0.0, 0.0, 0.0),
-            ));
-        }
-    }
-}
+/// satellite currently picked for readouts
+#[derive(Resource, Default)]
+pub struct SelectedSatellite(pub Option<Entity>);
 
 /// update satellite positions
 fn update(
@@ -99,69 +72,98 @@ fn update(
 ) {
     // println!("{}", time_state.sim_time);
 
-    for (satellite, mut transform) in satellite_query.iter_mut() {
+    // independent per satellite, spread across threads for large catalogues
+    satellite_query.par_iter_mut().for_each(|(satellite, mut transform)| {
         let new_position = satellite.get_position(time_state.sim_time);
         transform.translation = new_position;
+    });
+}
+
+/// spawn a satellite entity at its current position
+/// satellites have no mesh of their own, they are drawn by `instancing.rs`
+pub fn spawn_satellite(commands: &mut Commands, satellite: Satellite, time: DateTime<Utc>) -> Entity {
+    let position = satellite.get_position(time);
+
+    commands.spawn((
+        Transform::from_translation(position),
+        Visibility::default(),
+        SatelliteColor::default(),
+        satellite,
+    )).id()
+}
+
+/// cycle the selected satellite with Tab
+fn cycle_selection(
+    actions: Res<Actions>,
+    mut selected: ResMut<SelectedSatellite>,
+    satellite_query: Query<Entity, With<Satellite>>,
+) {
+    if !actions.just_pressed(Action::CycleSelection) {
+        return;
     }
+
+    // sort so the order is stable between frames
+    let mut entities: Vec<Entity> = satellite_query.iter().collect();
+    entities.sort();
+
+    let next = match selected.0.and_then(|current| entities.iter().position(|&e| e == current)) {
+        Some(index) => entities.get(index + 1).copied(), // past the end clears selection
+        None => entities.first().copied(),
+    };
+    selected.0 = next;
 }
 
 /// called on startup
 /// setup satellites, meshes, and stuff
 fn start(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    catalogue: Res<Catalogue>,
     time_state: Res<TimeState>,
 ) {
+    spawn_catalogue(&mut commands, load_catalogue(&catalogue), time_state.sim_time);
+}
+
+/// read every satellite in a catalogue, empty if that fails
+pub fn load_catalogue(catalogue: &Catalogue) -> Vec<Satellite> {
     // fetch TLE data
-    let task = std::thread::spawn(|| {
+    let catalogue = catalogue.clone();
+    let task = std::thread::spawn(move || {
         tokio::runtime::Runtime::new().unwrap().block_on(async {
-            fetch_satellites().await
+            fetch_satellites(&catalogue).await
         })
     });
 
     // block process briefly to get data
     // need to implement proper async handling in the future
     match task.join() {
-        Ok(Ok(mut satellites)) => {
-            // create satellite material
-            let satellite_material = materials.add(StandardMaterial {
-                base_color: Srgba::hex("#ffffff").unwrap().into(),
-                metallic: 0.0,
-                perceptual_roughness: 1.0,
-                ..default()
-            });
-
-            // generate orbit paths
-            for satellite in &mut satellites {
-                satellite.generate_orbit_path(128, time_state.sim_time);
-                
-                // debug: print orbit info
-                info!("Generated orbit for {}: {:.1} minutes, {} points", 
-                    satellite.name(), 
-                    satellite.orbit_duration_m, 
-                    satellite.orbit_path.len());
-            }
-
-            // spawn satellites at initial positions
-            for satellite in &satellites {
-                let position = satellite.get_position(time_state.sim_time);
-                
-                commands.spawn((
-                    Mesh3d(meshes.add(Sphere::new(10.0).mesh().ico(8).unwrap())),
-                    MeshMaterial3d(satellite_material.clone()),
-                    Transform::from_translation(position),
-                    satellite.clone(),
-                ));
-            }
-
-            render_orbits(&satellites, &mut commands, &mut meshes, &mut materials);
-        }
+        Ok(Ok(satellites)) => satellites,
         Ok(Err(e)) => {
             error!("Failed to fetch TLE data: {:?}", e);
+            Vec::new()
         }
         Err(_) => {
             error!("Thread panicked while fetching TLE data");
+            Vec::new()
         }
     }
-}
\ No newline at end of file
+}
+
+/// generate orbit paths from `time` and spawn satellites at their positions
+pub fn spawn_catalogue(commands: &mut Commands, mut satellites: Vec<Satellite>, time: DateTime<Utc>) {
+    // generate orbit paths
+    for satellite in &mut satellites {
+        satellite.generate_orbit_path(128, time);
+
+        // debug: print orbit info
+        info!("Generated orbit for {}: {:.1} minutes, {} points",
+            satellite.name(),
+            satellite.orbit_duration_m,
+            satellite.orbit_path.len());
+    }
+
+    // spawn satellites at initial positions
+    // orbit lines are picked up by orbits::rebuild
+    for satellite in satellites {
+        spawn_satellite(commands, satellite, time);
+    }
+}
@@ -0,0 +1,212 @@
+//! orbital.rs
+//!
+//! Derived orbital quantities from an SGP4 state vector
+//! everything here works in the TEME frame SGP4 outputs (km, km/s), f64 for precision
+//! use `teme_to_world` to bring vectors into Bevy world space for rendering
+
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use chrono::{DateTime, Utc};
+
+use crate::config::{EARTH_MU, EARTH_RADIUS};
+
+/// classical (osculating) Keplerian elements
+/// distances in km, angles in radians
+#[derive(Clone, Copy, Debug)]
+pub struct KeplerianElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub raan: f64,            // right ascension of ascending node
+    pub arg_of_perigee: f64,
+    pub true_anomaly: f64,
+}
+
+impl KeplerianElements {
+    /// orbital period in minutes, None for unbound orbits
+    pub fn period_m(&self) -> Option<f64> {
+        if self.semi_major_axis <= 0.0 || self.eccentricity >= 1.0 {
+            return None;
+        }
+        let period_s = 2.0 * std::f64::consts::PI * (self.semi_major_axis.powi(3) / EARTH_MU).sqrt();
+        Some(period_s / 60.0)
+    }
+
+    /// altitudes above the surface at perigee and apogee (km)
+    pub fn perigee_apogee_altitude(&self) -> (f64, f64) {
+        let perigee = self.semi_major_axis * (1.0 - self.eccentricity) - EARTH_RADIUS as f64;
+        let apogee = self.semi_major_axis * (1.0 + self.eccentricity) - EARTH_RADIUS as f64;
+        (perigee, apogee)
+    }
+}
+
+/// position and velocity at an instant, TEME frame
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitalState {
+    pub position: DVec3, // km
+    pub velocity: DVec3, // km/s
+}
+
+impl OrbitalState {
+    pub fn from_prediction(prediction: &sgp4::Prediction) -> Self {
+        Self {
+            position: DVec3::from_array(prediction.position),
+            velocity: DVec3::from_array(prediction.velocity),
+        }
+    }
+
+    /// speed in km/s
+    pub fn speed(&self) -> f64 {
+        self.velocity.length()
+    }
+
+    /// specific orbital energy (km²/s²), negative for bound orbits
+    /// https://en.wikipedia.org/wiki/Specific_orbital_energy
+    pub fn specific_energy(&self) -> f64 {
+        self.velocity.length_squared() / 2.0 - EARTH_MU / self.position.length()
+    }
+
+    /// specific angular momentum vector (km²/s)
+    pub fn angular_momentum(&self) -> DVec3 {
+        self.position.cross(self.velocity)
+    }
+
+    /// flight-path angle in radians
+    /// angle between velocity and the local horizontal, positive when climbing
+    pub fn flight_path_angle(&self) -> f64 {
+        let radial_speed = self.position.normalize().dot(self.velocity);
+        (radial_speed / self.speed()).clamp(-1.0, 1.0).asin()
+    }
+
+    /// radial / along-track / cross-track unit vectors (RSW frame)
+    /// along-track is perpendicular to radial in the orbit plane, so it only
+    /// matches the velocity direction for circular orbits
+    pub fn rtn_frame(&self) -> (DVec3, DVec3, DVec3) {
+        let radial = self.position.normalize();
+        let cross_track = self.angular_momentum().normalize();
+        let along_track = cross_track.cross(radial);
+        (radial, along_track, cross_track)
+    }
+
+    /// osculating Keplerian elements from the state vector
+    /// https://en.wikipedia.org/wiki/Orbital_elements#Calculating_orbital_elements_from_state_vectors
+    pub fn keplerian(&self) -> KeplerianElements {
+        use std::f64::consts::TAU;
+        const EPSILON: f64 = 1e-9;
+
+        let r = self.position;
+        let v = self.velocity;
+        let r_len = r.length();
+
+        let h = self.angular_momentum();
+        let node = DVec3::Z.cross(h); // points to ascending node
+        let ecc_vec = (v.cross(h) / EARTH_MU) - r / r_len;
+
+        let eccentricity = ecc_vec.length();
+        let energy = self.specific_energy();
+        let semi_major_axis = if energy.abs() > EPSILON { -EARTH_MU / (2.0 * energy) } else { f64::INFINITY };
+        let inclination = (h.z / h.length()).clamp(-1.0, 1.0).acos();
+
+        // angle between two vectors, flipped onto [0, 2π) using a sign test
+        let angle = |a: DVec3, b: DVec3, flip: bool| {
+            let cos = (a.dot(b) / (a.length() * b.length())).clamp(-1.0, 1.0);
+            if flip { TAU - cos.acos() } else { cos.acos() }
+        };
+
+        let equatorial = node.length() < EPSILON;
+        let circular = eccentricity < EPSILON;
+
+        let raan = if equatorial { 0.0 } else { angle(DVec3::X, node, node.y < 0.0) };
+
+        // degenerate cases collapse the undefined angles into the true anomaly
+        let (arg_of_perigee, true_anomaly) = match (circular, equatorial) {
+            (false, false) => (
+                angle(node, ecc_vec, ecc_vec.z < 0.0),
+                angle(ecc_vec, r, r.dot(v) < 0.0),
+            ),
+            (false, true) => (
+                // longitude of perigee
+                ecc_vec.y.atan2(ecc_vec.x).rem_euclid(TAU),
+                angle(ecc_vec, r, r.dot(v) < 0.0),
+            ),
+            (true, false) => (0.0, angle(node, r, r.z < 0.0)), // argument of latitude
+            (true, true) => (0.0, r.y.atan2(r.x).rem_euclid(TAU)), // true longitude
+        };
+
+        KeplerianElements {
+            semi_major_axis,
+            eccentricity,
+            inclination,
+            raan,
+            arg_of_perigee,
+            true_anomaly,
+        }
+    }
+}
+
+/// convert a TEME vector into Bevy world space
+/// a quarter turn about X, so TEME's pole (Z) is world up (Y) and nothing gets mirrored
+pub fn teme_to_world(v: DVec3) -> Vec3 {
+    Vec3::new(v.x as f32, v.z as f32, -v.y as f32)
+}
+
+/// inverse of `teme_to_world`
+pub fn world_to_teme(v: Vec3) -> DVec3 {
+    DVec3::new(v.x as f64, -v.z as f64, v.y as f64)
+}
+
+/// the globe's orientation at a time, earth-local (see `LatLon::to_direction`) to world
+/// GMST about the pole, plus a quarter turn since local longitude 0 is +Z and TEME's X is world +X
+pub fn earth_rotation(time: DateTime<Utc>) -> Quat {
+    Quat::from_rotation_y((gmst(time) + std::f64::consts::FRAC_PI_2) as f32)
+}
+
+/// Greenwich mean sidereal time in radians, the angle the earth has turned under TEME
+/// https://en.wikipedia.org/wiki/Sidereal_time#Sidereal_time_and_solar_time
+pub fn gmst(time: DateTime<Utc>) -> f64 {
+    let days_since_j2000 = julian_days_since_j2000(time);
+    (280.46061837 + 360.98564736629 * days_since_j2000).rem_euclid(360.0).to_radians()
+}
+
+/// days since the J2000 epoch (2000-01-01 12:00 TT, UTC is close enough here)
+pub fn julian_days_since_j2000(time: DateTime<Utc>) -> f64 {
+    const J2000_UNIX_SECONDS: f64 = 946_728_000.0;
+    (time.timestamp_micros() as f64 / 1e6 - J2000_UNIX_SECONDS) / 86_400.0
+}
+
+/// point on the ground below a TEME position (latitude, longitude in degrees, altitude km)
+/// spherical earth, same as the rest of the renderer
+pub fn sub_satellite_point(position: DVec3, time: DateTime<Utc>) -> (f64, f64, f64) {
+    let distance = position.length();
+    let latitude = (position.z / distance).clamp(-1.0, 1.0).asin().to_degrees();
+    let longitude = (position.y.atan2(position.x) - gmst(time)).to_degrees();
+    let longitude = (longitude + 180.0).rem_euclid(360.0) - 180.0;
+    (latitude, longitude, distance - EARTH_RADIUS as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::earth::uv::LatLon;
+
+    // the globe and the map must put a satellite over the same place
+    #[test]
+    fn globe_matches_sub_satellite_point() {
+        let times = ["2000-01-01T12:00:00Z", "2024-03-20T03:06:00Z", "2031-11-05T17:42:13Z"];
+        let positions = [
+            DVec3::new(6778.0, 0.0, 0.0),
+            DVec3::new(-3000.0, 5000.0, 2500.0),
+            DVec3::new(1200.0, -800.0, -6900.0),
+            DVec3::new(-42164.0, -100.0, 30.0),
+        ];
+        for time in times {
+            let time = DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+            for position in positions {
+                let (latitude, longitude, _) = sub_satellite_point(position, time);
+                let on_map = LatLon::from_degrees(latitude as f32, longitude as f32).to_direction();
+                let on_globe = earth_rotation(time).inverse() * teme_to_world(position).normalize();
+                assert!(on_map.distance(on_globe) < 1e-4, "{time}: {on_map} vs {on_globe}");
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! orbits.rs
+//!
+//! Orbit line renderer
+//! every orbit is packed into one shared LineList mesh with per-vertex colour,
+//! rebuilt in place whenever orbits, colours or visibility change
+//!
+//! two trail modes, cycled with T:
+//! - full orbit loops (default)
+//! - comet tails: the last few minutes of motion fading out behind each satellite,
+//!   optionally with a short lead ahead. rebuilt every frame while time moves
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::view::NoFrustumCulling;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::systems::satellites::Satellite;
+use crate::systems::satellites::instancing::SatelliteColor;
+use crate::systems::settings::RenderSettings;
+use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+
+const TAIL_SAMPLES: usize = 32; // points per tail (and per lead)
+const TAIL_ALPHA_SCALE: f32 = 12.0; // tail alpha at the satellite per unit of orbit alpha (0.6 at the default 0.05)
+
+/// how orbit lines are drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailMode {
+    Orbit,
+    Tail {
+        past_m: f64,   // minutes of history behind the satellite
+        future_m: f64, // minutes of prediction ahead, 0 to disable
+    },
+}
+
+#[derive(Resource)]
+pub struct Trails {
+    pub mode: TrailMode,
+}
+
+impl Default for Trails {
+    fn default() -> Self {
+        Self { mode: TrailMode::Orbit }
+    }
+}
+
+/// the merged orbit line mesh
+#[derive(Component)]
+pub struct OrbitLines {
+    mesh: Handle<Mesh>,
+}
+
+/// vertex buffers for a batch of line segments
+#[derive(Default)]
+pub struct LineBuffers {
+    pub positions: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+}
+
+impl LineBuffers {
+    /// add a closed loop through the given points, single colour
+    pub fn push_loop(&mut self, points: &[Vec3], color: LinearRgba) {
+        let base = self.positions.len() as u32;
+        let len = points.len() as u32;
+
+        for point in points {
+            self.positions.push(point.to_array());
+            self.colors.push(color.to_f32_array());
+        }
+
+        // create line segments
+        for i in 0..len {
+            let next_i = (i + 1) % len; // wrap
+            self.indices.push(base + i);
+            self.indices.push(base + next_i);
+        }
+    }
+
+    /// add an open polyline, one colour per point
+    pub fn push_strip(&mut self, points: &[Vec3], colors: &[LinearRgba]) {
+        let base = self.positions.len() as u32;
+
+        for (point, color) in points.iter().zip(colors) {
+            self.positions.push(point.to_array());
+            self.colors.push(color.to_f32_array());
+        }
+
+        for i in 1..points.len().min(colors.len()) as u32 {
+            self.indices.push(base + i - 1);
+            self.indices.push(base + i);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// write buffers into a LineList mesh, replacing its contents
+    pub fn write_to(self, mesh: &mut Mesh) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.insert_indices(Indices::U32(self.indices));
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // start with one degenerate, transparent segment so the mesh is never empty
+    let mut placeholder = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default());
+    LineBuffers {
+        positions: vec![[0.0; 3]],
+        colors: vec![[0.0; 4]],
+        indices: vec![0, 0],
+    }.write_to(&mut placeholder);
+    let mesh = meshes.add(placeholder);
+
+    // vertex colours carry the per-orbit colour and alpha
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true, // glowing effect
+        ..default()
+    });
+
+    commands.spawn((
+        Mesh3d(mesh.clone()),
+        MeshMaterial3d(material),
+        Transform::default(),
+        Visibility::Hidden, // shown once there is something to draw
+        NoFrustumCulling,   // bounds change as the mesh is rebuilt
+        OrbitLines { mesh },
+    ));
+}
+
+/// cycle trail modes with T
+pub fn cycle_mode(
+    actions: Res<Actions>,
+    mut trails: ResMut<Trails>,
+) {
+    if actions.just_pressed(Action::CycleTrails) {
+        trails.mode = match trails.mode {
+            TrailMode::Orbit => TrailMode::Tail { past_m: 15.0, future_m: 0.0 },
+            TrailMode::Tail { future_m: 0.0, .. } => TrailMode::Tail { past_m: 15.0, future_m: 5.0 },
+            TrailMode::Tail { .. } => TrailMode::Orbit,
+        };
+    }
+}
+
+/// rebuild the merged mesh when anything feeding it changes
+/// tails depend on sim time, so they also rebuild whenever time moves
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn rebuild(
+    trails: Res<Trails>,
+    time_state: Res<TimeState>,
+    settings: Res<RenderSettings>,
+    mut applied: Local<Option<(bool, f32)>>, // orbit settings last built with
+    satellites: Query<(&Satellite, &SatelliteColor, &Visibility)>,
+    changed: Query<(), (With<Satellite>, Or<(Changed<Satellite>, Changed<SatelliteColor>, Changed<Visibility>)>)>,
+    mut removed: RemovedComponents<Satellite>,
+    mut lines: Query<(&OrbitLines, &mut Visibility), Without<Satellite>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let any_removed = removed.read().count() > 0;
+    let time_dependent = matches!(trails.mode, TrailMode::Tail { .. }) && time_state.is_changed();
+    // other settings don't affect the lines, skip rebuilding for those
+    let orbit_settings = (settings.orbits, settings.orbit_alpha);
+    let settings_changed = *applied != Some(orbit_settings);
+    if changed.is_empty() && !any_removed && !trails.is_changed() && !time_dependent && !settings_changed {
+        return;
+    }
+    *applied = Some(orbit_settings);
+    let Ok((orbit_lines, mut visibility)) = lines.single_mut() else { return; };
+
+    if !settings.orbits {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    }
+
+    let mut buffers = LineBuffers::default();
+    for (satellite, color, sat_visibility) in satellites.iter() {
+        if *sat_visibility == Visibility::Hidden || satellite.orbit_path.is_empty() {
+            continue;
+        }
+
+        match trails.mode {
+            TrailMode::Orbit => {
+                // extract just the positions from the orbit points
+                let orbit_positions: Vec<Vec3> = satellite.orbit_path
+                    .iter()
+                    .map(|point| point.position)
+                    .collect();
+
+                buffers.push_loop(&orbit_positions, LinearRgba::from(color.0.with_alpha(settings.orbit_alpha)));
+            }
+            TrailMode::Tail { past_m, future_m } => {
+                // "behind" follows playback direction, so tails flip when time runs backwards
+                let direction = if time_state.speed_mult < 0.0 { -1.0 } else { 1.0 };
+                // tails follow the orbit alpha slider too, just brighter since they're short
+                let base = LinearRgba::from(color.0).with_alpha((settings.orbit_alpha * TAIL_ALPHA_SCALE).min(1.0));
+
+                push_tail(&mut buffers, satellite, time_state.sim_time, -direction * past_m, base);
+                if future_m > 0.0 {
+                    push_tail(&mut buffers, satellite, time_state.sim_time, direction * future_m, base);
+                }
+            }
+        }
+    }
+
+    // an empty mesh can't be uploaded, hide the entity instead
+    if buffers.is_empty() {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    }
+    visibility.set_if_neq(Visibility::Inherited);
+
+    if let Some(mesh) = meshes.get_mut(&orbit_lines.mesh) {
+        buffers.write_to(mesh);
+    }
+}
+
+/// sample a fading polyline from `time` to `time + span_m` minutes
+/// alpha starts at `color`'s at the satellite and fades to nothing at the far end
+fn push_tail(
+    buffers: &mut LineBuffers,
+    satellite: &Satellite,
+    time: DateTime<Utc>,
+    span_m: f64,
+    color: LinearRgba,
+) {
+    // the cached orbit path wraps after one period, longer spans would overlap
+    let span_m = span_m.clamp(-satellite.orbit_duration_m, satellite.orbit_duration_m);
+
+    let mut points = Vec::with_capacity(TAIL_SAMPLES);
+    let mut colors = Vec::with_capacity(TAIL_SAMPLES);
+
+    for i in 0..TAIL_SAMPLES {
+        let fraction = i as f64 / (TAIL_SAMPLES - 1) as f64;
+        let offset = Duration::milliseconds((fraction * span_m * 60_000.0) as i64);
+
+        points.push(satellite.get_position(time + offset));
+        colors.push(color.with_alpha(color.alpha * (1.0 - fraction as f32).powi(2)));
+    }
+
+    buffers.push_strip(&points, &colors);
+}
@@ -0,0 +1,201 @@
+//! regime.rs
+//!
+//! Orbit regime classification from mean TLE elements
+//! thresholds are the usual rule-of-thumb ones, not any official definition
+
+use crate::config::{EARTH_MU, EARTH_RADIUS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OrbitRegime {
+    Leo,
+    Meo,
+    Geo,
+    Heo,
+    Molniya,
+    Sso, // sun-synchronous, a subset of LEO
+    Decaying,
+}
+
+impl OrbitRegime {
+    pub const ALL: [OrbitRegime; 7] = [
+        OrbitRegime::Leo,
+        OrbitRegime::Sso,
+        OrbitRegime::Meo,
+        OrbitRegime::Geo,
+        OrbitRegime::Heo,
+        OrbitRegime::Molniya,
+        OrbitRegime::Decaying,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrbitRegime::Leo => "LEO",
+            OrbitRegime::Meo => "MEO",
+            OrbitRegime::Geo => "GEO",
+            OrbitRegime::Heo => "HEO",
+            OrbitRegime::Molniya => "Molniya",
+            OrbitRegime::Sso => "SSO",
+            OrbitRegime::Decaying => "Decaying",
+        }
+    }
+
+    /// classify from mean elements
+    pub fn classify(elements: &sgp4::Elements) -> Self {
+        let shape = MeanOrbit::from_elements(elements);
+        let inclination = elements.inclination;
+
+        // re-entering soon, or already dragging hard
+        // mean_motion_dot is in rev/day², anything above this is burning up
+        if shape.perigee_altitude < 200.0 || elements.mean_motion_dot > 1e-3 {
+            return OrbitRegime::Decaying;
+        }
+
+        // ~12h period, highly eccentric, critical inclination
+        if elements.eccentricity > 0.5
+            && (62.0..=65.5).contains(&inclination)
+            && (1.9..=2.1).contains(&elements.mean_motion)
+        {
+            return OrbitRegime::Molniya;
+        }
+
+        if elements.eccentricity > 0.25 {
+            return OrbitRegime::Heo;
+        }
+
+        // one revolution per sidereal day
+        if (0.98..=1.02).contains(&elements.mean_motion) && elements.eccentricity < 0.05 {
+            return OrbitRegime::Geo;
+        }
+
+        if shape.apogee_altitude < 2000.0 {
+            if is_sun_synchronous(shape.semi_major_axis, elements.eccentricity, inclination) {
+                return OrbitRegime::Sso;
+            }
+            return OrbitRegime::Leo;
+        }
+
+        if shape.apogee_altitude < 35000.0 {
+            return OrbitRegime::Meo;
+        }
+
+        OrbitRegime::Heo
+    }
+}
+
+/// orbit size derived from the TLE mean motion (km)
+#[derive(Clone, Copy, Debug)]
+pub struct MeanOrbit {
+    pub semi_major_axis: f64,
+    pub perigee_altitude: f64,
+    pub apogee_altitude: f64,
+}
+
+impl MeanOrbit {
+    pub fn from_elements(elements: &sgp4::Elements) -> Self {
+        // rev/day to rad/s
+        let n = elements.mean_motion * std::f64::consts::TAU / 86400.0;
+        let semi_major_axis = (EARTH_MU / (n * n)).cbrt();
+
+        Self {
+            semi_major_axis,
+            perigee_altitude: semi_major_axis * (1.0 - elements.eccentricity) - EARTH_RADIUS as f64,
+            apogee_altitude: semi_major_axis * (1.0 + elements.eccentricity) - EARTH_RADIUS as f64,
+        }
+    }
+
+    /// mean altitude, average of perigee and apogee
+    pub fn mean_altitude(&self) -> f64 {
+        (self.perigee_altitude + self.apogee_altitude) / 2.0
+    }
+}
+
+/// J2 nodal precession matches the sun's apparent motion (~0.9856°/day)
+/// cos(i) = -(a / 12352km)^(7/2) * (1 - e²)²
+/// https://en.wikipedia.org/wiki/Sun-synchronous_orbit#Technical_details
+fn is_sun_synchronous(semi_major_axis: f64, eccentricity: f64, inclination_deg: f64) -> bool {
+    const TOLERANCE_DEG: f64 = 1.5;
+
+    let cos_i = -(semi_major_axis / 12352.0).powf(3.5) * (1.0 - eccentricity * eccentricity).powi(2);
+    if cos_i < -1.0 {
+        return false; // too high for SSO to exist
+    }
+
+    let required = cos_i.acos().to_degrees();
+    (inclination_deg - required).abs() < TOLERANCE_DEG
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISS: (&str, &str) = (
+        "1 25544U 98067A   24001.50000000  .00016717  00000-0  30074-3 0  9991",
+        "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.50377579432838",
+    );
+
+    /// the ISS with its shape swapped out
+    fn orbit(mean_motion: f64, eccentricity: f64, inclination: f64) -> sgp4::Elements {
+        let mut elements = sgp4::Elements::from_tle(None, ISS.0.as_bytes(), ISS.1.as_bytes()).unwrap();
+        elements.mean_motion = mean_motion;
+        elements.eccentricity = eccentricity;
+        elements.inclination = inclination;
+        elements.mean_motion_dot = 0.0;
+        elements
+    }
+
+    /// circular orbit at an altitude (km)
+    fn circular(altitude: f64, inclination: f64) -> sgp4::Elements {
+        let semi_major_axis = EARTH_RADIUS as f64 + altitude;
+        let mean_motion = (EARTH_MU / semi_major_axis.powi(3)).sqrt() * 86400.0 / std::f64::consts::TAU;
+        orbit(mean_motion, 0.0, inclination)
+    }
+
+    #[test]
+    fn known_orbits() {
+        let iss = sgp4::Elements::from_tle(None, ISS.0.as_bytes(), ISS.1.as_bytes()).unwrap();
+        assert_eq!(OrbitRegime::classify(&iss), OrbitRegime::Leo);
+
+        assert_eq!(OrbitRegime::classify(&circular(800.0, 98.6)), OrbitRegime::Sso);
+        assert_eq!(OrbitRegime::classify(&orbit(2.0056, 0.01, 55.0)), OrbitRegime::Meo); // GPS
+        assert_eq!(OrbitRegime::classify(&orbit(1.0027, 0.0002, 0.05)), OrbitRegime::Geo);
+        assert_eq!(OrbitRegime::classify(&orbit(2.006, 0.74, 63.4)), OrbitRegime::Molniya);
+        assert_eq!(OrbitRegime::classify(&orbit(2.27, 0.73, 27.0)), OrbitRegime::Heo); // GTO
+        assert_eq!(OrbitRegime::classify(&circular(150.0, 51.6)), OrbitRegime::Decaying);
+    }
+
+    #[test]
+    fn boundaries() {
+        // perigee 200 km
+        assert_eq!(OrbitRegime::classify(&circular(201.0, 51.6)), OrbitRegime::Leo);
+        assert_eq!(OrbitRegime::classify(&circular(199.0, 51.6)), OrbitRegime::Decaying);
+
+        // dragging hard, whatever the altitude
+        let mut decaying = circular(400.0, 51.6);
+        decaying.mean_motion_dot = 2e-3;
+        assert_eq!(OrbitRegime::classify(&decaying), OrbitRegime::Decaying);
+
+        // apogee 2000 km between LEO and MEO
+        assert_eq!(OrbitRegime::classify(&circular(1999.0, 51.6)), OrbitRegime::Leo);
+        assert_eq!(OrbitRegime::classify(&circular(2001.0, 51.6)), OrbitRegime::Meo);
+
+        // SSO within 1.5° of the inclination its altitude needs (~98.6° at 800 km)
+        assert_eq!(OrbitRegime::classify(&circular(800.0, 97.5)), OrbitRegime::Sso);
+        assert_eq!(OrbitRegime::classify(&circular(800.0, 96.5)), OrbitRegime::Leo);
+
+        // eccentricity 0.25 between MEO and HEO
+        assert_eq!(OrbitRegime::classify(&orbit(2.0, 0.25, 55.0)), OrbitRegime::Meo);
+        assert_eq!(OrbitRegime::classify(&orbit(2.0, 0.26, 55.0)), OrbitRegime::Heo);
+
+        // GEO: 0.98-1.02 rev/day, nearly circular
+        assert_eq!(OrbitRegime::classify(&orbit(0.98, 0.0, 0.1)), OrbitRegime::Geo);
+        assert_eq!(OrbitRegime::classify(&orbit(1.02, 0.0, 0.1)), OrbitRegime::Geo);
+        assert_ne!(OrbitRegime::classify(&orbit(0.97, 0.0, 0.1)), OrbitRegime::Geo);
+        assert_ne!(OrbitRegime::classify(&orbit(1.0, 0.05, 0.1)), OrbitRegime::Geo);
+
+        // Molniya: critical inclination 62-65.5°, ~2 rev/day
+        assert_eq!(OrbitRegime::classify(&orbit(2.006, 0.74, 62.0)), OrbitRegime::Molniya);
+        assert_eq!(OrbitRegime::classify(&orbit(2.006, 0.74, 65.5)), OrbitRegime::Molniya);
+        assert_eq!(OrbitRegime::classify(&orbit(2.006, 0.74, 61.5)), OrbitRegime::Heo);
+        assert_eq!(OrbitRegime::classify(&orbit(2.2, 0.7, 63.4)), OrbitRegime::Heo);
+    }
+}
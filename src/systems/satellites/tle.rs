@@ -4,6 +4,7 @@
 //! uses SGP4 model to propagate satellite orbits from TLE datasets, and convert
 //! orbital predictions into Bevy world coordinates
 
+use bevy::math::DVec3;
 use bevy::prelude::*;
 
 use bevy::asset::uuid::Error;
@@ -13,13 +14,16 @@ use sgp4::Prediction;
 use std::fs;
 use std::path::Path;
 
-use crate::config::EARTH_RADIUS;
+use crate::config::{EARTH_RADIUS, TLE_SOURCES};
+use crate::systems::satellites::orbital::{OrbitalState, teme_to_world, world_to_teme};
+use crate::systems::satellites::regime::OrbitRegime;
 
 // point in orbital path
 #[derive(Clone, Debug)]
 pub struct OrbitPoint {
     pub time: DateTime<Utc>,
     pub position: Vec3,
+    pub velocity: Vec3, // km/s, world space
 }
 
 // satellite component
@@ -31,6 +35,9 @@ pub struct Satellite {
 
     pub orbit_path: Vec<OrbitPoint>,
     pub orbit_duration_m: f64, // how long the orbit path covers, minutes
+
+    pub group: String, // catalogue this came from
+    pub regime: OrbitRegime,
 }
 
 impl Satellite {
@@ -46,14 +53,22 @@ impl Satellite {
             line1.as_bytes(),
             line2.as_bytes()
         ).ok()?;
-        
+
+        Self::from_elements(elements)
+    }
+
+    /// build from already parsed elements, None if SGP4 rejects them
+    pub fn from_elements(elements: sgp4::Elements) -> Option<Self> {
         let constants = sgp4::Constants::from_elements(&elements).ok()?;
+        let regime = OrbitRegime::classify(&elements);
 
         Some(Satellite {
             elements,
             constants,
             orbit_path: Vec::new(), // will be populated later
             orbit_duration_m: 0.0,
+            group: String::new(),
+            regime,
         })
     }
 
@@ -61,21 +76,26 @@ impl Satellite {
     pub fn name(&self) -> &str {
         self.elements.object_name.as_deref().unwrap_or("Unknown")
     }
-    // pub fn norad_id(&self) -> u64 {
-    //     self.elements.norad_id
-    // }
+    pub fn norad_id(&self) -> u64 {
+        self.elements.norad_id
+    }
     // pub fn intl_id(&self) -> &str {
     //     self.elements.international_designator.as_deref().unwrap_or("Unknown")
     // }
-    // pub fn inclination(&self) -> f64 {
-    //     self.elements.inclination
-    // }
+    pub fn inclination(&self) -> f64 {
+        self.elements.inclination
+    }
     // pub fn mean_motion(&self) -> f64 {
     //     self.elements.mean_motion
     // }
-    // pub fn epoch_datetime(&self) -> &chrono::NaiveDateTime {
-    //     &self.elements.datetime
-    // }
+    pub fn epoch_datetime(&self) -> &chrono::NaiveDateTime {
+        &self.elements.datetime
+    }
+
+    /// days between the TLE epoch and a given time, negative if before epoch
+    pub fn tle_age_days(&self, time: DateTime<Utc>) -> f64 {
+        (time.naive_utc() - *self.epoch_datetime()).num_seconds() as f64 / 86400.0
+    }
 
     // generate orbital path and store it in self.orbit_path
     pub fn generate_orbit_path(&mut self, resolution: usize, base_time: DateTime<Utc>) {
@@ -98,16 +118,18 @@ impl Satellite {
 
             if let Some(minutes_since_epoch) = self.minutes_since_epoch(point_time) {
                 let prediction = self.constants.propagate(sgp4::MinutesSinceEpoch(minutes_since_epoch))
-                    .unwrap_or_else(|_| Prediction {
+                    .unwrap_or(Prediction {
                         position: [0.0, 0.0, 0.0],
                         velocity: [0.0, 0.0, 0.0],
                     });
                 
-                let position = sgp4_to_cartesian(&prediction);
-                
+                let position = teme_to_world(DVec3::from_array(prediction.position));
+                let velocity = teme_to_world(DVec3::from_array(prediction.velocity));
+
                 self.orbit_path.push(OrbitPoint {
                     time: point_time,
                     position,
+                    velocity,
                 });
             }
         }
@@ -124,31 +146,58 @@ impl Satellite {
             return self.orbit_path[0].position;
         }
 
-        let elapsed_minutes = (target_time - self.orbit_path[0].time).num_seconds() as f64 / 60.0;
-        let cycle_time = elapsed_minutes.rem_euclid(self.orbit_duration_m); // correct modulo for negative times
-        let time_per_segment = self.orbit_duration_m / (self.orbit_path.len() - 1) as f64;
-        let segment_index = ((cycle_time / time_per_segment).floor() as usize)
-            .min(self.orbit_path.len() - 2);
-
-        let t = ((cycle_time % time_per_segment) / time_per_segment).clamp(0.0, 1.0);
+        let (segment_index, t) = self.segment_at(target_time);
 
         self.orbit_path[segment_index]
             .position
-            .lerp(self.orbit_path[segment_index + 1].position, t as f32)
+            .lerp(self.orbit_path[segment_index + 1].position, t)
+    }
+
+    /// Get velocity of satellite given a time value (km/s, world space)
+    /// interpolates across the generated orbit path, same as `get_position`
+    pub fn get_velocity(&self, target_time: DateTime<Utc>) -> Vec3 {
+        match self.orbit_path.len() {
+            0 => Vec3::ZERO,
+            1 => self.orbit_path[0].velocity,
+            _ => {
+                let (segment_index, t) = self.segment_at(target_time);
+                self.orbit_path[segment_index]
+                    .velocity
+                    .lerp(self.orbit_path[segment_index + 1].velocity, t)
+            }
+        }
+    }
+
+    /// exact SGP4 state at a given time, TEME frame
+    /// use this for readouts, the interpolated getters are for rendering
+    pub fn orbital_state(&self, time: DateTime<Utc>) -> Option<OrbitalState> {
+        let minutes_since_epoch = self.minutes_since_epoch(time)?;
+        let prediction = self.constants.propagate(sgp4::MinutesSinceEpoch(minutes_since_epoch)).ok()?;
+        Some(OrbitalState::from_prediction(&prediction))
     }
 
     /// get geodetic position at specific time (lat, lon, alt)
     pub fn geodetic_position(&self, time: DateTime<Utc>) -> (f64, f64, f64) {
-        let position = self.get_position(time);
-        cartesian_to_geodetic(
-            position.x as f64,
-            position.y as f64, 
-            position.z as f64
-        )
+        let position = world_to_teme(self.get_position(time));
+        cartesian_to_geodetic(position.x, position.y, position.z)
     }
 
     // HELPERS
 
+    /// find which orbit path segment a time falls in, and how far along it
+    /// assumes at least two points in the path
+    fn segment_at(&self, target_time: DateTime<Utc>) -> (usize, f32) {
+        let elapsed_minutes = (target_time - self.orbit_path[0].time).as_seconds_f64() / 60.0;
+        let cycle_time = elapsed_minutes.rem_euclid(self.orbit_duration_m); // correct modulo for negative times
+        let time_per_segment = self.orbit_duration_m / (self.orbit_path.len() - 1) as f64;
+        let segment_index = ((cycle_time / time_per_segment).floor() as usize)
+            .min(self.orbit_path.len() - 2);
+
+        let t = ((cycle_time % time_per_segment) / time_per_segment).clamp(0.0, 1.0);
+
+        (segment_index, t as f32)
+    }
+
     /// time difference since TLE epoch
     fn minutes_since_epoch(&self, target_time: DateTime<Utc>) -> Option<f64> {
         let target_naive = target_time.naive_utc();
@@ -174,42 +223,68 @@ pub fn cartesian_to_geodetic(x: f64, y: f64, z: f64) -> (f64, f64, f64) {
     (latitude, longitude, altitude)
 }
 
-/// convert SGP4 coordinates to Bevy world coordinates
-pub fn sgp4_to_cartesian(prediction: &Prediction) -> Vec3 {
-    // SGP4 returns coordinates in kilometers
-    Vec3::new(
-        prediction.position[0] as f32,
-        prediction.position[2] as f32,  // swapped Y and Z
-        prediction.position[1] as f32,
-    )
+/// where satellites come from: TLE files, plus TLE text carried inline (by a scenario file)
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct Catalogue {
+    pub sources: Vec<String>,
+    pub embedded: Vec<(String, String)>, // group, 3-line TLE text
 }
 
-/// fetch satellite data, asynchronous
-pub async fn fetch_satellites() -> Result<Vec<Satellite>, Error> {
-    let path = Path::new("assets/data/weather.txt");
-    let tle_data = match fs::read_to_string(path) {
-        Ok(contents) => {
-            info!("Loaded TLE data from local file: {:?}", path);
-            contents
-        }
-        Err(err) => {
-            eprintln!("Failed to read TLE file: {err}");
-            return Ok(vec![]);
+impl Default for Catalogue {
+    fn default() -> Self {
+        Self {
+            sources: TLE_SOURCES.iter().map(|source| source.to_string()).collect(),
+            embedded: Vec::new(),
         }
-    };
-    
-    // parse TLE data into satellites
-    let lines: Vec<&str> = tle_data.lines().collect();
+    }
+}
+
+/// group a catalogue file's satellites are tagged with, its file name
+pub fn source_group(source: &str) -> &str {
+    Path::new(source).file_stem().and_then(|s| s.to_str()).unwrap_or("unknown")
+}
+
+/// fetch satellite data, asynchronous
+/// reads every file in the catalogue, the file name becomes the satellite's group
+pub async fn fetch_satellites(catalogue: &Catalogue) -> Result<Vec<Satellite>, Error> {
     let mut satellites: Vec<Satellite> = Vec::new();
 
-    for chunk in lines.chunks(3) {
-        if chunk.len() == 3 {
-            if let Some(satellite) = Satellite::parse(chunk[0], chunk[1], chunk[2]) {
-                satellites.push(satellite);
+    for source in &catalogue.sources {
+        let path = Path::new(source);
+        let tle_data = match fs::read_to_string(path) {
+            Ok(contents) => {
+                info!("Loaded TLE data from local file: {:?}", path);
+                contents
             }
-        }
+            Err(err) => {
+                eprintln!("Failed to read TLE file {source}: {err}");
+                continue;
+            }
+        };
+
+        satellites.extend(parse_tle_data(&tle_data, source_group(source)));
+    }
+
+    for (group, tle_data) in &catalogue.embedded {
+        satellites.extend(parse_tle_data(tle_data, group));
     }
 
     info!("Parsed {} satellites", satellites.len());
     Ok(satellites)
-}
\ No newline at end of file
+}
+
+/// parse 3-line TLE text into satellites, tagging each with a catalogue group
+pub fn parse_tle_data(tle_data: &str, group: &str) -> Vec<Satellite> {
+    let lines: Vec<&str> = tle_data.lines().collect();
+    let mut satellites: Vec<Satellite> = Vec::new();
+
+    for chunk in lines.chunks(3) {
+        if chunk.len() == 3
+            && let Some(mut satellite) = Satellite::parse(chunk[0], chunk[1], chunk[2]) {
+            satellite.group = group.to_string();
+            satellites.push(satellite);
+        }
+    }
+
+    satellites
+}
@@ -0,0 +1,74 @@
+//! vectors.rs
+//!
+//! Debug gizmos for satellite motion
+//! draws a velocity arrow on each satellite, plus the radial/along-track/cross-track axes
+//! toggle with V (velocity) and F (frame)
+
+use bevy::prelude::*;
+
+use crate::systems::satellites::Satellite;
+use crate::systems::satellites::orbital::teme_to_world;
+use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+
+/// gizmo display settings
+#[derive(Resource)]
+pub struct VelocityArrows {
+    pub show_velocity: bool,
+    pub show_frame: bool,
+    pub velocity_scale: f32, // world units per km/s
+    pub frame_length: f32,   // length of the RTN axes
+}
+
+impl Default for VelocityArrows {
+    fn default() -> Self {
+        Self {
+            show_velocity: false,
+            show_frame: false,
+            velocity_scale: 100.0, // ~750km arrow for LEO
+            frame_length: 400.0,
+        }
+    }
+}
+
+pub fn toggle(
+    actions: Res<Actions>,
+    mut arrows: ResMut<VelocityArrows>,
+) {
+    if actions.just_pressed(Action::ToggleVelocity) {
+        arrows.show_velocity = !arrows.show_velocity;
+    }
+    if actions.just_pressed(Action::ToggleAxes) {
+        arrows.show_frame = !arrows.show_frame;
+    }
+}
+
+pub fn draw(
+    mut gizmos: Gizmos,
+    arrows: Res<VelocityArrows>,
+    time_state: Res<TimeState>,
+    satellites: Query<(&Satellite, &Transform)>,
+) {
+    if !arrows.show_velocity && !arrows.show_frame {
+        return;
+    }
+
+    for (satellite, transform) in satellites.iter() {
+        let origin = transform.translation;
+
+        if arrows.show_velocity {
+            let velocity = satellite.get_velocity(time_state.sim_time);
+            gizmos.arrow(origin, origin + velocity * arrows.velocity_scale, Color::srgb(1.0, 0.8, 0.2));
+        }
+
+        if arrows.show_frame {
+            let Some(state) = satellite.orbital_state(time_state.sim_time) else { continue; };
+            let (radial, along_track, cross_track) = state.rtn_frame();
+
+            let length = arrows.frame_length;
+            gizmos.line(origin, origin + teme_to_world(radial) * length, Color::srgb(1.0, 0.3, 0.3));
+            gizmos.line(origin, origin + teme_to_world(along_track) * length, Color::srgb(0.3, 1.0, 0.3));
+            gizmos.line(origin, origin + teme_to_world(cross_track) * length, Color::srgb(0.3, 0.5, 1.0));
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! scenario.rs
+//!
+//! Scenario files, a JSON snapshot of a session so anyone can open it and see the same thing:
+//! start time, speed, the catalogue (TLE file paths and/or TLEs inline), selected satellites
+//! (NORAD ids), ground stations, camera bookmarks, the camera view and the layers
+//! every key is optional, anything left out keeps the app's default
+//!
+//! `--scenario <file>` starts from one, `--save-scenario <file>` is where saving writes (otherwise
+//! the loaded file, or SCENARIO_PATH). F5 / the settings panel save, F8 / the panel reload, which
+//! respawns the satellites from the file's catalogue at its start time
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value, json};
+
+use crate::config::{SCENARIO_EMBED_TLES, SCENARIO_PATH};
+use crate::systems::bookmarks::{Bookmark, Bookmarks, FlyTo, decimal};
+use crate::systems::camera::OrbitCamera;
+use crate::systems::camera_modes::{CameraMode, CameraModeState};
+use crate::systems::controls::{Action, Actions};
+use crate::systems::earth::Earth;
+use crate::systems::earth::stations::{GroundStation, GroundStations};
+use crate::systems::satellites::orbital::earth_rotation;
+use crate::systems::satellites::tle::source_group;
+use crate::systems::satellites::{self, Catalogue, Satellite, SelectedSatellite};
+use crate::systems::settings::RenderSettings;
+use crate::systems::time::TimeState;
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        let (paths, startup) = ScenarioPaths::from_args();
+
+        // time and catalogue have to be in place before the satellites spawn on startup,
+        // the rest is applied on the first frame
+        let mut pending = PendingScenario::default();
+        if let Some(path) = startup {
+            match Scenario::load(&path) {
+                Ok(scenario) => {
+                    info!("Starting from scenario {}", path);
+                    app.insert_resource(scenario.time_state())
+                        .insert_resource(scenario.catalogue.clone());
+                    pending.0 = Some((scenario, false));
+                }
+                Err(e) => error!("Could not load scenario {}: {}", path, e),
+            }
+        }
+
+        app.add_event::<ScenarioCommand>()
+            .insert_resource(paths)
+            .insert_resource(pending)
+            .init_resource::<PendingSelection>()
+            .add_systems(Update, (
+                handle_actions,
+                save.after(handle_actions),
+                load.after(handle_actions),
+                apply.after(load),
+                select_pending.after(apply),
+            ));
+    }
+}
+
+/// save or reload, from keys or the settings panel
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenarioCommand {
+    Save,
+    Load,
+}
+
+/// files scenarios are read from and written to
+#[derive(Resource, Clone, Debug)]
+pub struct ScenarioPaths {
+    pub load: String,
+    pub save: String,
+}
+
+impl ScenarioPaths {
+    /// `--scenario <file>` and `--save-scenario <file>`, plus the file to start from if there is one
+    fn from_args() -> (Self, Option<String>) {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| {
+            let index = args.iter().position(|arg| arg == flag)?;
+            args.get(index + 1).filter(|arg| !arg.starts_with("--")).cloned()
+        };
+
+        let startup = value("--scenario");
+        let load = startup.clone().unwrap_or_else(|| SCENARIO_PATH.to_string());
+        let save = value("--save-scenario").unwrap_or_else(|| load.clone());
+        (Self { load, save }, startup)
+    }
+}
+
+/// scenario waiting to be applied, and whether the satellites have to be respawned for it
+#[derive(Resource, Default)]
+pub struct PendingScenario(Option<(Scenario, bool)>);
+
+/// NORAD ids to select once their satellites exist
+#[derive(Resource, Default)]
+pub struct PendingSelection(Vec<u64>);
+
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub start_time: DateTime<Utc>,
+    pub speed: f64,
+    pub paused: bool,
+    pub catalogue: Catalogue,
+    pub selected: Vec<u64>, // NORAD ids, the app selects one at a time so only the first is used
+    pub ground_stations: Vec<GroundStation>,
+    pub bookmarks: Vec<Bookmark>,
+    pub camera: Option<Bookmark>, // orbit camera view, earth frame
+    pub settings: RenderSettings,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let time_state = TimeState::default();
+        Self {
+            start_time: time_state.sim_time,
+            speed: time_state.speed_mult,
+            paused: time_state.is_paused,
+            catalogue: Catalogue::default(),
+            selected: Vec::new(),
+            ground_stations: Vec::new(),
+            bookmarks: Vec::new(),
+            camera: None,
+            settings: RenderSettings::default(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let json = serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())?;
+        Self::from_json(&json)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let text = serde_json::to_string_pretty(&self.to_json()).map_err(|e| e.to_string())?;
+        std::fs::write(path, text + "\n").map_err(|e| e.to_string())
+    }
+
+    fn time_state(&self) -> TimeState {
+        TimeState { is_paused: self.paused, speed_mult: self.speed, sim_time: self.start_time }
+    }
+
+    /// malformed values are errors, bad list entries are skipped with a warning
+    pub fn from_json(json: &Value) -> Result<Self, String> {
+        let json = json.as_object().ok_or("expected a JSON object")?;
+        let mut scenario = Self::default();
+
+        if let Some(time) = json.get("start_time") {
+            let time = time.as_str().ok_or("start_time should be an RFC 3339 string")?;
+            scenario.start_time = DateTime::parse_from_rfc3339(time)
+                .map_err(|e| format!("start_time: {}", e))?
+                .with_timezone(&Utc);
+        }
+        if let Some(speed) = json.get("speed") {
+            scenario.speed = speed.as_f64().ok_or("speed should be a number")?;
+        }
+        if let Some(paused) = json.get("paused") {
+            scenario.paused = paused.as_bool().ok_or("paused should be true or false")?;
+        }
+
+        // either key replaces the default catalogue
+        if json.contains_key("catalogues") || json.contains_key("tles") {
+            let sources = list(json, "catalogues")
+                .filter_map(|source| warn_skipped("catalogues", source.as_str().map(str::to_string)))
+                .collect();
+            let tles = |entry: &Value| {
+                let group = entry.get("group").and_then(Value::as_str).unwrap_or("scenario").to_string();
+                let lines: Vec<&str> = entry.get("lines")?.as_array()?.iter().map(Value::as_str).collect::<Option<_>>()?;
+                Some((group, lines.join("\n")))
+            };
+            let embedded = list(json, "tles").filter_map(|entry| warn_skipped("tles", tles(entry))).collect();
+            scenario.catalogue = Catalogue { sources, embedded };
+        }
+
+        scenario.selected = list(json, "selected").filter_map(|id| warn_skipped("selected", id.as_u64())).collect();
+        let station = |station: &Value| {
+            Some(GroundStation {
+                name: station.get("name")?.as_str()?.to_string(),
+                latitude: station.get("latitude")?.as_f64()? as f32,
+                longitude: station.get("longitude")?.as_f64()? as f32,
+            })
+        };
+        scenario.ground_stations = list(json, "ground_stations")
+            .filter_map(|entry| warn_skipped("ground_stations", station(entry)))
+            .collect();
+        scenario.bookmarks = list(json, "bookmarks").filter_map(|bookmark| warn_skipped("bookmarks", Bookmark::from_json(bookmark))).collect();
+
+        if let Some(camera) = json.get("camera") {
+            let mut camera = camera.clone();
+            if let Some(object) = camera.as_object_mut() {
+                object.entry("name").or_insert(json!("camera"));
+            }
+            scenario.camera = Some(Bookmark::from_json(&camera).ok_or("camera needs a position")?);
+        }
+
+        // layers and sliders, unknown names are ignored
+        for (name, value) in layers(&mut scenario.settings) {
+            if let Some(on) = json.get("layers").and_then(|layers| layers.get(name)).and_then(Value::as_bool) {
+                *value = on;
+            }
+        }
+        for (name, value) in sliders(&mut scenario.settings) {
+            if let Some(number) = json.get("settings").and_then(|settings| settings.get(name)).and_then(Value::as_f64) {
+                *value = number as f32;
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut settings = self.settings.clone();
+        let layers: Map<String, Value> = layers(&mut settings).into_iter().map(|(name, on)| (name.to_string(), json!(*on))).collect();
+        let sliders: Map<String, Value> = sliders(&mut settings).into_iter().map(|(name, value)| (name.to_string(), json!(decimal(*value)))).collect();
+
+        let mut json = json!({
+            "start_time": self.start_time.to_rfc3339(),
+            "speed": self.speed,
+            "paused": self.paused,
+            "catalogues": self.catalogue.sources,
+            "tles": self.catalogue.embedded.iter().map(|(group, text)| json!({
+                "group": group,
+                "lines": text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "selected": self.selected,
+            "ground_stations": self.ground_stations.iter().map(|station| json!({
+                "name": station.name,
+                "latitude": decimal(station.latitude),
+                "longitude": decimal(station.longitude),
+            })).collect::<Vec<_>>(),
+            "bookmarks": self.bookmarks.iter().map(Bookmark::to_json).collect::<Vec<_>>(),
+            "layers": layers,
+            "settings": sliders,
+        });
+        if let Some(camera) = &self.camera {
+            json["camera"] = camera.to_json();
+        }
+        json
+    }
+}
+
+/// entries of an optional array
+fn list<'a>(json: &'a Map<String, Value>, key: &str) -> impl Iterator<Item = &'a Value> {
+    json.get(key).and_then(Value::as_array).into_iter().flatten()
+}
+
+fn warn_skipped<T>(key: &str, value: Option<T>) -> Option<T> {
+    if value.is_none() {
+        warn!("Scenario: skipped an invalid entry in {}", key);
+    }
+    value
+}
+
+/// names of the layer toggles in the file
+fn layers(settings: &mut RenderSettings) -> [(&'static str, &mut bool); 10] {
+    [
+        ("atmosphere", &mut settings.atmosphere),
+        ("clouds", &mut settings.clouds),
+        ("night_lights", &mut settings.night_lights),
+        ("wireframe", &mut settings.wireframe),
+        ("orbits", &mut settings.orbits),
+        ("labels", &mut settings.labels),
+        ("coastlines", &mut settings.coastlines),
+        ("borders", &mut settings.borders),
+        ("cities", &mut settings.cities),
+        ("graticule", &mut settings.graticule),
+    ]
+}
+
+/// names of the sliders in the file
+fn sliders(settings: &mut RenderSettings) -> [(&'static str, &mut f32); 5] {
+    [
+        ("cloud_opacity", &mut settings.cloud_opacity),
+        ("sun_intensity", &mut settings.sun_intensity),
+        ("rayleigh_scale", &mut settings.rayleigh_scale),
+        ("orbit_alpha", &mut settings.orbit_alpha),
+        ("label_size", &mut settings.label_size),
+    ]
+}
+
+/// F5 saves, F8 reloads
+fn handle_actions(actions: Res<Actions>, mut commands: EventWriter<ScenarioCommand>) {
+    if actions.just_pressed(Action::SaveScenario) {
+        commands.write(ScenarioCommand::Save);
+    }
+    if actions.just_pressed(Action::LoadScenario) {
+        commands.write(ScenarioCommand::Load);
+    }
+}
+
+/// snapshot the session into the save file
+#[allow(clippy::too_many_arguments)]
+fn save(
+    mut commands: EventReader<ScenarioCommand>,
+    paths: Res<ScenarioPaths>,
+    time_state: Res<TimeState>,
+    catalogue: Res<Catalogue>,
+    selected: Res<SelectedSatellite>,
+    satellites: Query<&Satellite>,
+    stations: Res<GroundStations>,
+    bookmarks: Res<Bookmarks>,
+    settings: Res<RenderSettings>,
+    camera: Query<&OrbitCamera>,
+    earth: Query<&Transform, With<Earth>>,
+) {
+    if !commands.read().any(|command| *command == ScenarioCommand::Save) {
+        return;
+    }
+
+    let mut catalogue = catalogue.clone();
+    if SCENARIO_EMBED_TLES {
+        catalogue.sources.retain(|source| match std::fs::read_to_string(source) {
+            Ok(text) => {
+                catalogue.embedded.push((source_group(source).to_string(), text));
+                false
+            }
+            Err(e) => {
+                warn!("Could not embed {}, keeping its path: {}", source, e);
+                true
+            }
+        });
+    }
+
+    // the camera in the earth's frame, like a bookmark, the earth's turn comes from the start time
+    let earth = earth.single().ok();
+    let view = camera.single().ok().zip(earth).map(|(camera, earth)| {
+        let to_earth = earth.rotation.inverse();
+        Bookmark {
+            name: "camera".to_string(),
+            target: to_earth * (camera.target - earth.translation),
+            rotation: to_earth * camera.rotation,
+            radius: camera.radius,
+            sim_time: None,
+        }
+    });
+
+    let scenario = Scenario {
+        start_time: time_state.sim_time,
+        speed: time_state.speed_mult,
+        paused: time_state.is_paused,
+        catalogue,
+        selected: selected.0.and_then(|entity| satellites.get(entity).ok()).map(Satellite::norad_id).into_iter().collect(),
+        ground_stations: stations.0.clone(),
+        bookmarks: bookmarks.0.clone(),
+        camera: view,
+        settings: settings.clone(),
+    };
+    match scenario.save(&paths.save) {
+        Ok(()) => info!("Saved scenario to {}", paths.save),
+        Err(e) => error!("Could not save scenario to {}: {}", paths.save, e),
+    }
+}
+
+/// read the scenario file, it's applied by `apply`
+fn load(
+    mut commands: EventReader<ScenarioCommand>,
+    paths: Res<ScenarioPaths>,
+    mut pending: ResMut<PendingScenario>,
+) {
+    if !commands.read().any(|command| *command == ScenarioCommand::Load) {
+        return;
+    }
+
+    match Scenario::load(&paths.load) {
+        Ok(scenario) => {
+            info!("Loaded scenario {}", paths.load);
+            pending.0 = Some((scenario, true));
+        }
+        Err(e) => error!("Could not load scenario {}: {}", paths.load, e),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply(
+    mut commands: Commands,
+    mut pending: ResMut<PendingScenario>,
+    mut time_state: ResMut<TimeState>,
+    mut catalogue: ResMut<Catalogue>,
+    mut settings: ResMut<RenderSettings>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut stations: ResMut<GroundStations>,
+    mut selection: ResMut<PendingSelection>,
+    mut selected: ResMut<SelectedSatellite>,
+    satellites: Query<Entity, With<Satellite>>,
+    mut camera: Query<(&Transform, &mut OrbitCamera)>,
+    earth: Query<&Transform, (With<Earth>, Without<OrbitCamera>)>,
+    mut mode: ResMut<CameraModeState>,
+    mut fly_to: ResMut<FlyTo>,
+) {
+    let Some((scenario, respawn)) = pending.0.take() else { return; };
+
+    // new satellites, with orbit paths from the new start time
+    if respawn {
+        *time_state = scenario.time_state();
+        *catalogue = scenario.catalogue.clone();
+        for entity in &satellites {
+            commands.entity(entity).despawn();
+        }
+        satellites::spawn_catalogue(&mut commands, satellites::load_catalogue(&catalogue), time_state.sim_time);
+    }
+
+    *settings = scenario.settings.clone();
+    stations.0 = scenario.ground_stations.clone();
+    // bookmarks join the saved ones, replacing any with the same name
+    for bookmark in scenario.bookmarks {
+        match bookmarks.0.iter_mut().find(|existing| existing.name == bookmark.name) {
+            Some(existing) => *existing = bookmark,
+            None => bookmarks.0.push(bookmark),
+        }
+    }
+    selected.0 = None;
+    selection.0 = scenario.selected;
+
+    // the globe's turn follows the sim time, it may not have caught up with a new one yet
+    let Ok(earth) = earth.single() else { return; };
+    let rotation = earth_rotation(time_state.sim_time);
+    if let (Some(view), Ok((transform, mut camera))) = (scenario.camera, camera.single_mut()) {
+        fly_to.cancel();
+        if mode.mode != CameraMode::Orbit {
+            mode.switch(CameraMode::Orbit, transform);
+        }
+        camera.set_view(earth.translation + rotation * view.target, rotation * view.rotation, view.radius);
+    }
+}
+
+/// select the scenario's satellite once it has spawned
+fn select_pending(
+    mut selection: ResMut<PendingSelection>,
+    mut selected: ResMut<SelectedSatellite>,
+    satellites: Query<(Entity, &Satellite)>,
+) {
+    if selection.0.is_empty() || satellites.is_empty() {
+        return;
+    }
+
+    let found = selection.0.iter().find_map(|&id| {
+        satellites.iter().find(|(_, satellite)| satellite.norad_id() == id).map(|(entity, _)| entity)
+    });
+    if found.is_none() {
+        warn!("Scenario: none of the selected satellites {:?} are in the catalogue", selection.0);
+    }
+    selected.0 = found;
+    selection.0.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, position: Vec3, sim_time: Option<DateTime<Utc>>) -> Bookmark {
+        Bookmark {
+            name: name.to_string(),
+            target: Vec3::new(10.0, -20.0, 5.0),
+            rotation: Transform::from_translation(position).looking_at(Vec3::new(10.0, -20.0, 5.0), Vec3::Y).rotation,
+            radius: position.distance(Vec3::new(10.0, -20.0, 5.0)),
+            sim_time,
+        }
+    }
+
+    fn assert_same_view(a: &Bookmark, b: &Bookmark) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.sim_time, b.sim_time);
+        assert!(a.target.distance(b.target) < 1e-2, "{} target {} vs {}", a.name, a.target, b.target);
+        assert!((a.radius - b.radius).abs() < 1e-2, "{} radius {} vs {}", a.name, a.radius, b.radius);
+        assert!(a.rotation.angle_between(b.rotation) < 1e-3, "{} rotation {} vs {}", a.name, a.rotation, b.rotation);
+    }
+
+    /// through text, like a file
+    fn round_trip(scenario: &Scenario) -> Scenario {
+        let text = serde_json::to_string_pretty(&scenario.to_json()).unwrap();
+        Scenario::from_json(&serde_json::from_str(&text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn save_then_load_keeps_everything() {
+        let time = |text: &str| DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc);
+        let mut settings = RenderSettings::default();
+        for (_, on) in layers(&mut settings) {
+            *on = !*on;
+        }
+        for (i, (_, value)) in sliders(&mut settings).into_iter().enumerate() {
+            *value = 0.15 + i as f32 * 1.3;
+        }
+
+        let scenario = Scenario {
+            start_time: time("2024-06-01T08:30:15.250Z"),
+            speed: -120.5,
+            paused: true,
+            catalogue: Catalogue {
+                sources: vec!["assets/data/weather.txt".to_string(), "other/stations.tle".to_string()],
+                embedded: vec![(
+                    "mine".to_string(),
+                    "ISS (ZARYA)\n1 25544U 98067A   24001.50000000  .00016717  00000-0  30074-3 0  9991\n2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.50377579432838".to_string(),
+                )],
+            },
+            selected: vec![25544, 25338],
+            ground_stations: vec![
+                GroundStation { name: "Svalbard".to_string(), latitude: 78.23, longitude: 15.39 },
+                GroundStation { name: "McMurdo".to_string(), latitude: -77.84, longitude: 166.67 },
+            ],
+            bookmarks: vec![
+                bookmark("Over the pole", Vec3::new(0.5, 30000.0, 2.0), None),
+                bookmark("Launch", Vec3::new(4000.0, 3000.0, -9000.0), Some(time("2024-06-02T00:00:00Z"))),
+            ],
+            camera: Some(bookmark("camera", Vec3::new(487.2, 8810.8, 1770.0), None)),
+            settings,
+        };
+
+        let loaded = round_trip(&scenario);
+        assert_eq!(loaded.start_time, scenario.start_time);
+        assert_eq!(loaded.speed, scenario.speed);
+        assert_eq!(loaded.paused, scenario.paused);
+        assert_eq!(loaded.catalogue, scenario.catalogue);
+        assert_eq!(loaded.selected, scenario.selected);
+        assert_eq!(loaded.ground_stations, scenario.ground_stations);
+        assert_eq!(loaded.bookmarks.len(), scenario.bookmarks.len());
+        for (a, b) in loaded.bookmarks.iter().zip(&scenario.bookmarks) {
+            assert_same_view(a, b);
+        }
+        assert_same_view(loaded.camera.as_ref().unwrap(), scenario.camera.as_ref().unwrap());
+
+        let (mut expected, mut actual) = (scenario.settings.clone(), loaded.settings.clone());
+        for ((name, a), (_, b)) in layers(&mut actual).into_iter().zip(layers(&mut expected)) {
+            assert_eq!(*a, *b, "layer {name}");
+        }
+        for ((name, a), (_, b)) in sliders(&mut actual).into_iter().zip(sliders(&mut expected)) {
+            assert_eq!(*a, *b, "slider {name}");
+        }
+    }
+
+    #[test]
+    fn example_scenario_parses() {
+        let scenario = Scenario::load("scenarios/example.json").unwrap();
+        assert_eq!(scenario.speed, 60.0);
+        assert_eq!(scenario.catalogue.sources, vec!["assets/data/weather.txt"]);
+        assert_eq!(scenario.selected, vec![25338]);
+        assert_eq!(scenario.ground_stations.len(), 3);
+        assert_eq!(scenario.bookmarks.len(), 1);
+        assert!(scenario.camera.is_some());
+        assert!(scenario.settings.graticule && !scenario.settings.cities);
+        assert_eq!(scenario.settings.orbit_alpha, 0.15);
+    }
+
+    #[test]
+    fn malformed_values_are_errors() {
+        for json in [
+            json!({ "start_time": "yesterday" }),
+            json!({ "start_time": 1722686400 }),
+            json!({ "speed": "fast" }),
+            json!({ "speed": null }),
+            json!({ "paused": "no" }),
+            json!({ "camera": { "target": [0, 0, 0] } }),
+            json!([1, 2, 3]),
+        ] {
+            assert!(Scenario::from_json(&json).is_err(), "{json} should be rejected");
+        }
+
+        // missing keys keep the defaults
+        let empty = Scenario::from_json(&json!({})).unwrap();
+        assert_eq!(empty.catalogue, Catalogue::default());
+        assert_eq!(empty.speed, Scenario::default().speed);
+    }
+}
@@ -0,0 +1,70 @@
+//! settings.rs
+//!
+//! Runtime render settings
+//! everything the settings panel (ui.rs) can change lives in one resource,
+//! the systems that own each layer read from it
+
+use bevy::prelude::*;
+use bevy::pbr::wireframe::WireframeConfig;
+
+use crate::config::{CLOUD_OPACITY, ORBIT_ALPHA, SUN_INTENSITY};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderSettings>()
+            .add_systems(Update, apply_wireframe);
+    }
+}
+
+/// layer toggles and sliders
+#[derive(Resource, Clone, Debug)]
+pub struct RenderSettings {
+    pub atmosphere: bool,
+    pub clouds: bool,
+    pub night_lights: bool,
+    pub wireframe: bool,
+    pub orbits: bool,
+    pub labels: bool,
+    pub coastlines: bool,
+    pub borders: bool,
+    pub cities: bool,
+    pub graticule: bool,
+    pub cloud_opacity: f32,
+    pub sun_intensity: f32,  // atmosphere brightness
+    pub rayleigh_scale: f32, // multiplies RAYLEIGH_COEFF, higher is a thicker, bluer sky
+    pub orbit_alpha: f32,
+    pub label_size: f32, // font size
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            atmosphere: true,
+            clouds: true,
+            night_lights: true,
+            wireframe: false,
+            orbits: true,
+            labels: true,
+            coastlines: true,
+            borders: true,
+            cities: false,
+            graticule: false,
+            cloud_opacity: CLOUD_OPACITY,
+            sun_intensity: SUN_INTENSITY,
+            rayleigh_scale: 1.0,
+            orbit_alpha: ORBIT_ALPHA,
+            label_size: 8.0,
+        }
+    }
+}
+
+fn apply_wireframe(
+    settings: Res<RenderSettings>,
+    mut wireframe: ResMut<WireframeConfig>,
+) {
+    if settings.is_changed() {
+        wireframe.global = settings.wireframe;
+    }
+}
@@ -6,12 +6,14 @@
 use bevy::prelude::*;
 use chrono::Utc;
 
+use crate::systems::controls::{Action, Actions};
+
 pub struct TimePlugin;
 
 impl Plugin for TimePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(TimeState::default())
-           .add_systems(Update, update);
+           .add_systems(Update, (handle_actions, update.after(handle_actions)));
     }
 }
 
@@ -44,9 +46,9 @@ impl TimeState {
     //     self.speed_mult = speed.clamp(-4096.0, 4096.0);
     // }
 
-    // pub fn toggle_pause(&mut self) {
-    //     self.is_paused = !self.is_paused;
-    // }
+    pub fn toggle_pause(&mut self) {
+        self.is_paused = !self.is_paused;
+    }
 
     // decrease speed, or go negative
     pub fn step_backward(&mut self) {
@@ -80,7 +82,26 @@ impl TimeState {
     }
 }
 
-fn update(
+/// pause and speed from bound keys/buttons, same steps as the UI buttons
+fn handle_actions(
+    actions: Res<Actions>,
+    mut time_state: ResMut<TimeState>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        time_state.toggle_pause();
+    }
+    if actions.just_pressed(Action::TimeSlower) {
+        time_state.step_backward();
+    }
+    if actions.just_pressed(Action::TimeFaster) {
+        time_state.step_forward();
+    }
+    if actions.just_pressed(Action::TimeReset) {
+        time_state.reset_to_normal();
+    }
+}
+
+pub fn update(
     mut time_state: ResMut<TimeState>,
     time: Res<Time>
 ) {
@@ -88,9 +109,9 @@ fn update(
         let real_delta_seconds = time.delta_secs_f64();
         let sim_delta_seconds = real_delta_seconds * time_state.speed_mult;
         
-        // apply
+        // apply, to the nanosecond so fixed recording steps (1/30 s) aren't truncated
         if let Some(new_time) = time_state.sim_time.checked_add_signed(
-            chrono::Duration::milliseconds((sim_delta_seconds * 1000.0) as i64)
+            chrono::Duration::nanoseconds((sim_delta_seconds * 1e9).round() as i64)
         ) {
             time_state.sim_time = new_time;
         }
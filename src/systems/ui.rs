@@ -2,22 +2,41 @@
 //! 
 //! Simplistic UI implementation
 //! just has satellite count, datetime, and buttons for time control
+//! plus a settings panel (L) with layer toggles and sliders, see settings.rs,
+//! and scenario save/load buttons, see scenario.rs
 
 use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
 
-use crate::systems::satellites::Satellite;
+use crate::systems::earth::cursor::{format_lat_lon, CursorLocation};
+use crate::systems::satellites::{Satellite, SelectedSatellite};
+use crate::systems::settings::RenderSettings;
 use crate::systems::time::TimeState;
+use crate::systems::controls::{Action, Actions};
+use crate::systems::scenario::ScenarioCommand;
+
+const TOGGLE_ON_COLOR: Color = Color::srgba(0.2, 0.35, 0.5, 0.8);
+const TOGGLE_OFF_COLOR: Color = Color::srgba(0.2, 0.2, 0.2, 0.8);
 
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup)
+        app.add_systems(Startup, (setup, setup_settings_panel))
            .add_systems(Update, (
                 update_satellite_count, 
                 update_datetime, 
+                update_orbital_readout,
+                update_cursor_readout,
                 handle_time_control,
                 handle_exit,
+                toggle_settings_panel,
+                handle_setting_toggles,
+                handle_setting_sliders,
+                handle_scenario_buttons,
+                update_settings_panel
+                    .after(handle_setting_toggles)
+                    .after(handle_setting_sliders),
             ));
     }
 }
@@ -30,6 +49,14 @@ pub struct SatelliteCounter;
 #[derive(Component)]
 pub struct DateTimeDisplay;
 
+// UI component to display the selected satellite's orbital state
+#[derive(Component)]
+pub struct OrbitalReadout;
+
+// UI component to display the location under the cursor
+#[derive(Component)]
+pub struct CursorReadout;
+
 // time control button components
 #[derive(Component)]
 pub struct ResetButton;
@@ -40,12 +67,163 @@ pub struct BackwardButton;
 #[derive(Component)]
 pub struct ForwardButton;
 
+/// settings panel container, toggled with L
+#[derive(Component)]
+pub struct SettingsPanel;
+
+/// on/off button for a render layer
+#[derive(Component, Clone, Copy)]
+pub enum SettingToggle {
+    Atmosphere,
+    Clouds,
+    NightLights,
+    Wireframe,
+    Orbits,
+    Labels,
+    Coastlines,
+    Borders,
+    Cities,
+    Graticule,
+}
+
+impl SettingToggle {
+    const ALL: [Self; 10] = [
+        Self::Atmosphere,
+        Self::Clouds,
+        Self::NightLights,
+        Self::Wireframe,
+        Self::Orbits,
+        Self::Labels,
+        Self::Coastlines,
+        Self::Borders,
+        Self::Cities,
+        Self::Graticule,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Atmosphere => "Atmosphere",
+            Self::Clouds => "Clouds",
+            Self::NightLights => "Night lights",
+            Self::Wireframe => "Wireframe",
+            Self::Orbits => "Orbits",
+            Self::Labels => "Labels",
+            Self::Coastlines => "Coastlines",
+            Self::Borders => "Borders",
+            Self::Cities => "Cities",
+            Self::Graticule => "Lat/lon grid",
+        }
+    }
+
+    fn value(self, settings: &RenderSettings) -> bool {
+        match self {
+            Self::Atmosphere => settings.atmosphere,
+            Self::Clouds => settings.clouds,
+            Self::NightLights => settings.night_lights,
+            Self::Wireframe => settings.wireframe,
+            Self::Orbits => settings.orbits,
+            Self::Labels => settings.labels,
+            Self::Coastlines => settings.coastlines,
+            Self::Borders => settings.borders,
+            Self::Cities => settings.cities,
+            Self::Graticule => settings.graticule,
+        }
+    }
+
+    fn value_mut(self, settings: &mut RenderSettings) -> &mut bool {
+        match self {
+            Self::Atmosphere => &mut settings.atmosphere,
+            Self::Clouds => &mut settings.clouds,
+            Self::NightLights => &mut settings.night_lights,
+            Self::Wireframe => &mut settings.wireframe,
+            Self::Orbits => &mut settings.orbits,
+            Self::Labels => &mut settings.labels,
+            Self::Coastlines => &mut settings.coastlines,
+            Self::Borders => &mut settings.borders,
+            Self::Cities => &mut settings.cities,
+            Self::Graticule => &mut settings.graticule,
+        }
+    }
+}
+
+/// horizontal slider track, click or drag to set
+#[derive(Component, Clone, Copy)]
+pub enum SettingSlider {
+    CloudOpacity,
+    SunIntensity,
+    Rayleigh,
+    OrbitAlpha,
+    LabelSize,
+}
+
+impl SettingSlider {
+    const ALL: [Self; 5] = [Self::CloudOpacity, Self::SunIntensity, Self::Rayleigh, Self::OrbitAlpha, Self::LabelSize];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::CloudOpacity => "Cloud opacity",
+            Self::SunIntensity => "Atmosphere intensity",
+            Self::Rayleigh => "Rayleigh scattering",
+            Self::OrbitAlpha => "Orbit alpha",
+            Self::LabelSize => "Label size",
+        }
+    }
+
+    fn range(self) -> (f32, f32) {
+        match self {
+            Self::CloudOpacity => (0.0, 1.0),
+            Self::SunIntensity => (0.0, 30.0),
+            Self::Rayleigh => (0.0, 3.0),
+            Self::OrbitAlpha => (0.0, 0.5),
+            Self::LabelSize => (4.0, 20.0),
+        }
+    }
+
+    fn value(self, settings: &RenderSettings) -> f32 {
+        match self {
+            Self::CloudOpacity => settings.cloud_opacity,
+            Self::SunIntensity => settings.sun_intensity,
+            Self::Rayleigh => settings.rayleigh_scale,
+            Self::OrbitAlpha => settings.orbit_alpha,
+            Self::LabelSize => settings.label_size,
+        }
+    }
+
+    fn value_mut(self, settings: &mut RenderSettings) -> &mut f32 {
+        match self {
+            Self::CloudOpacity => &mut settings.cloud_opacity,
+            Self::SunIntensity => &mut settings.sun_intensity,
+            Self::Rayleigh => &mut settings.rayleigh_scale,
+            Self::OrbitAlpha => &mut settings.orbit_alpha,
+            Self::LabelSize => &mut settings.label_size,
+        }
+    }
+
+    /// slider position, 0..1
+    fn fraction(self, settings: &RenderSettings) -> f32 {
+        let (min, max) = self.range();
+        ((self.value(settings) - min) / (max - min)).clamp(0.0, 1.0)
+    }
+}
+
+/// save / reload the scenario file, see scenario.rs
+#[derive(Component, Clone, Copy)]
+pub struct ScenarioButton(ScenarioCommand);
+
+/// filled part of a slider track
+#[derive(Component)]
+pub struct SliderFill(SettingSlider);
+
+/// number next to a slider
+#[derive(Component)]
+pub struct SliderValue(SettingSlider);
+
 // system exit
 fn handle_exit(
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<Actions>,
     mut exit: EventWriter<AppExit>
 ) {
-    if keyboard.just_pressed(KeyCode::Escape) {
+    if actions.just_pressed(Action::Quit) {
         exit.write(AppExit::Success);
     }
 }
@@ -95,6 +273,36 @@ pub fn setup(
                 },
             ));
 
+            // selected satellite readout
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                OrbitalReadout,
+                Node {
+                    margin: UiRect::top(Val::Px(5.0)),
+                    ..default()
+                },
+            ));
+
+            // location under the cursor
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                CursorReadout,
+                Node {
+                    margin: UiRect::top(Val::Px(5.0)),
+                    ..default()
+                },
+            ));
+
             // time control buttons container
             parent.spawn((
                 Node {
@@ -189,12 +397,242 @@ pub fn setup(
         });
 }
 
+/// spawn the settings panel, hidden until L is pressed
+fn setup_settings_panel(
+    mut commands: Commands,
+    settings: Res<RenderSettings>,
+) {
+    let font = TextFont {
+        font_size: 12.0,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(20.0),
+                top: Val::Px(20.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            BorderRadius::all(Val::Px(4.0)),
+            Visibility::Hidden,
+            SettingsPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((Text::new("Layers (L)"), font.clone(), TextColor(Color::WHITE)));
+
+            // toggles
+            for toggle in SettingToggle::ALL {
+                let on = toggle.value(&settings);
+                panel.spawn((
+                    Button,
+                    Text::new(toggle_text(toggle, on)),
+                    font.clone(),
+                    TextColor(Color::WHITE),
+                    Node {
+                        padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                        ..default()
+                    },
+                    BackgroundColor(if on { TOGGLE_ON_COLOR } else { TOGGLE_OFF_COLOR }),
+                    BorderRadius::all(Val::Px(3.0)),
+                    toggle,
+                ));
+            }
+
+            // sliders, name above a track with its value
+            for slider in SettingSlider::ALL {
+                panel.spawn((
+                    Text::new(slider.name()),
+                    font.clone(),
+                    TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                    Node {
+                        margin: UiRect::top(Val::Px(4.0)),
+                        ..default()
+                    },
+                ));
+                panel
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(6.0),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Node {
+                                width: Val::Px(140.0),
+                                height: Val::Px(10.0),
+                                ..default()
+                            },
+                            BackgroundColor(TOGGLE_OFF_COLOR),
+                            BorderRadius::all(Val::Px(3.0)),
+                            Interaction::default(),
+                            RelativeCursorPosition::default(),
+                            slider,
+                        ))
+                        .with_children(|track| {
+                            track.spawn((
+                                Node {
+                                    width: Val::Percent(slider.fraction(&settings) * 100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                BackgroundColor(Color::srgba(0.5, 0.7, 0.9, 0.9)),
+                                BorderRadius::all(Val::Px(3.0)),
+                                SliderFill(slider),
+                            ));
+                        });
+                        row.spawn((
+                            Text::new(format!("{:.2}", slider.value(&settings))),
+                            font.clone(),
+                            TextColor(Color::WHITE),
+                            SliderValue(slider),
+                        ));
+                    });
+            }
+
+            // scenario file
+            panel.spawn((
+                Text::new("Scenario (F5 / F8)"),
+                font.clone(),
+                TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                Node {
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                },
+            ));
+            panel
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (label, command) in [("Save", ScenarioCommand::Save), ("Load", ScenarioCommand::Load)] {
+                        row.spawn((
+                            Button,
+                            Text::new(label),
+                            font.clone(),
+                            TextColor(Color::WHITE),
+                            Node {
+                                padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                                ..default()
+                            },
+                            BackgroundColor(TOGGLE_OFF_COLOR),
+                            BorderRadius::all(Val::Px(3.0)),
+                            ScenarioButton(command),
+                        ));
+                    }
+                });
+        });
+}
+
+fn toggle_text(toggle: SettingToggle, on: bool) -> String {
+    format!("{}: {}", toggle.name(), if on { "on" } else { "off" })
+}
+
+/// show/hide the settings panel with L
+fn toggle_settings_panel(
+    actions: Res<Actions>,
+    mut panel_query: Query<&mut Visibility, With<SettingsPanel>>,
+) {
+    if !actions.just_pressed(Action::SettingsPanel) {
+        return;
+    }
+
+    for mut visibility in &mut panel_query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// flip a layer when its button is clicked
+fn handle_setting_toggles(
+    mut settings: ResMut<RenderSettings>,
+    toggle_query: Query<(&Interaction, &SettingToggle), Changed<Interaction>>,
+) {
+    for (interaction, toggle) in &toggle_query {
+        if *interaction == Interaction::Pressed {
+            let value = toggle.value_mut(&mut settings);
+            *value = !*value;
+        }
+    }
+}
+
+/// set a slider's value from the cursor while it's held
+fn handle_setting_sliders(
+    mut settings: ResMut<RenderSettings>,
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &SettingSlider)>,
+) {
+    for (interaction, cursor, slider) in &slider_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(position) = cursor.normalized else { continue; };
+
+        // 0,0 is the track's top-left corner, dragging past either end clamps
+        let (min, max) = slider.range();
+        let new_value = min + position.x.clamp(0.0, 1.0) * (max - min);
+
+        // avoid flagging the settings as changed while the cursor sits still
+        if slider.value(&settings) != new_value {
+            *slider.value_mut(&mut settings) = new_value;
+        }
+    }
+}
+
+/// save or reload the scenario when its buttons are clicked
+fn handle_scenario_buttons(
+    button_query: Query<(&Interaction, &ScenarioButton), Changed<Interaction>>,
+    mut commands: EventWriter<ScenarioCommand>,
+) {
+    for (interaction, button) in &button_query {
+        if *interaction == Interaction::Pressed {
+            commands.write(button.0);
+        }
+    }
+}
+
+/// sync the panel with the settings, they can also change from keyboard shortcuts
+#[allow(clippy::type_complexity)]
+fn update_settings_panel(
+    settings: Res<RenderSettings>,
+    mut toggle_query: Query<(&SettingToggle, &mut Text, &mut BackgroundColor)>,
+    mut fill_query: Query<(&SliderFill, &mut Node)>,
+    mut value_query: Query<(&SliderValue, &mut Text), Without<SettingToggle>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (toggle, mut text, mut color) in &mut toggle_query {
+        let on = toggle.value(&settings);
+        text.0 = toggle_text(*toggle, on);
+        *color = BackgroundColor(if on { TOGGLE_ON_COLOR } else { TOGGLE_OFF_COLOR });
+    }
+
+    for (fill, mut node) in &mut fill_query {
+        node.width = Val::Percent(fill.0.fraction(&settings) * 100.0);
+    }
+
+    for (value, mut text) in &mut value_query {
+        text.0 = format!("{:.2}", value.0.value(&settings));
+    }
+}
+
 /// Handle time control button interactions
+#[allow(clippy::type_complexity)]
 fn handle_time_control(
     mut time_state: ResMut<TimeState>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (Changed<Interaction>, With<Button>, Without<SettingToggle>),
     >,
     backward_query: Query<&Interaction, (With<BackwardButton>, Changed<Interaction>)>,
     reset_query: Query<&Interaction, (With<ResetButton>, Changed<Interaction>)>,
@@ -211,24 +649,21 @@ fn handle_time_control(
     }
 
     // handle backward button
-    if let Ok(interaction) = backward_query.single() {
-        if *interaction == Interaction::Pressed {
-            time_state.step_backward();
-        }
+    if let Ok(interaction) = backward_query.single()
+        && *interaction == Interaction::Pressed {
+        time_state.step_backward();
     }
 
     // handle reset button
-    if let Ok(interaction) = reset_query.single() {
-        if *interaction == Interaction::Pressed {
-            time_state.reset_to_normal();
-        }
+    if let Ok(interaction) = reset_query.single()
+        && *interaction == Interaction::Pressed {
+        time_state.reset_to_normal();
     }
 
     // handle forward button
-    if let Ok(interaction) = forward_query.single() {
-        if *interaction == Interaction::Pressed {
-            time_state.step_forward();
-        }
+    if let Ok(interaction) = forward_query.single()
+        && *interaction == Interaction::Pressed {
+        time_state.step_forward();
     }
 }
 
@@ -256,4 +691,64 @@ fn update_datetime(
             time_state.speed_mult,
         );
     }
-}
\ No newline at end of file
+}
+
+/// update the orbital readout for the selected satellite (Tab to cycle)
+fn update_orbital_readout(
+    mut text_query: Query<&mut Text, With<OrbitalReadout>>,
+    selected: Res<SelectedSatellite>,
+    satellite_query: Query<&Satellite>,
+    time_state: Res<TimeState>,
+) {
+    let Ok(mut text) = text_query.single_mut() else { return; };
+
+    let Some(satellite) = selected.0.and_then(|entity| satellite_query.get(entity).ok()) else {
+        text.0 = "Tab: select satellite".to_string();
+        return;
+    };
+
+    let Some(state) = satellite.orbital_state(time_state.sim_time) else {
+        text.0 = format!("{}\nPropagation failed", satellite.name());
+        return;
+    };
+
+    let kepler = state.keplerian();
+    let (perigee, apogee) = kepler.perigee_apogee_altitude();
+    let period = kepler.period_m().map_or("-".to_string(), |p| format!("{:.1}min", p));
+
+    text.0 = format!(
+        "{}\nSpeed: {:.3}km/s  FPA: {:.2}°\nEnergy: {:.2}km²/s²\na: {:.0}km  e: {:.4}  i: {:.2}°\nRAAN: {:.2}°  ω: {:.2}°  ν: {:.2}°\nPerigee: {:.0}km  Apogee: {:.0}km  T: {}",
+        satellite.name(),
+        state.speed(),
+        state.flight_path_angle().to_degrees(),
+        state.specific_energy(),
+        kepler.semi_major_axis,
+        kepler.eccentricity,
+        kepler.inclination.to_degrees(),
+        kepler.raan.to_degrees(),
+        kepler.arg_of_perigee.to_degrees(),
+        kepler.true_anomaly.to_degrees(),
+        perigee,
+        apogee,
+        period,
+    );
+}
+
+/// update the location readout under the cursor
+fn update_cursor_readout(
+    mut text_query: Query<&mut Text, With<CursorReadout>>,
+    location: Res<CursorLocation>,
+    time_state: Res<TimeState>,
+) {
+    let Ok(mut text) = text_query.single_mut() else { return; };
+
+    text.0 = match &location.0 {
+        Some(point) => format!(
+            "Cursor: {}\nTerrain: {:.1}km  Solar time: {}",
+            format_lat_lon(point),
+            point.height,
+            point.solar_time(time_state.sim_time).format("%H:%M"),
+        ),
+        None => String::new(),
+    };
+}